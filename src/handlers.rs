@@ -1,19 +1,26 @@
+use crate::key_pattern::KeyPattern;
 use crate::models::*;
-use crate::scylladb::ScyllaDb;
-use crate::tree::build_tree;
+use crate::predicate::Predicate;
+use crate::store::KvStore;
+use crate::tree::{build_tree, json_patch};
+use crate::watch::{self, Subscription, Topic, WatchPosition, WatchTarget};
 use crate::AppState;
+use actix_web::http::header::{self, HeaderValue, HttpDate};
 use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use sha2::{Digest, Sha256};
 
 use std::collections::HashSet;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, UNIX_EPOCH};
 
 const THROTTLE_EXPIRY: Duration = Duration::from_secs(60);
 const MAX_THROTTLE_ENTRIES: usize = 50_000;
+/// Concurrent store lookups per batch request.
+pub(crate) const BATCH_CONCURRENCY: usize = 10;
 
-pub(crate) async fn require_db(state: &AppState) -> Result<Arc<ScyllaDb>, ApiError> {
+pub(crate) async fn require_db(state: &AppState) -> Result<Arc<dyn KvStore>, ApiError> {
     state
-        .scylladb
+        .store
         .read()
         .await
         .clone()
@@ -63,6 +70,83 @@ fn respond_paginated(
     }
 }
 
+/// Cache validators for a read: a weak ETag over the request's path and query,
+/// each entry's key, `block_height` and value, and the page's `has_more` and
+/// `next_cursor`, and `Last-Modified` from the newest `block_timestamp`.
+pub(crate) struct Validators {
+    etag: String,
+    last_modified: Option<HttpDate>,
+}
+
+impl Validators {
+    pub(crate) fn new<'a>(
+        req: &HttpRequest,
+        entries: impl IntoIterator<Item = &'a KvEntry>,
+        has_more: bool,
+        next_cursor: Option<&str>,
+    ) -> Self {
+        let mut hasher = Sha256::new()
+            .chain_update(req.path())
+            .chain_update(b"?")
+            .chain_update(req.query_string());
+        let (mut block_height, mut block_timestamp) = (0, 0);
+        for entry in entries {
+            block_height = block_height.max(entry.block_height);
+            block_timestamp = block_timestamp.max(entry.block_timestamp);
+            // Length-prefixed so adjacent fields can't run into each other
+            for field in [entry.key.as_bytes(), entry.value.as_bytes()] {
+                hasher.update((field.len() as u64).to_be_bytes());
+                hasher.update(field);
+            }
+            hasher.update(entry.block_height.to_be_bytes());
+        }
+        hasher.update([u8::from(has_more)]);
+        if let Some(cursor) = next_cursor {
+            hasher.update(cursor);
+        }
+        let digest = hasher.finalize();
+        Self {
+            etag: format!("W/\"{block_height}-{}\"", hex::encode(&digest[..8])),
+            last_modified: (block_timestamp > 0)
+                .then(|| HttpDate::from(UNIX_EPOCH + Duration::from_nanos(block_timestamp))),
+        }
+    }
+
+    /// `304 Not Modified` if `If-None-Match` lists this ETag, else `response()`;
+    /// either way with the validators attached.
+    pub(crate) fn respond(
+        &self,
+        req: &HttpRequest,
+        response: impl FnOnce() -> HttpResponse,
+    ) -> HttpResponse {
+        let mut response = if self.matches(req) {
+            HttpResponse::NotModified().finish()
+        } else {
+            response()
+        };
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            response.headers_mut().insert(header::ETAG, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            if let Ok(value) = HeaderValue::from_str(&last_modified.to_string()) {
+                response.headers_mut().insert(header::LAST_MODIFIED, value);
+            }
+        }
+        response
+    }
+
+    /// Weak comparison, as `If-None-Match` requires.
+    fn matches(&self, req: &HttpRequest) -> bool {
+        let ours = self.etag.trim_start_matches("W/");
+        req.headers()
+            .get_all(header::IF_NONE_MATCH)
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == ours)
+    }
+}
+
 pub(crate) fn validate_account_id(value: &str, name: &str) -> Result<(), ApiError> {
     if value.is_empty() {
         return Err(ApiError::InvalidParameter(format!(
@@ -144,6 +228,122 @@ fn validate_block_range(from_block: Option<i64>, to_block: Option<i64>) -> Resul
     Ok(())
 }
 
+/// Parse `from_time`/`to_time` into nanoseconds, rejecting inverted ranges.
+pub(crate) fn validate_time_range(
+    from_time: Option<&str>,
+    to_time: Option<&str>,
+) -> Result<(Option<u64>, Option<u64>), ApiError> {
+    let from = from_time
+        .map(|t| parse_time_param(t, "from_time"))
+        .transpose()?;
+    let to = to_time
+        .map(|t| parse_time_param(t, "to_time"))
+        .transpose()?;
+    if let (Some(from), Some(to)) = (from, to) {
+        if from > to {
+            return Err(ApiError::InvalidParameter(
+                "from_time: must be <= to_time".to_string(),
+            ));
+        }
+    }
+    Ok((from, to))
+}
+
+/// Block range for a request after applying `from_time`/`to_time`.
+pub(crate) struct BlockBounds {
+    pub from_block: Option<i64>,
+    pub to_block: Option<i64>,
+    /// Reported as `meta.resolved_range`; `None` when no time bound was given.
+    pub resolved: Option<ResolvedBlockRange>,
+}
+
+impl BlockBounds {
+    /// A time bound matched no write, or the resolved range does not overlap
+    /// the explicit block range. The store query can be skipped.
+    pub fn is_empty(&self) -> bool {
+        self.resolved.is_some_and(|r| r.empty)
+    }
+}
+
+/// Resolve time bounds to block heights within the writer's partition and
+/// intersect them with the explicit block range. Timestamps only exist on
+/// rows, so the resolved bounds are the first/last blocks this writer wrote
+/// in, which selects exactly the same rows as the wall-clock range.
+pub(crate) async fn resolve_time_range(
+    db: &dyn KvStore,
+    predecessor_id: &str,
+    current_account_id: &str,
+    (from_ns, to_ns): (Option<u64>, Option<u64>),
+    from_block: Option<i64>,
+    to_block: Option<i64>,
+) -> Result<BlockBounds, ApiError> {
+    if from_ns.is_none() && to_ns.is_none() {
+        return Ok(BlockBounds {
+            from_block,
+            to_block,
+            resolved: None,
+        });
+    }
+
+    let mut from_block = from_block;
+    let mut to_block = to_block;
+    let mut empty = false;
+    if let Some(ns) = from_ns {
+        match db
+            .first_block_at_or_after(predecessor_id, current_account_id, ns)
+            .await?
+        {
+            Some(b) => {
+                let b = i64::try_from(b).unwrap_or(i64::MAX);
+                from_block = Some(from_block.map_or(b, |f| f.max(b)));
+            }
+            None => empty = true,
+        }
+    }
+    if let Some(ns) = to_ns {
+        match db
+            .last_block_at_or_before(predecessor_id, current_account_id, ns)
+            .await?
+        {
+            Some(b) => {
+                let b = i64::try_from(b).unwrap_or(i64::MAX);
+                to_block = Some(to_block.map_or(b, |t| t.min(b)));
+            }
+            None => empty = true,
+        }
+    }
+    if let (Some(from), Some(to)) = (from_block, to_block) {
+        empty |= from > to;
+    }
+
+    let resolved = if empty {
+        ResolvedBlockRange {
+            empty,
+            ..Default::default()
+        }
+    } else {
+        ResolvedBlockRange {
+            from_block: from_block.map(bigint_to_u64),
+            to_block: to_block.map(bigint_to_u64),
+            empty,
+        }
+    };
+    Ok(BlockBounds {
+        from_block,
+        to_block,
+        resolved: Some(resolved),
+    })
+}
+
+fn validate_at_block(at_block: Option<i64>) -> Result<(), ApiError> {
+    if at_block.is_some_and(|v| v < 0) {
+        return Err(ApiError::InvalidParameter(
+            "at_block: cannot be negative".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 fn validate_prefix(prefix: &Option<String>) -> Result<(), ApiError> {
     if let Some(ref p) = prefix {
         if p.is_empty() {
@@ -160,10 +360,87 @@ fn validate_prefix(prefix: &Option<String>) -> Result<(), ApiError> {
     Ok(())
 }
 
+fn validate_where(where_clause: &Option<String>) -> Result<(), ApiError> {
+    if let Some(ref w) = where_clause {
+        if w.len() > MAX_PREDICATE_LENGTH {
+            return Err(ApiError::InvalidParameter(format!(
+                "where: cannot exceed {MAX_PREDICATE_LENGTH} characters"
+            )));
+        }
+        Predicate::parse(w).map_err(|e| ApiError::InvalidParameter(format!("where: {e}")))?;
+    }
+    Ok(())
+}
+
+fn validate_key_pattern(query: &QueryParams) -> Result<(), ApiError> {
+    let regex = match query.pattern_syntax.as_deref() {
+        None | Some("glob") => false,
+        Some("regex") => true,
+        Some(_) => {
+            return Err(ApiError::InvalidParameter(
+                "pattern_syntax: must be 'glob' or 'regex'".to_string(),
+            ))
+        }
+    };
+    let Some(ref pattern) = query.key_pattern else {
+        if query.pattern_syntax.is_some() {
+            return Err(ApiError::InvalidParameter(
+                "pattern_syntax: requires key_pattern".to_string(),
+            ));
+        }
+        return Ok(());
+    };
+    if query.key_prefix.is_some() {
+        return Err(ApiError::InvalidParameter(
+            "key_pattern: cannot be combined with key_prefix".to_string(),
+        ));
+    }
+    validate_key(pattern, "key_pattern", MAX_PREFIX_LENGTH)?;
+    KeyPattern::parse(pattern, regex)
+        .map_err(|e| ApiError::InvalidParameter(format!("key_pattern: {e}")))?;
+    Ok(())
+}
+
+fn validate_key_range(query: &QueryParams) -> Result<(), ApiError> {
+    for (bound, name) in [(&query.start_key, "start_key"), (&query.end_key, "end_key")] {
+        if let Some(ref key) = bound {
+            validate_key(key, name, MAX_KEY_LENGTH)?;
+        }
+    }
+    if let (Some(start), Some(end)) = (&query.start_key, &query.end_key) {
+        if start > end {
+            return Err(ApiError::InvalidParameter(
+                "start_key: cannot be after end_key".to_string(),
+            ));
+        }
+    }
+    if query.order.eq_ignore_ascii_case("desc") {
+        if query.after_key.is_some() {
+            return Err(ApiError::InvalidParameter(
+                "after_key: use before_key with order=desc".to_string(),
+            ));
+        }
+    } else if query.before_key.is_some() {
+        return Err(ApiError::InvalidParameter(
+            "before_key: requires order=desc".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn validate_tree_format(format: &Option<String>) -> Result<(), ApiError> {
+    if format.as_deref().is_some_and(|f| f != "tree") {
+        return Err(ApiError::InvalidParameter(
+            "format: must be 'tree' or omitted".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 /// Extract client IP from X-Forwarded-For (rightmost entry = added by Railway's proxy).
 /// Correct for a single trusted proxy hop. If a CDN is added in front, this would
 /// need to skip additional hops from the right.
-fn extract_client_ip(req: &HttpRequest) -> String {
+pub(crate) fn extract_client_ip(req: &HttpRequest) -> String {
     req.headers()
         .get("X-Forwarded-For")
         .and_then(|v| v.to_str().ok())
@@ -221,7 +498,7 @@ fn check_scan_throttle(app_state: &AppState, ip: &str) -> Result<(), ApiError> {
 )]
 #[get("/health")]
 pub async fn health_check(app_state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
-    let db = app_state.scylladb.read().await.clone();
+    let db = app_state.store.read().await.clone();
     match db.as_ref() {
         Some(db) => match db.health_check().await {
             Ok(_) => Ok(HttpResponse::Ok().json(HealthResponse {
@@ -259,43 +536,59 @@ pub async fn health_check(app_state: web::Data<AppState>) -> Result<HttpResponse
 pub async fn get_kv_handler(
     query: web::Query<GetParams>,
     app_state: web::Data<AppState>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ApiError> {
     validate_account_id(&query.predecessor_id, "accountId")?;
     validate_account_id(&query.current_account_id, "contractId")?;
     validate_key(&query.key, "key", MAX_KEY_LENGTH)?;
+    validate_at_block(query.at_block)?;
 
     tracing::info!(
         target: PROJECT_ID,
         accountId = %query.predecessor_id,
         contractId = %query.current_account_id,
         key = %query.key,
+        at_block = ?query.at_block,
         "GET /v1/kv/get"
     );
 
     let db = require_db(&app_state).await?;
-    let entry = db
-        .get_kv(&query.predecessor_id, &query.current_account_id, &query.key)
-        .await?;
+    let entry = match query.at_block {
+        Some(block_height) => {
+            db.get_kv_at_block(
+                &query.predecessor_id,
+                &query.current_account_id,
+                &query.key,
+                block_height,
+            )
+            .await?
+        }
+        None => {
+            db.get_kv(&query.predecessor_id, &query.current_account_id, &query.key)
+                .await?
+        }
+    };
 
     // Apply field selection and optional value decoding
     let fields = parse_field_set(&query.fields)?;
     let decode = should_decode(&query.value_format)?;
-    match entry {
+    let validators = Validators::new(&req, entry.iter(), false, None);
+    Ok(validators.respond(&req, || match entry {
         Some(entry) => {
             if fields.is_some() || decode {
                 let mut json = entry.to_json_with_fields(&fields);
                 if decode {
                     decode_value_in_json(&mut json);
                 }
-                Ok(HttpResponse::Ok().json(serde_json::json!({ "data": json })))
+                HttpResponse::Ok().json(serde_json::json!({ "data": json }))
             } else {
-                Ok(HttpResponse::Ok().json(DataResponse { data: Some(entry) }))
+                HttpResponse::Ok().json(DataResponse { data: Some(entry) })
             }
         }
-        None => Ok(HttpResponse::Ok().json(DataResponse {
+        None => HttpResponse::Ok().json(DataResponse {
             data: Option::<KvEntry>::None,
-        })),
-    }
+        }),
+    }))
 }
 
 /// Query KV entries with optional prefix filtering and pagination
@@ -314,26 +607,28 @@ pub async fn get_kv_handler(
 pub async fn query_kv_handler(
     query: web::Query<QueryParams>,
     app_state: web::Data<AppState>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ApiError> {
     validate_account_id(&query.predecessor_id, "accountId")?;
     validate_account_id(&query.current_account_id, "contractId")?;
     validate_limit(query.limit)?;
     validate_prefix(&query.key_prefix)?;
+    validate_at_block(query.at_block)?;
+    validate_where(&query.where_clause)?;
+    validate_key_pattern(&query)?;
+    validate_order(&query.order)?;
+    validate_key_range(&query)?;
 
-    validate_cursor_or_offset(
-        query.after_key.as_deref(),
-        "after_key",
-        query.offset,
-        |c, n| validate_key(c, n, MAX_KEY_LENGTH),
-    )?;
+    let (cursor, cursor_name) = if query.order.eq_ignore_ascii_case("desc") {
+        (query.before_key.as_deref(), "before_key")
+    } else {
+        (query.after_key.as_deref(), "after_key")
+    };
+    validate_cursor_or_offset(cursor, cursor_name, query.offset, |c, n| {
+        validate_key(c, n, MAX_KEY_LENGTH)
+    })?;
 
-    if let Some(ref fmt) = query.format {
-        if fmt != "tree" {
-            return Err(ApiError::InvalidParameter(
-                "format: must be 'tree' or omitted".to_string(),
-            ));
-        }
-    }
+    validate_tree_format(&query.format)?;
 
     tracing::info!(
         target: PROJECT_ID,
@@ -343,28 +638,44 @@ pub async fn query_kv_handler(
         limit = query.limit,
         offset = query.offset,
         after_key = ?query.after_key,
+        before_key = ?query.before_key,
+        order = %query.order,
+        start_key = ?query.start_key,
+        end_key = ?query.end_key,
+        at_block = ?query.at_block,
+        r#where = ?query.where_clause,
+        key_pattern = ?query.key_pattern,
         "GET /v1/kv/query"
     );
 
     let db = require_db(&app_state).await?;
-    let (entries, has_more, dropped) = db.query_kv_with_pagination(&query).await?;
+    let (entries, has_more, truncated, dropped, next_cursor) =
+        db.query_kv_with_pagination(&query).await?;
+    let validators = Validators::new(&req, &entries, has_more, next_cursor.as_deref());
 
     if query.format.as_deref() == Some("tree") {
-        let items: Vec<(String, String)> = entries.into_iter().map(|e| (e.key, e.value)).collect();
-        let tree = build_tree(&items);
-        return Ok(HttpResponse::Ok().json(TreeResponse { tree, has_more }));
+        return Ok(validators.respond(&req, || {
+            let items: Vec<(String, String)> =
+                entries.into_iter().map(|e| (e.key, e.value)).collect();
+            let tree = build_tree(&items);
+            HttpResponse::Ok().json(TreeResponse {
+                tree,
+                has_more,
+                next_cursor: next_cursor.filter(|_| has_more),
+            })
+        }));
     }
 
-    let next_cursor = entries.last().map(|e| e.key.clone());
     let meta = PaginationMeta {
         has_more,
-        truncated: false,
+        truncated,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
     let fields = parse_field_set(&query.fields)?;
     let decode = should_decode(&query.value_format)?;
-    Ok(respond_paginated(entries, meta, &fields, decode))
+    Ok(validators.respond(&req, || respond_paginated(entries, meta, &fields, decode)))
 }
 
 #[utoipa::path(
@@ -399,6 +710,10 @@ pub async fn history_kv_handler(
             parse_history_cursor(c)?;
         }
     }
+    if let Some(ref signer) = query.signer_id {
+        validate_account_id(signer, "signer_id")?;
+    }
+    let times = validate_time_range(query.from_time.as_deref(), query.to_time.as_deref())?;
 
     tracing::info!(
         target: PROJECT_ID,
@@ -410,17 +725,100 @@ pub async fn history_kv_handler(
         order = %query.order,
         from_block = ?query.from_block,
         to_block = ?query.to_block,
+        signer_id = ?query.signer_id,
+        from_time = ?query.from_time,
+        to_time = ?query.to_time,
         "GET /v1/kv/history"
     );
 
     let db = require_db(&app_state).await?;
-    let (entries, has_more, dropped, next_cursor) = db.get_kv_history(&query).await?;
+    let bounds = resolve_time_range(
+        db.as_ref(),
+        &query.predecessor_id,
+        &query.current_account_id,
+        times,
+        query.from_block,
+        query.to_block,
+    )
+    .await?;
+    let mut params = query.into_inner();
+    params.from_block = bounds.from_block;
+    params.to_block = bounds.to_block;
+    let (entries, has_more, truncated, dropped, next_cursor) = if bounds.is_empty() {
+        (Vec::new(), false, false, 0, None)
+    } else {
+        db.get_kv_history(&params).await?
+    };
+
+    let meta = PaginationMeta {
+        has_more,
+        truncated,
+        next_cursor,
+        dropped_rows: dropped_to_option(dropped),
+        resolved_range: bounds.resolved,
+    };
+    let fields = parse_field_set(&params.fields)?;
+    let decode = should_decode(&params.value_format)?;
+    Ok(respond_paginated(entries, meta, &fields, decode))
+}
+
+/// Every write under a key prefix within a block range
+#[utoipa::path(
+    get,
+    path = "/v1/kv/history/prefix",
+    params(PrefixHistoryParams),
+    responses(
+        (status = 200, description = "Block-ordered list of writes under the prefix", body = inline(PaginatedResponse<KvEntry>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/history/prefix")]
+pub async fn history_prefix_kv_handler(
+    query: web::Query<PrefixHistoryParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&query.predecessor_id, "accountId")?;
+    validate_account_id(&query.current_account_id, "contractId")?;
+    validate_key(&query.key_prefix, "key_prefix", MAX_PREFIX_LENGTH)?;
+    validate_limit(query.limit)?;
+    validate_order(&query.order)?;
+    validate_block_range(query.from_block, query.to_block)?;
+    if let Some(ref c) = query.cursor {
+        if c.len() > MAX_CURSOR_LENGTH {
+            return Err(ApiError::InvalidParameter(
+                "cursor: exceeds max length".to_string(),
+            ));
+        }
+        if !c.is_empty() {
+            parse_timeline_cursor(c)?;
+        }
+    }
+
+    tracing::info!(
+        target: PROJECT_ID,
+        accountId = %query.predecessor_id,
+        contractId = %query.current_account_id,
+        key_prefix = %query.key_prefix,
+        limit = query.limit,
+        cursor = ?query.cursor,
+        order = %query.order,
+        from_block = ?query.from_block,
+        to_block = ?query.to_block,
+        "GET /v1/kv/history/prefix"
+    );
+
+    let db = require_db(&app_state).await?;
+    let (entries, has_more, truncated, dropped, next_cursor) =
+        db.get_kv_prefix_history(&query).await?;
 
     let meta = PaginationMeta {
         has_more,
-        truncated: false,
+        truncated,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
     let fields = parse_field_set(&query.fields)?;
     let decode = should_decode(&query.value_format)?;
@@ -450,6 +848,7 @@ pub async fn writers_handler(
     if let Some(ref pred) = query.predecessor_id {
         validate_account_id(pred, "accountId")?;
     }
+    validate_where(&query.where_clause)?;
 
     validate_cursor_or_offset(
         query.after_account.as_deref(),
@@ -466,18 +865,19 @@ pub async fn writers_handler(
         limit = query.limit,
         offset = query.offset,
         after_account = ?query.after_account,
+        r#where = ?query.where_clause,
         "GET /v1/kv/writers"
     );
 
     let db = require_db(&app_state).await?;
-    let (entries, has_more, truncated, dropped) = db.query_writers(&query).await?;
+    let (entries, has_more, truncated, dropped, next_cursor) = db.query_writers(&query).await?;
 
-    let next_cursor = entries.last().map(|e| e.predecessor_id.clone());
     let meta = PaginationMeta {
         has_more,
         truncated,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
     let fields = parse_field_set(&query.fields)?;
     let decode = should_decode(&query.value_format)?;
@@ -586,6 +986,7 @@ pub async fn accounts_handler(
         truncated,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
 
     Ok(HttpResponse::Ok().json(PaginatedResponse {
@@ -656,6 +1057,7 @@ pub async fn contracts_handler(
         truncated: false,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
 
     Ok(HttpResponse::Ok().json(PaginatedResponse {
@@ -738,6 +1140,158 @@ pub async fn diff_kv_handler(
     }
 }
 
+/// Added, removed and changed keys under a prefix between two block heights
+#[utoipa::path(
+    get,
+    path = "/v1/kv/diff/tree",
+    params(DiffTreeParams),
+    responses(
+        (status = 200, description = "Key-level diff (list), nested trees (format=tree) or JSON Patch operations (format=patch)", body = inline(DataResponse<TreeDiff>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/diff/tree")]
+pub async fn diff_tree_kv_handler(
+    query: web::Query<DiffTreeParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&query.predecessor_id, "accountId")?;
+    validate_account_id(&query.current_account_id, "contractId")?;
+    validate_key(&query.key_prefix, "key_prefix", MAX_PREFIX_LENGTH)?;
+    if query.block_height_a < 0 || query.block_height_b < 0 {
+        return Err(ApiError::InvalidParameter(
+            "block_height_a/block_height_b: must be non-negative".to_string(),
+        ));
+    }
+    if let Some(ref c) = query.after_key {
+        validate_key(c, "after_key", MAX_KEY_LENGTH)?;
+    }
+    if let Some(ref fmt) = query.format {
+        if fmt != "tree" && fmt != "patch" {
+            return Err(ApiError::InvalidParameter(
+                "format: must be 'tree', 'patch' or omitted".to_string(),
+            ));
+        }
+    }
+    let decode = should_decode(&query.value_format)?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        accountId = %query.predecessor_id,
+        contractId = %query.current_account_id,
+        key_prefix = %query.key_prefix,
+        block_height_a = query.block_height_a,
+        block_height_b = query.block_height_b,
+        after_key = ?query.after_key,
+        format = ?query.format,
+        "GET /v1/kv/diff/tree"
+    );
+
+    let db = require_db(&app_state).await?;
+    let snapshot = |block_height| QueryParams {
+        predecessor_id: query.predecessor_id.clone(),
+        current_account_id: query.current_account_id.clone(),
+        key_prefix: Some(query.key_prefix.clone()),
+        exclude_deleted: Some(true),
+        limit: MAX_DIFF_TREE_KEYS,
+        offset: 0,
+        fields: None,
+        format: None,
+        value_format: None,
+        after_key: query.after_key.clone(),
+        at_block: Some(block_height),
+        ..Default::default()
+    };
+    let (params_a, params_b) = (
+        snapshot(query.block_height_a),
+        snapshot(query.block_height_b),
+    );
+    let (
+        (mut a, more_a, truncated_a, dropped_a, cursor_a),
+        (mut b, more_b, truncated_b, dropped_b, cursor_b),
+    ) = futures::future::try_join(
+        db.query_kv_with_pagination(&params_a),
+        db.query_kv_with_pagination(&params_b),
+    )
+    .await?;
+
+    // A capped side only covers keys up to its cursor; compare the range
+    // both sides fully cover and resume from there.
+    let boundary = [(more_a, cursor_a), (more_b, cursor_b)]
+        .into_iter()
+        .filter_map(|(more, cursor)| cursor.filter(|_| more))
+        .min();
+    if let Some(ref last) = boundary {
+        a.retain(|e| e.key <= *last);
+        b.retain(|e| e.key <= *last);
+    }
+    let has_more = more_a || more_b;
+    let truncated = truncated_a || truncated_b;
+
+    if query.format.is_some() {
+        let items = |entries: &[KvEntry]| -> Vec<(String, String)> {
+            entries
+                .iter()
+                .map(|e| (e.key.clone(), e.value.clone()))
+                .collect()
+        };
+        let (tree_a, tree_b) = (build_tree(&items(&a)), build_tree(&items(&b)));
+        if query.format.as_deref() == Some("patch") {
+            let meta = PaginationMeta {
+                has_more,
+                truncated,
+                next_cursor: boundary,
+                dropped_rows: dropped_to_option(dropped_a + dropped_b),
+                ..Default::default()
+            };
+            return Ok(HttpResponse::Ok().json(serde_json::json!({
+                "data": json_patch(&tree_a, &tree_b),
+                "meta": meta,
+            })));
+        }
+        let diff = TreeDiff::between(a, b);
+        let tree = serde_json::json!({
+            "added": build_tree(&items(&diff.added)),
+            "removed": build_tree(&items(&diff.removed)),
+            "changed": build_tree(
+                &diff.changed.iter().map(|c| (c.key.clone(), c.b.value.clone())).collect::<Vec<_>>()
+            ),
+        });
+        return Ok(HttpResponse::Ok().json(TreeResponse {
+            tree,
+            has_more,
+            next_cursor: boundary,
+        }));
+    }
+
+    let diff = TreeDiff::between(a, b);
+    let meta = PaginationMeta {
+        has_more,
+        truncated,
+        next_cursor: boundary,
+        dropped_rows: dropped_to_option(dropped_a + dropped_b),
+        ..Default::default()
+    };
+    if decode {
+        let mut data = serde_json::to_value(&diff).map_err(|e| anyhow::anyhow!(e))?;
+        for list in ["added", "removed"] {
+            if let Some(entries) = data[list].as_array_mut() {
+                entries.iter_mut().for_each(decode_value_in_json);
+            }
+        }
+        if let Some(changes) = data["changed"].as_array_mut() {
+            for change in changes {
+                decode_value_in_json(&mut change["a"]);
+                decode_value_in_json(&mut change["b"]);
+            }
+        }
+        return Ok(HttpResponse::Ok().json(serde_json::json!({ "data": data, "meta": meta })));
+    }
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "data": diff, "meta": meta })))
+}
+
 #[utoipa::path(
     get,
     path = "/v1/kv/timeline",
@@ -769,6 +1323,7 @@ pub async fn timeline_kv_handler(
             parse_timeline_cursor(c)?;
         }
     }
+    let times = validate_time_range(query.from_time.as_deref(), query.to_time.as_deref())?;
 
     tracing::info!(
         target: PROJECT_ID,
@@ -779,52 +1334,291 @@ pub async fn timeline_kv_handler(
         order = %query.order,
         from_block = ?query.from_block,
         to_block = ?query.to_block,
+        from_time = ?query.from_time,
+        to_time = ?query.to_time,
         "GET /v1/kv/timeline"
     );
 
     let db = require_db(&app_state).await?;
-    let (entries, has_more, dropped, next_cursor) = db.get_kv_timeline(&query).await?;
+    let bounds = resolve_time_range(
+        db.as_ref(),
+        &query.predecessor_id,
+        &query.current_account_id,
+        times,
+        query.from_block,
+        query.to_block,
+    )
+    .await?;
+    let mut params = query.into_inner();
+    params.from_block = bounds.from_block;
+    params.to_block = bounds.to_block;
+    let (entries, has_more, dropped, next_cursor) = if bounds.is_empty() {
+        (Vec::new(), false, 0, None)
+    } else {
+        db.get_kv_timeline(&params).await?
+    };
 
     let meta = PaginationMeta {
         has_more,
         truncated: false,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        resolved_range: bounds.resolved,
     };
-    let fields = parse_field_set(&query.fields)?;
-    let decode = should_decode(&query.value_format)?;
+    let fields = parse_field_set(&params.fields)?;
+    let decode = should_decode(&params.value_format)?;
     Ok(respond_paginated(entries, meta, &fields, decode))
 }
 
-/// Batch lookup: get values for multiple keys in a single request
+/// Every write by one account across all contracts it has touched, merged in block order
 #[utoipa::path(
-    post,
-    path = "/v1/kv/batch",
-    request_body = BatchQuery,
+    get,
+    path = "/v1/account/timeline",
+    params(AccountTimelineParams),
     responses(
-        (status = 200, description = "Batch results", body = inline(DataResponse<Vec<BatchResultItem>>)),
+        (status = 200, description = "Block-ordered writes across contracts", body = inline(PaginatedResponse<KvEntry>)),
         (status = 400, description = "Invalid parameters", body = ErrorResponse),
         (status = 503, description = "Database unavailable", body = ErrorResponse),
     ),
     tag = "kv"
 )]
-#[post("/v1/kv/batch")]
-pub async fn batch_kv_handler(
-    body: web::Json<BatchQuery>,
+#[get("/v1/account/timeline")]
+pub async fn account_timeline_handler(
+    query: web::Query<AccountTimelineParams>,
     app_state: web::Data<AppState>,
 ) -> Result<HttpResponse, ApiError> {
-    validate_account_id(&body.predecessor_id, "accountId")?;
-    validate_account_id(&body.current_account_id, "contractId")?;
-    if body.keys.is_empty() {
-        return Err(ApiError::InvalidParameter(
-            "keys: cannot be empty".to_string(),
-        ));
-    }
+    use futures::stream::{self, StreamExt, TryStreamExt};
+
+    validate_account_id(&query.predecessor_id, "accountId")?;
+    validate_limit(query.limit)?;
+    validate_order(&query.order)?;
+    validate_block_range(query.from_block, query.to_block)?;
+    let cursor = match query.cursor.as_deref() {
+        Some(c) if c.len() > MAX_CURSOR_LENGTH => {
+            return Err(ApiError::InvalidParameter(
+                "cursor: exceeds max length".to_string(),
+            ));
+        }
+        Some(c) if !c.is_empty() => Some(parse_account_timeline_cursor(c)?),
+        _ => None,
+    };
+
+    tracing::info!(
+        target: PROJECT_ID,
+        accountId = %query.predecessor_id,
+        limit = query.limit,
+        cursor = ?query.cursor,
+        order = %query.order,
+        from_block = ?query.from_block,
+        to_block = ?query.to_block,
+        "GET /v1/account/timeline"
+    );
+
+    let db = require_db(&app_state).await?;
+    let (contracts, capped, dropped_contracts) = db
+        .query_contracts_by_account(&query.predecessor_id, MAX_TIMELINE_CONTRACTS, None)
+        .await?;
+
+    let is_asc = query.order.eq_ignore_ascii_case("asc");
+    let per_contract: Vec<TimelineParams> = contracts
+        .into_iter()
+        .filter_map(|contract| contract_timeline_params(&query, contract, cursor.as_ref(), is_asc))
+        .collect();
+    let pages: Vec<_> = stream::iter(per_contract.into_iter().map(|params| {
+        let db = db.clone();
+        async move { db.get_kv_timeline(&params).await }
+    }))
+    .buffered(BATCH_CONCURRENCY)
+    .try_collect()
+    .await?;
+
+    let mut has_more = false;
+    let mut dropped = dropped_contracts;
+    let mut entries = Vec::new();
+    for (page, page_has_more, page_dropped, _) in pages {
+        has_more |= page_has_more;
+        dropped += page_dropped;
+        entries.extend(page);
+    }
+    // Same tie-breaks as a single-contract timeline: desc is (block DESC,
+    // contract ASC, key ASC) and asc is its exact reverse.
+    entries.sort_by(|a, b| {
+        b.block_height
+            .cmp(&a.block_height)
+            .then_with(|| a.current_account_id.cmp(&b.current_account_id))
+            .then_with(|| a.key.cmp(&b.key))
+    });
+    if is_asc {
+        entries.reverse();
+    }
+    if entries.len() > query.limit {
+        entries.truncate(query.limit);
+        has_more = true;
+    }
+    let next_cursor = if has_more {
+        entries
+            .last()
+            .map(|e| format!("{}:{}:{}", e.block_height, e.current_account_id, e.key))
+    } else {
+        None
+    };
+
+    let meta = PaginationMeta {
+        has_more,
+        truncated: capped,
+        next_cursor,
+        dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
+    };
+    let fields = parse_field_set(&query.fields)?;
+    let decode = should_decode(&query.value_format)?;
+    Ok(respond_paginated(entries, meta, &fields, decode))
+}
+
+/// Narrows one contract's timeline so it only yields entries past the merged
+/// cursor; `None` when nothing in that contract can follow it.
+fn contract_timeline_params(
+    query: &AccountTimelineParams,
+    contract: String,
+    cursor: Option<&(i64, String, String)>,
+    is_asc: bool,
+) -> Option<TimelineParams> {
+    let mut from_block = query.from_block;
+    let mut to_block = query.to_block;
+    let mut contract_cursor = None;
+    if let Some((cb, cc, ck)) = cursor {
+        match (contract.cmp(cc), is_asc) {
+            (std::cmp::Ordering::Equal, _) => contract_cursor = Some(format!("{cb}:{ck}")),
+            // Within a block, contracts that come after the cursor's in page order
+            // still have entries at the cursor's block; earlier ones do not.
+            (std::cmp::Ordering::Greater, false) => {
+                to_block = Some(to_block.map_or(*cb, |t| t.min(*cb)))
+            }
+            (std::cmp::Ordering::Less, false) => {
+                to_block = Some(to_block.map_or(cb - 1, |t| t.min(cb - 1)))
+            }
+            (std::cmp::Ordering::Less, true) => {
+                from_block = Some(from_block.map_or(*cb, |f| f.max(*cb)))
+            }
+            (std::cmp::Ordering::Greater, true) => {
+                from_block = Some(from_block.map_or(cb + 1, |f| f.max(cb + 1)))
+            }
+        }
+    }
+    if matches!((from_block, to_block), (Some(f), Some(t)) if f > t)
+        || matches!(to_block, Some(t) if t < 0)
+    {
+        return None;
+    }
+    Some(TimelineParams {
+        predecessor_id: query.predecessor_id.clone(),
+        current_account_id: contract,
+        limit: query.limit,
+        order: query.order.clone(),
+        from_block,
+        to_block,
+        fields: None,
+        value_format: None,
+        cursor: contract_cursor,
+        from_time: None,
+        to_time: None,
+    })
+}
+
+/// Every write to one contract across all writers within a block range
+#[utoipa::path(
+    get,
+    path = "/v1/kv/contract/timeline",
+    params(ContractTimelineParams),
+    responses(
+        (status = 200, description = "Block-ordered writes to the contract", body = inline(PaginatedResponse<KvEntry>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 501, description = "s_kv_by_contract not deployed", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/contract/timeline")]
+pub async fn contract_timeline_handler(
+    query: web::Query<ContractTimelineParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&query.current_account_id, "contractId")?;
+    validate_limit(query.limit)?;
+    validate_order(&query.order)?;
+    validate_block_range(Some(query.from_block), Some(query.to_block))?;
+    if query.to_block - query.from_block >= MAX_CONTRACT_TIMELINE_SPAN {
+        return Err(ApiError::InvalidParameter(format!(
+            "to_block: range cannot exceed {MAX_CONTRACT_TIMELINE_SPAN} blocks"
+        )));
+    }
+    if let Some(ref c) = query.cursor {
+        if c.len() > MAX_CURSOR_LENGTH {
+            return Err(ApiError::InvalidParameter(
+                "cursor: exceeds max length".to_string(),
+            ));
+        }
+        if !c.is_empty() {
+            parse_contract_timeline_cursor(c)?;
+        }
+    }
+
+    tracing::info!(
+        target: PROJECT_ID,
+        contractId = %query.current_account_id,
+        limit = query.limit,
+        cursor = ?query.cursor,
+        order = %query.order,
+        from_block = query.from_block,
+        to_block = query.to_block,
+        "GET /v1/kv/contract/timeline"
+    );
+
+    let db = require_db(&app_state).await?;
+    let (entries, has_more, dropped, next_cursor) = db.get_contract_timeline(&query).await?;
+
+    let meta = PaginationMeta {
+        has_more,
+        truncated: false,
+        next_cursor,
+        dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
+    };
+    let fields = parse_field_set(&query.fields)?;
+    let decode = should_decode(&query.value_format)?;
+    Ok(respond_paginated(entries, meta, &fields, decode))
+}
+
+/// Batch lookup: get values for multiple keys in a single request
+#[utoipa::path(
+    post,
+    path = "/v1/kv/batch",
+    request_body = BatchQuery,
+    responses(
+        (status = 200, description = "Batch results", body = inline(DataResponse<Vec<BatchResultItem>>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[post("/v1/kv/batch")]
+pub async fn batch_kv_handler(
+    body: web::Json<BatchQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&body.predecessor_id, "accountId")?;
+    validate_account_id(&body.current_account_id, "contractId")?;
+    if body.keys.is_empty() {
+        return Err(ApiError::InvalidParameter(
+            "keys: cannot be empty".to_string(),
+        ));
+    }
     if body.keys.len() > MAX_BATCH_KEYS {
         return Err(ApiError::InvalidParameter(format!(
             "keys: cannot exceed {MAX_BATCH_KEYS} items"
         )));
     }
+    validate_at_block(body.at_block)?;
     for key in &body.keys {
         if key.is_empty() {
             return Err(ApiError::InvalidParameter(
@@ -843,6 +1637,7 @@ pub async fn batch_kv_handler(
         accountId = %body.predecessor_id,
         contractId = %body.current_account_id,
         key_count = body.keys.len(),
+        at_block = ?body.at_block,
         "POST /v1/kv/batch"
     );
 
@@ -851,12 +1646,13 @@ pub async fn batch_kv_handler(
 
     use futures::stream::{self, StreamExt};
     let items: Vec<BatchResultItem> = stream::iter(body.keys.iter().map(|key| {
-        let scylladb = app_state.scylladb.clone();
+        let store = app_state.store.clone();
         let predecessor_id = body.predecessor_id.clone();
         let current_account_id = body.current_account_id.clone();
         let key = key.clone();
+        let at_block = body.at_block;
         async move {
-            let db = scylladb.read().await.clone();
+            let db = store.read().await.clone();
             let Some(ref db) = db else {
                 return BatchResultItem {
                     key,
@@ -865,7 +1661,14 @@ pub async fn batch_kv_handler(
                     error: Some("Database unavailable".to_string()),
                 };
             };
-            match db.get_kv_last(&predecessor_id, &current_account_id, &key).await {
+            let lookup = match at_block {
+                Some(block_height) => db
+                    .get_kv_at_block(&predecessor_id, &current_account_id, &key, block_height)
+                    .await
+                    .map(|entry| entry.map(|e| e.value)),
+                None => db.get_kv_last(&predecessor_id, &current_account_id, &key).await,
+            };
+            match lookup {
                 Ok(value) => BatchResultItem {
                     key,
                     found: value.is_some(),
@@ -885,7 +1688,255 @@ pub async fn batch_kv_handler(
             }
         }
     }))
-    .buffered(10)
+    .buffered(BATCH_CONCURRENCY)
+    .collect()
+    .await;
+
+    Ok(HttpResponse::Ok().json(DataResponse { data: items }))
+}
+
+/// Batch lookup across writers and contracts: one entry per `(accountId, contractId, key)`
+#[utoipa::path(
+    post,
+    path = "/v1/kv/batch/multi",
+    request_body = MultiBatchQuery,
+    responses(
+        (status = 200, description = "Batch results in request order", body = inline(DataResponse<Vec<MultiBatchResultItem>>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[post("/v1/kv/batch/multi")]
+pub async fn batch_multi_kv_handler(
+    body: web::Json<MultiBatchQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if body.items.is_empty() {
+        return Err(ApiError::InvalidParameter(
+            "items: cannot be empty".to_string(),
+        ));
+    }
+    if body.items.len() > MAX_BATCH_KEYS {
+        return Err(ApiError::InvalidParameter(format!(
+            "items: cannot exceed {MAX_BATCH_KEYS} items"
+        )));
+    }
+    validate_at_block(body.at_block)?;
+    for item in &body.items {
+        validate_account_id(&item.predecessor_id, "items[].accountId")?;
+        validate_account_id(&item.current_account_id, "items[].contractId")?;
+        validate_key(&item.key, "items[].key", MAX_BATCH_KEY_LENGTH)?;
+    }
+    let fields = parse_field_set(&body.fields)?;
+    let decode = should_decode(&body.value_format)?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        item_count = body.items.len(),
+        at_block = ?body.at_block,
+        "POST /v1/kv/batch/multi"
+    );
+
+    // Verify DB is available before starting batch
+    let _ = require_db(&app_state).await?;
+
+    use futures::stream::{self, StreamExt};
+    let items: Vec<MultiBatchResultItem> = stream::iter(body.items.iter().map(|item| {
+        let store = app_state.store.clone();
+        let item = item.clone();
+        let at_block = body.at_block;
+        let fields = &fields;
+        async move {
+            let db = store.read().await.clone();
+            let Some(ref db) = db else {
+                return MultiBatchResultItem {
+                    item,
+                    found: false,
+                    entry: None,
+                    error: Some("Database unavailable".to_string()),
+                };
+            };
+            let lookup = match at_block {
+                Some(block_height) => {
+                    db.get_kv_at_block(
+                        &item.predecessor_id,
+                        &item.current_account_id,
+                        &item.key,
+                        block_height,
+                    )
+                    .await
+                }
+                None => {
+                    db.get_kv(&item.predecessor_id, &item.current_account_id, &item.key)
+                        .await
+                }
+            };
+            match lookup {
+                Ok(entry) => MultiBatchResultItem {
+                    item,
+                    found: entry.is_some(),
+                    entry: entry.map(|e| {
+                        let mut json = e.to_json_with_fields(fields);
+                        if decode {
+                            decode_value_in_json(&mut json);
+                        }
+                        json
+                    }),
+                    error: None,
+                },
+                Err(e) => {
+                    // Log full error internally, return generic message to client
+                    tracing::warn!(target: PROJECT_ID, error = %e, key = %item.key, "Batch key lookup failed");
+                    MultiBatchResultItem {
+                        item,
+                        found: false,
+                        entry: None,
+                        error: Some("Lookup failed".to_string()),
+                    }
+                }
+            }
+        }
+    }))
+    .buffered(BATCH_CONCURRENCY)
+    .collect()
+    .await;
+
+    Ok(HttpResponse::Ok().json(DataResponse { data: items }))
+}
+
+/// Batch prefix query: several `(accountId, contractId, key_prefix)` scans in one request
+#[utoipa::path(
+    post,
+    path = "/v1/kv/query/batch",
+    request_body = PrefixBatchQuery,
+    responses(
+        (status = 200, description = "Per-item results in request order", body = inline(DataResponse<Vec<PrefixBatchResultItem>>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[post("/v1/kv/query/batch")]
+pub async fn query_batch_kv_handler(
+    body: web::Json<PrefixBatchQuery>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if body.items.is_empty() {
+        return Err(ApiError::InvalidParameter(
+            "items: cannot be empty".to_string(),
+        ));
+    }
+    if body.items.len() > MAX_BATCH_QUERIES {
+        return Err(ApiError::InvalidParameter(format!(
+            "items: cannot exceed {MAX_BATCH_QUERIES} items"
+        )));
+    }
+    validate_at_block(body.at_block)?;
+    validate_tree_format(&body.format)?;
+    for item in &body.items {
+        validate_account_id(&item.predecessor_id, "items[].accountId")?;
+        validate_account_id(&item.current_account_id, "items[].contractId")?;
+        validate_limit(item.limit)?;
+        validate_prefix(&item.key_prefix)?;
+        if let Some(ref after_key) = item.after_key {
+            validate_key(after_key, "items[].after_key", MAX_KEY_LENGTH)?;
+        }
+    }
+    let fields = parse_field_set(&body.fields)?;
+    let decode = should_decode(&body.value_format)?;
+    let as_tree = body.format.is_some();
+
+    tracing::info!(
+        target: PROJECT_ID,
+        item_count = body.items.len(),
+        format = ?body.format,
+        at_block = ?body.at_block,
+        "POST /v1/kv/query/batch"
+    );
+
+    // Verify DB is available before starting batch
+    let _ = require_db(&app_state).await?;
+
+    use futures::stream::{self, StreamExt};
+    let items: Vec<PrefixBatchResultItem> = stream::iter(body.items.iter().map(|item| {
+        let store = app_state.store.clone();
+        let params = QueryParams {
+            predecessor_id: item.predecessor_id.clone(),
+            current_account_id: item.current_account_id.clone(),
+            key_prefix: item.key_prefix.clone(),
+            exclude_deleted: body.exclude_deleted,
+            limit: item.limit,
+            offset: 0,
+            fields: None,
+            format: None,
+            value_format: None,
+            after_key: item.after_key.clone(),
+            at_block: body.at_block,
+            ..Default::default()
+        };
+        let item = item.clone();
+        let fields = &fields;
+        async move {
+            let db = store.read().await.clone();
+            let Some(ref db) = db else {
+                return PrefixBatchResultItem {
+                    item,
+                    data: None,
+                    tree: None,
+                    meta: None,
+                    error: Some("Database unavailable".to_string()),
+                };
+            };
+            match db.query_kv_with_pagination(&params).await {
+                Ok((entries, has_more, truncated, dropped, next_cursor)) => {
+                    let meta = PaginationMeta {
+                        has_more,
+                        truncated,
+                        next_cursor,
+                        dropped_rows: dropped_to_option(dropped),
+                        ..Default::default()
+                    };
+                    let (data, tree) = if as_tree {
+                        let pairs: Vec<(String, String)> =
+                            entries.into_iter().map(|e| (e.key, e.value)).collect();
+                        (None, Some(build_tree(&pairs)))
+                    } else {
+                        let data = entries
+                            .into_iter()
+                            .map(|e| {
+                                let mut json = e.to_json_with_fields(fields);
+                                if decode {
+                                    decode_value_in_json(&mut json);
+                                }
+                                json
+                            })
+                            .collect();
+                        (Some(serde_json::Value::Array(data)), None)
+                    };
+                    PrefixBatchResultItem {
+                        item,
+                        data,
+                        tree,
+                        meta: Some(meta),
+                        error: None,
+                    }
+                }
+                Err(e) => {
+                    // Log full error internally, return generic message to client
+                    tracing::warn!(target: PROJECT_ID, error = %e, accountId = %item.predecessor_id, "Batch prefix query failed");
+                    PrefixBatchResultItem {
+                        item,
+                        data: None,
+                        tree: None,
+                        meta: None,
+                        error: Some("Query failed".to_string()),
+                    }
+                }
+            }
+        }
+    }))
+    .buffered(BATCH_CONCURRENCY)
     .collect()
     .await;
 
@@ -947,6 +1998,7 @@ pub async fn edges_handler(
         truncated: false,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
 
     Ok(HttpResponse::Ok().json(PaginatedResponse {
@@ -994,120 +2046,583 @@ pub async fn edges_count_handler(
     }))
 }
 
-/// Watch a key for changes via Server-Sent Events (SSE).
-///
-/// Returns a `text/event-stream` that emits `change` events whenever the
-/// watched key's block height advances.  Supports `Last-Event-ID` for
-/// reconnection.  Server limits concurrent watches to `MAX_CONCURRENT_WATCHES`.
+/// Count keys under a prefix in one writer's partition
 #[utoipa::path(
     get,
-    path = "/v1/kv/watch",
-    params(WatchParams),
+    path = "/v1/kv/count",
+    params(KeyCountParams),
     responses(
-        (status = 200, description = "SSE event stream", content_type = "text/event-stream"),
+        (status = 200, description = "Key count", body = inline(DataResponse<CountResponse>)),
         (status = 400, description = "Invalid parameters", body = ErrorResponse),
-        (status = 429, description = "Too many watch connections", body = ErrorResponse),
         (status = 503, description = "Database unavailable", body = ErrorResponse),
     ),
     tag = "kv"
 )]
-#[get("/v1/kv/watch")]
-pub async fn watch_kv_handler(
-    query: web::Query<WatchParams>,
+#[get("/v1/kv/count")]
+pub async fn count_kv_handler(
+    query: web::Query<KeyCountParams>,
     app_state: web::Data<AppState>,
-    req: HttpRequest,
 ) -> Result<HttpResponse, ApiError> {
     validate_account_id(&query.predecessor_id, "accountId")?;
     validate_account_id(&query.current_account_id, "contractId")?;
-    validate_key(&query.key, "key", MAX_KEY_LENGTH)?;
-
-    let poll_secs = query.interval.clamp(MIN_POLL_INTERVAL, MAX_POLL_INTERVAL);
+    validate_prefix(&query.key_prefix)?;
 
-    // Atomically claim a watch slot; rollback if over limit
-    let prev = app_state
-        .watch_count
-        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-    if prev >= MAX_CONCURRENT_WATCHES {
-        app_state
-            .watch_count
-            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
-        return Err(ApiError::TooManyRequests(
-            "Too many active watch connections".to_string(),
-        ));
-    }
+    tracing::info!(
+        target: PROJECT_ID,
+        accountId = %query.predecessor_id,
+        contractId = %query.current_account_id,
+        key_prefix = ?query.key_prefix,
+        exclude_deleted = ?query.exclude_deleted,
+        "GET /v1/kv/count"
+    );
 
-    // RAII guard: created immediately after incrementing watch_count so that
-    // early disconnects (before the stream is polled) still decrement.
-    let guard = WatchGuard(app_state.watch_count.clone());
+    let db = require_db(&app_state).await?;
+    let (count, approximate) = db
+        .count_keys(
+            &query.predecessor_id,
+            &query.current_account_id,
+            query.key_prefix.as_deref(),
+            query.exclude_deleted.unwrap_or(false),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(DataResponse {
+        data: CountResponse { count, approximate },
+    }))
+}
+
+/// Count distinct writers of a key
+#[utoipa::path(
+    get,
+    path = "/v1/kv/writers/count",
+    params(WritersCountParams),
+    responses(
+        (status = 200, description = "Writer count", body = inline(DataResponse<CountResponse>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/writers/count")]
+pub async fn writers_count_handler(
+    query: web::Query<WritersCountParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&query.current_account_id, "contractId")?;
+    validate_key(&query.key, "key", MAX_KEY_LENGTH)?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        contractId = %query.current_account_id,
+        key = %query.key,
+        exclude_deleted = ?query.exclude_deleted,
+        "GET /v1/kv/writers/count"
+    );
+
+    let db = require_db(&app_state).await?;
+    let (count, approximate) = db
+        .count_writers(
+            &query.current_account_id,
+            &query.key,
+            query.exclude_deleted.unwrap_or(false),
+        )
+        .await?;
+
+    Ok(HttpResponse::Ok().json(DataResponse {
+        data: CountResponse { count, approximate },
+    }))
+}
+
+/// All KV writes produced by a transaction
+#[utoipa::path(
+    get,
+    path = "/v1/kv/by-tx",
+    params(TxLookupParams),
+    responses(
+        (status = 200, description = "Writes in execution order", body = inline(PaginatedResponse<KvEntry>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 501, description = "Lookup view not deployed", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/by-tx")]
+pub async fn by_tx_handler(
+    query: web::Query<TxLookupParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_key(&query.tx_hash, "tx_hash", MAX_HASH_LENGTH)?;
+    validate_limit(query.limit)?;
+    validate_offset(query.offset)?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        tx_hash = %query.tx_hash,
+        limit = query.limit,
+        offset = query.offset,
+        "GET /v1/kv/by-tx"
+    );
+
+    let db = require_db(&app_state).await?;
+    let (entries, has_more, dropped) = db
+        .query_kv_by_tx(&query.tx_hash, query.limit, query.offset)
+        .await?;
+
+    let meta = PaginationMeta {
+        has_more,
+        truncated: false,
+        next_cursor: None,
+        dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
+    };
+    let fields = parse_field_set(&query.fields)?;
+    let decode = should_decode(&query.value_format)?;
+    Ok(respond_paginated(entries, meta, &fields, decode))
+}
+
+/// All KV writes produced by a receipt
+#[utoipa::path(
+    get,
+    path = "/v1/kv/by-receipt",
+    params(ReceiptLookupParams),
+    responses(
+        (status = 200, description = "Writes in execution order", body = inline(PaginatedResponse<KvEntry>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 501, description = "Lookup view not deployed", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/by-receipt")]
+pub async fn by_receipt_handler(
+    query: web::Query<ReceiptLookupParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_key(&query.receipt_id, "receipt_id", MAX_HASH_LENGTH)?;
+    validate_limit(query.limit)?;
+    validate_offset(query.offset)?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        receipt_id = %query.receipt_id,
+        limit = query.limit,
+        offset = query.offset,
+        "GET /v1/kv/by-receipt"
+    );
+
+    let db = require_db(&app_state).await?;
+    let (entries, has_more, dropped) = db
+        .query_kv_by_receipt(&query.receipt_id, query.limit, query.offset)
+        .await?;
+
+    let meta = PaginationMeta {
+        has_more,
+        truncated: false,
+        next_cursor: None,
+        dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
+    };
+    let fields = parse_field_set(&query.fields)?;
+    let decode = should_decode(&query.value_format)?;
+    Ok(respond_paginated(entries, meta, &fields, decode))
+}
+
+/// Watch a key or key prefix for changes via Server-Sent Events (SSE).
+///
+/// Returns a `text/event-stream` that emits one `change` event per write to
+/// the watched key, in order, read from `s_kv` history.  With `key_prefix`,
+/// emits the newest write per key and block under the prefix, from
+/// `s_kv_by_block`.  Event ids are `block_height:order_id`; `Last-Event-ID`
+/// resumes right after that write.  Connections watching the same key share
+/// one poller in `WatchHub`.
+/// Server limits concurrent watches to `MAX_CONCURRENT_WATCHES`.
+#[utoipa::path(
+    get,
+    path = "/v1/kv/watch",
+    params(WatchParams),
+    responses(
+        (status = 200, description = "SSE event stream", content_type = "text/event-stream"),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 429, description = "Too many watch connections", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/watch")]
+pub async fn watch_kv_handler(
+    query: web::Query<WatchParams>,
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&query.predecessor_id, "accountId")?;
+    validate_account_id(&query.current_account_id, "contractId")?;
+    let target = match (&query.key, &query.key_prefix) {
+        (Some(key), None) => {
+            validate_key(key, "key", MAX_KEY_LENGTH)?;
+            WatchTarget::Key(key.clone())
+        }
+        (None, Some(key_prefix)) => {
+            validate_key(key_prefix, "key_prefix", MAX_PREFIX_LENGTH)?;
+            WatchTarget::Prefix(key_prefix.clone())
+        }
+        _ => {
+            return Err(ApiError::InvalidParameter(
+                "key: exactly one of key or key_prefix is required".to_string(),
+            ));
+        }
+    };
+
+    let guard = claim_watch_slot(&app_state)?;
 
     // Verify DB is available (guard's Drop handles rollback on error)
-    let _ = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
 
     tracing::info!(
         target: PROJECT_ID,
         accountId = %query.predecessor_id,
         contractId = %query.current_account_id,
-        key = %query.key,
-        interval = poll_secs,
+        key = ?query.key,
+        key_prefix = ?query.key_prefix,
         "GET /v1/kv/watch (SSE)"
     );
 
-    // Support Last-Event-ID for reconnection
-    let last_block: Option<u64> = req
-        .headers()
+    let after = last_event_position(&req);
+    let topic = Topic {
+        predecessor_id: query.predecessor_id.clone(),
+        current_account_id: query.current_account_id.clone(),
+        target,
+    };
+    let mut subscriptions = vec![Subscription::new(topic, after)];
+    let initial = match after {
+        Some(_) => Vec::new(),
+        None => watch::start_all(&mut subscriptions, db.as_ref()).await?,
+    };
+
+    watch_response(subscriptions, initial, db, &app_state, guard).await
+}
+
+/// Watch many keys, across writers and contracts, over one SSE connection.
+///
+/// Emits the same `change` events as `/v1/kv/watch`, each naming its key,
+/// merged in write order.  The connection opens with each key's current
+/// value unless `Last-Event-ID` resumes it.  The whole list takes a single
+/// `MAX_CONCURRENT_WATCHES` slot.
+#[utoipa::path(
+    post,
+    path = "/v1/kv/watch/multi",
+    request_body = WatchMultiRequest,
+    responses(
+        (status = 200, description = "SSE event stream", content_type = "text/event-stream"),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 429, description = "Too many watch connections", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[post("/v1/kv/watch/multi")]
+pub async fn watch_multi_kv_handler(
+    body: web::Json<WatchMultiRequest>,
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    tracing::info!(
+        target: PROJECT_ID,
+        key_count = body.keys.len(),
+        "POST /v1/kv/watch/multi (SSE)"
+    );
+    watch_multi(&body.keys, &app_state, &req).await
+}
+
+/// `GET` form of `POST /v1/kv/watch/multi`, for `EventSource` clients: the
+/// key list is the POST body's `keys` array, JSON-encoded in `keys=`.
+#[utoipa::path(
+    get,
+    path = "/v1/kv/watch/multi",
+    params(WatchMultiParams),
+    responses(
+        (status = 200, description = "SSE event stream", content_type = "text/event-stream"),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 429, description = "Too many watch connections", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/watch/multi")]
+pub async fn watch_multi_kv_get_handler(
+    query: web::Query<WatchMultiParams>,
+    app_state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let keys: Vec<MultiBatchItem> = serde_json::from_str(&query.keys).map_err(|_| {
+        ApiError::InvalidParameter(
+            "keys: must be a JSON array of {accountId, contractId, key}".to_string(),
+        )
+    })?;
+    tracing::info!(
+        target: PROJECT_ID,
+        key_count = keys.len(),
+        "GET /v1/kv/watch/multi (SSE)"
+    );
+    watch_multi(&keys, &app_state, &req).await
+}
+
+async fn watch_multi(
+    keys: &[MultiBatchItem],
+    app_state: &AppState,
+    req: &HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    if keys.is_empty() {
+        return Err(ApiError::InvalidParameter(
+            "keys: cannot be empty".to_string(),
+        ));
+    }
+    if keys.len() > MAX_WATCH_KEYS {
+        return Err(ApiError::InvalidParameter(format!(
+            "keys: cannot exceed {MAX_WATCH_KEYS} items"
+        )));
+    }
+    for item in keys {
+        validate_account_id(&item.predecessor_id, "keys[].accountId")?;
+        validate_account_id(&item.current_account_id, "keys[].contractId")?;
+        validate_key(&item.key, "keys[].key", MAX_KEY_LENGTH)?;
+    }
+
+    let guard = claim_watch_slot(app_state)?;
+    let db = require_db(app_state).await?;
+
+    let after = last_event_position(req);
+    let mut seen = HashSet::new();
+    let mut subscriptions: Vec<Subscription> = keys
+        .iter()
+        .filter(|item| {
+            seen.insert((
+                item.predecessor_id.as_str(),
+                item.current_account_id.as_str(),
+                item.key.as_str(),
+            ))
+        })
+        .map(|item| {
+            let topic = Topic {
+                predecessor_id: item.predecessor_id.clone(),
+                current_account_id: item.current_account_id.clone(),
+                target: WatchTarget::Key(item.key.clone()),
+            };
+            Subscription::new(topic, after)
+        })
+        .collect();
+    let initial = match after {
+        Some(_) => Vec::new(),
+        None => watch::start_all(&mut subscriptions, db.as_ref()).await?,
+    };
+
+    watch_response(subscriptions, initial, db, app_state, guard).await
+}
+
+/// Long-poll for the next write to a key.
+///
+/// Responds with the key's newest write as soon as it has one above
+/// `since_block`, or 204 after `timeout` seconds.  For clients that cannot
+/// hold an SSE stream; it shares `WatchHub` polling with `/v1/kv/watch` and
+/// holds a `MAX_CONCURRENT_WATCHES` slot while waiting.
+#[utoipa::path(
+    get,
+    path = "/v1/kv/wait",
+    params(WaitParams),
+    responses(
+        (status = 200, description = "The key changed after since_block", body = inline(DataResponse<KvEntry>)),
+        (status = 204, description = "No change before the timeout"),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 429, description = "Too many watch connections", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/kv/wait")]
+pub async fn wait_kv_handler(
+    query: web::Query<WaitParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_account_id(&query.predecessor_id, "accountId")?;
+    validate_account_id(&query.current_account_id, "contractId")?;
+    validate_key(&query.key, "key", MAX_KEY_LENGTH)?;
+    if query.since_block < 0 {
+        return Err(ApiError::InvalidParameter(
+            "since_block: must be non-negative".to_string(),
+        ));
+    }
+    if query.timeout == 0 || query.timeout > MAX_WAIT_TIMEOUT_SECS {
+        return Err(ApiError::InvalidParameter(format!(
+            "timeout: must be between 1 and {MAX_WAIT_TIMEOUT_SECS}"
+        )));
+    }
+
+    let _guard = claim_watch_slot(&app_state)?;
+    let db = require_db(&app_state).await?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        accountId = %query.predecessor_id,
+        contractId = %query.current_account_id,
+        key = %query.key,
+        since_block = query.since_block,
+        timeout = query.timeout,
+        "GET /v1/kv/wait"
+    );
+
+    let topic = Topic {
+        predecessor_id: query.predecessor_id.clone(),
+        current_account_id: query.current_account_id.clone(),
+        target: WatchTarget::Key(query.key.clone()),
+    };
+    let subscription = Subscription::new(topic, Some((query.since_block, i64::MAX)));
+    let hub = app_state.watch_hub.clone();
+    let mut rounds = hub.rounds();
+    let mut feed = hub.subscribe(subscription, db.as_ref()).await?;
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(query.timeout);
+    let changed = |entry: KvEntry| {
+        HttpResponse::Ok()
+            .insert_header(("Cache-Control", "no-cache"))
+            .json(DataResponse { data: entry })
+    };
+
+    // Already changed: answer with the newest write rather than draining
+    // everything since `since_block`. Subscribing first means a write landing
+    // after this read is still caught by the feed.
+    let latest = db
+        .get_kv_at_block(
+            &query.predecessor_id,
+            &query.current_account_id,
+            &query.key,
+            i64::MAX,
+        )
+        .await?;
+    if let Some(entry) = latest.filter(|e| e.block_height > query.since_block as u64) {
+        return Ok(changed(entry));
+    }
+
+    let mut newest = None;
+    loop {
+        let db = app_state.store.read().await.clone();
+        let mut failed = db.is_none();
+        if let Some(db) = db {
+            let (entries, poll_failed) =
+                watch::drain_all(std::slice::from_mut(&mut feed), db.as_ref()).await;
+            failed = poll_failed;
+            if let Some((_, entry)) = entries.into_iter().last() {
+                newest = Some(entry);
+            }
+        }
+        // Read on to the newest write before answering, within the timeout
+        if failed || feed.is_live() {
+            if let Some(entry) = newest.take() {
+                return Ok(changed(entry));
+            }
+            match tokio::time::timeout_at(deadline, rounds.changed()).await {
+                Ok(Ok(())) => {}
+                Ok(Err(_)) | Err(_) => break,
+            }
+        } else if tokio::time::Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    Ok(match newest {
+        Some(entry) => changed(entry),
+        None => HttpResponse::NoContent()
+            .insert_header(("Cache-Control", "no-cache"))
+            .finish(),
+    })
+}
+
+/// Atomically claim a watch slot; rollback if over limit.
+pub(crate) fn claim_watch_slot(app_state: &AppState) -> Result<WatchGuard, ApiError> {
+    let prev = app_state
+        .watch_count
+        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    if prev >= MAX_CONCURRENT_WATCHES {
+        app_state
+            .watch_count
+            .fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+        return Err(ApiError::TooManyRequests(
+            "Too many active watch connections".to_string(),
+        ));
+    }
+    // RAII guard: created immediately after incrementing watch_count so that
+    // early disconnects (before the stream is polled) still decrement.
+    Ok(WatchGuard(app_state.watch_count.clone()))
+}
+
+/// Resume point from the `Last-Event-ID` header, if it holds a valid id.
+fn last_event_position(req: &HttpRequest) -> Option<WatchPosition> {
+    req.headers()
         .get("Last-Event-ID")
         .and_then(|v| v.to_str().ok())
-        .and_then(|s| s.parse().ok());
+        .and_then(watch::parse_last_event_id)
+}
 
-    let scylladb = app_state.scylladb.clone();
-    let predecessor_id = query.predecessor_id.clone();
-    let current_account_id = query.current_account_id.clone();
-    let key = query.key.clone();
+/// SSE response that emits `initial`, then the subscriptions' writes as the
+/// shared `WatchHub` rounds complete, until the client disconnects.
+async fn watch_response(
+    subscriptions: Vec<Subscription>,
+    initial: Vec<KvEntry>,
+    db: Arc<dyn KvStore>,
+    app_state: &AppState,
+    guard: WatchGuard,
+) -> Result<HttpResponse, ApiError> {
+    let hub = app_state.watch_hub.clone();
+    let mut feeds = Vec::with_capacity(subscriptions.len());
+    for subscription in subscriptions {
+        feeds.push(hub.subscribe(subscription, db.as_ref()).await?);
+    }
+    let store = app_state.store.clone();
 
     let stream = async_stream::stream! {
         let _guard = guard; // move RAII guard into the stream so it lives until disconnect
-        let mut last_known_block = last_block.unwrap_or(0);
-        let mut poll_interval = tokio::time::interval(Duration::from_secs(poll_secs));
+        let mut feeds = feeds;
+        for entry in initial {
+            if let Some(frame) = watch::watch_change_frame(entry) {
+                yield Ok::<actix_web::web::Bytes, actix_web::Error>(frame);
+            }
+        }
+        let mut rounds = hub.rounds();
         let mut heartbeat_interval = tokio::time::interval(Duration::from_secs(SSE_HEARTBEAT_SECS));
+        let mut catch_up = true;
 
         loop {
-            tokio::select! {
-                _ = poll_interval.tick() => {
-                    // Clone the Arc and drop the guard before awaiting DB call,
-                    // so the RwLock is not held across .await (blocks reconnection).
-                    let db = scylladb.read().await.clone();
-                    if let Some(ref db) = db {
-                        match db.get_kv(&predecessor_id, &current_account_id, &key).await {
-                            Ok(Some(entry)) if entry.block_height > last_known_block => {
-                                last_known_block = entry.block_height;
-                                let event = WatchEvent {
-                                    key: entry.key,
-                                    value: entry.value,
-                                    block_height: entry.block_height,
-                                    block_timestamp: entry.block_timestamp,
-                                    predecessor_id: entry.predecessor_id.clone(),
-                                    current_account_id: entry.current_account_id.clone(),
-                                };
-                                if let Ok(data) = serde_json::to_string(&event) {
-                                    let msg = format!("id: {}\nevent: change\ndata: {}\n\n", last_known_block, data);
-                                    yield Ok::<actix_web::web::Bytes, actix_web::Error>(actix_web::web::Bytes::from(msg));
-                                }
-                            }
-                            Ok(_) => {} // No change
-                            Err(e) => {
-                                tracing::warn!(target: PROJECT_ID, error = %e, "Watch poll error");
-                                let msg = "event: error\ndata: {\"error\":\"poll_failed\"}\n\n";
-                                yield Ok(actix_web::web::Bytes::from(msg));
-                            }
+            // Feeds behind the topic channel read the store right away;
+            // live feeds wait for the next hub round.
+            if !catch_up {
+                tokio::select! {
+                    changed = rounds.changed() => {
+                        if changed.is_err() {
+                            break;
                         }
-                    } else {
-                        let msg = "event: error\ndata: {\"error\":\"database_unavailable\"}\n\n";
-                        yield Ok(actix_web::web::Bytes::from(msg));
+                    }
+                    _ = heartbeat_interval.tick() => {
+                        yield Ok(actix_web::web::Bytes::from(": heartbeat\n\n"));
+                        continue;
                     }
                 }
-                _ = heartbeat_interval.tick() => {
-                    yield Ok(actix_web::web::Bytes::from(": heartbeat\n\n"));
+            }
+            // Clone the Arc and drop the guard before awaiting DB call,
+            // so the RwLock is not held across .await (blocks reconnection).
+            let db = store.read().await.clone();
+            if let Some(ref db) = db {
+                let (entries, failed) = watch::drain_all(&mut feeds, db.as_ref()).await;
+                for (_, entry) in entries {
+                    if let Some(frame) = watch::watch_change_frame(entry) {
+                        yield Ok(frame);
+                    }
+                }
+                catch_up = !failed && feeds.iter().any(|f| !f.is_live());
+                if failed {
+                    let msg = "event: error\ndata: {\"error\":\"poll_failed\"}\n\n";
+                    yield Ok(actix_web::web::Bytes::from(msg));
                 }
+            } else {
+                catch_up = false;
+                let msg = "event: error\ndata: {\"error\":\"database_unavailable\"}\n\n";
+                yield Ok(actix_web::web::Bytes::from(msg));
             }
         }
     };
@@ -1121,7 +2636,7 @@ pub async fn watch_kv_handler(
 }
 
 /// RAII guard that decrements the watch counter when the SSE stream drops.
-struct WatchGuard(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+pub(crate) struct WatchGuard(std::sync::Arc<std::sync::atomic::AtomicUsize>);
 impl Drop for WatchGuard {
     fn drop(&mut self) {
         self.0.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
@@ -1139,7 +2654,7 @@ impl Drop for WatchGuard {
 )]
 #[get("/v1/status")]
 pub async fn status_handler(app_state: web::Data<AppState>) -> HttpResponse {
-    let db = app_state.scylladb.read().await.clone();
+    let db = app_state.store.read().await.clone();
     let indexer_block = match db.as_ref() {
         Some(db) => db.get_indexer_block_height().await.ok().flatten(),
         None => None,
@@ -1150,3 +2665,886 @@ pub async fn status_handler(app_state: web::Data<AppState>) -> HttpResponse {
         timestamp: chrono::Utc::now().to_rfc3339(),
     })
 }
+
+/// Every KV write stored for one block, grouped by receipt and action index
+#[utoipa::path(
+    get,
+    path = "/v1/blocks/{height}/kv",
+    params(
+        ("height" = u64, Path, description = "Block height"),
+        BlockKvParams,
+    ),
+    responses(
+        (status = 200, description = "Writes grouped by receipt and action", body = inline(DataResponse<BlockKvResponse>)),
+        (status = 400, description = "Invalid parameters", body = ErrorResponse),
+        (status = 501, description = "mv_kv_by_block not deployed", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/blocks/{height}/kv")]
+pub async fn block_kv_handler(
+    path: web::Path<String>,
+    query: web::Query<BlockKvParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let block_height: i64 = path.parse().ok().filter(|h| *h >= 0).ok_or_else(|| {
+        ApiError::InvalidParameter("height: must be a non-negative integer".to_string())
+    })?;
+    let decode = should_decode(&query.value_format)?;
+
+    tracing::info!(
+        target: PROJECT_ID,
+        block_height,
+        "GET /v1/blocks/{{height}}/kv"
+    );
+
+    let db = require_db(&app_state).await?;
+    let ((entries, truncated, dropped), indexer_block) = futures::future::try_join(
+        db.query_kv_by_block(block_height, MAX_BLOCK_WRITES),
+        db.get_indexer_block_height(),
+    )
+    .await?;
+
+    let block_height = block_height as u64;
+    let block = BlockKvResponse {
+        block_height,
+        indexer_block,
+        beyond_indexer_head: indexer_block.is_some_and(|head| block_height > head),
+        receipts: group_block_writes(entries),
+        truncated,
+        dropped_rows: dropped_to_option(dropped),
+    };
+    if decode {
+        let mut data = serde_json::to_value(&block).map_err(|e| anyhow::anyhow!(e))?;
+        data["receipts"]
+            .as_array_mut()
+            .into_iter()
+            .flatten()
+            .filter_map(|receipt| receipt["actions"].as_array_mut())
+            .flatten()
+            .filter_map(|action| action["writes"].as_array_mut())
+            .flatten()
+            .for_each(decode_value_in_json);
+        return Ok(HttpResponse::Ok().json(serde_json::json!({ "data": data })));
+    }
+    Ok(HttpResponse::Ok().json(DataResponse { data: block }))
+}
+
+/// Groups execution-ordered writes by receipt, then by action index within
+/// each receipt. Both levels keep the order of their first write.
+fn group_block_writes(entries: Vec<KvEntry>) -> Vec<BlockReceiptWrites> {
+    let mut receipts: Vec<BlockReceiptWrites> = Vec::new();
+    for entry in entries {
+        let receipt = match receipts
+            .iter()
+            .position(|r| r.receipt_id == entry.receipt_id)
+        {
+            Some(i) => &mut receipts[i],
+            None => {
+                receipts.push(BlockReceiptWrites {
+                    receipt_id: entry.receipt_id.clone(),
+                    tx_hash: entry.tx_hash.clone(),
+                    shard_id: entry.shard_id,
+                    receipt_index: entry.receipt_index,
+                    actions: Vec::new(),
+                });
+                receipts.last_mut().unwrap()
+            }
+        };
+        match receipt
+            .actions
+            .iter_mut()
+            .find(|a| a.action_index == entry.action_index)
+        {
+            Some(action) => action.writes.push(entry),
+            None => receipt.actions.push(BlockActionWrites {
+                action_index: entry.action_index,
+                writes: vec![entry],
+            }),
+        }
+    }
+    receipts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+    use crate::test_support::{read_watch_events, sample_store, test_app_state, write};
+    use actix_web::{test as actix_test, App};
+
+    #[actix_web::test]
+    async fn test_account_timeline_merges_contracts() {
+        let store = sample_store();
+        for (key, block_height) in [("a", 110), ("b", 103)] {
+            let mut row = write("alice.near", key, "1", block_height, 0);
+            row.current_account_id = "app.near".to_string();
+            store.insert(row);
+        }
+        let store: Arc<dyn KvStore> = Arc::new(store);
+        let state = test_app_state(store);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(account_timeline_handler),
+        )
+        .await;
+
+        for (order, limit, expected) in [
+            (
+                "desc",
+                2,
+                [
+                    "120:social.near:profile/name",
+                    "110:app.near:a",
+                    "110:social.near:profile/bio",
+                    "105:social.near:graph/follow/bob.near",
+                    "103:app.near:b",
+                    "100:social.near:profile/name",
+                ],
+            ),
+            (
+                "asc",
+                4,
+                [
+                    "100:social.near:profile/name",
+                    "103:app.near:b",
+                    "105:social.near:graph/follow/bob.near",
+                    "110:social.near:profile/bio",
+                    "110:app.near:a",
+                    "120:social.near:profile/name",
+                ],
+            ),
+        ] {
+            let mut seen = Vec::new();
+            let mut cursor = String::new();
+            loop {
+                let req = actix_test::TestRequest::get()
+                    .uri(&format!(
+                        "/v1/account/timeline?accountId=alice.near&order={order}&limit={limit}&cursor={cursor}"
+                    ))
+                    .to_request();
+                let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+                for e in body["data"].as_array().unwrap() {
+                    seen.push(format!(
+                        "{}:{}:{}",
+                        e["blockHeight"],
+                        e["contractId"].as_str().unwrap(),
+                        e["key"].as_str().unwrap()
+                    ));
+                }
+                match body["meta"]["next_cursor"].as_str() {
+                    Some(next) => cursor = next.to_string(),
+                    None => break,
+                }
+            }
+            assert_eq!(seen, expected, "order={order}");
+        }
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/account/timeline?accountId=alice.near&cursor=110:app.near")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_web::test]
+    async fn test_watch_emits_every_write_in_order() {
+        use actix_web::body::MessageBody;
+
+        let store = MemoryStore::default();
+        for (value, block_height, order_id) in
+            [("1", 100, 0), ("2", 110, 0), ("3", 110, 1), ("4", 120, 0)]
+        {
+            store.insert(write(
+                "alice.near",
+                "profile/name",
+                value,
+                block_height,
+                order_id,
+            ));
+        }
+        let store: Arc<dyn KvStore> = Arc::new(store);
+        let state = test_app_state(store);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(watch_kv_handler),
+        )
+        .await;
+
+        for (last_event_id, expected) in [
+            (None, vec!["120:0"]),
+            (Some("110:0"), vec!["110:1", "120:0"]),
+            // Pre-history ids were bare block heights
+            (Some("100"), vec!["110:0", "110:1", "120:0"]),
+        ] {
+            let mut req = actix_test::TestRequest::get()
+                .uri("/v1/kv/watch?accountId=alice.near&contractId=social.near&key=profile/name");
+            if let Some(id) = last_event_id {
+                req = req.insert_header(("Last-Event-ID", id));
+            }
+            let resp = actix_test::call_service(&app, req.to_request()).await;
+            let mut body = std::pin::pin!(resp.into_body());
+            let mut ids = Vec::new();
+            while ids.len() < expected.len() {
+                let chunk = tokio::time::timeout(
+                    std::time::Duration::from_secs(5),
+                    futures::future::poll_fn(|cx| body.as_mut().poll_next(cx)),
+                )
+                .await
+                .expect("watch event")
+                .unwrap()
+                .unwrap();
+                let text = std::str::from_utf8(&chunk).unwrap();
+                ids.extend(
+                    text.lines()
+                        .filter_map(|l| l.strip_prefix("id: "))
+                        .map(String::from),
+                );
+            }
+            assert_eq!(ids, expected, "Last-Event-ID {last_event_id:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_watch_prefix_and_multi_key() {
+        let store: Arc<dyn KvStore> = Arc::new(sample_store());
+        let state = test_app_state(store);
+        let watch_count = state.watch_count.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(watch_kv_handler)
+                .service(watch_multi_kv_handler)
+                .service(watch_multi_kv_get_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/watch?accountId=alice.near&contractId=social.near&key_prefix=profile/")
+            .insert_header(("Last-Event-ID", "100"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(
+            read_watch_events(&mut Box::pin(resp.into_body()), 2).await,
+            [
+                ("110:0".to_string(), "alice.near/profile/bio".to_string()),
+                ("120:0".to_string(), "alice.near/profile/name".to_string()),
+            ]
+        );
+
+        let keys = serde_json::json!({"keys": [
+            {"accountId": "alice.near", "contractId": "social.near", "key": "profile/name"},
+            {"accountId": "carol.near", "contractId": "social.near", "key": "graph/follow/bob.near"},
+        ]});
+        // Fresh connection opens with each key's current value
+        let req = actix_test::TestRequest::post()
+            .uri("/v1/kv/watch/multi")
+            .set_json(&keys)
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(watch_count.load(std::sync::atomic::Ordering::Relaxed), 1);
+        assert_eq!(
+            read_watch_events(&mut Box::pin(resp.into_body()), 2).await,
+            [
+                ("120:0".to_string(), "alice.near/profile/name".to_string()),
+                (
+                    "130:0".to_string(),
+                    "carol.near/graph/follow/bob.near".to_string()
+                ),
+            ]
+        );
+        assert_eq!(watch_count.load(std::sync::atomic::Ordering::Relaxed), 0);
+
+        let req = actix_test::TestRequest::post()
+            .uri("/v1/kv/watch/multi")
+            .insert_header(("Last-Event-ID", "105:0"))
+            .set_json(&keys)
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        let ids: Vec<String> = read_watch_events(&mut Box::pin(resp.into_body()), 3)
+            .await
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, ["106:0", "120:0", "130:0"]);
+
+        // GET, for EventSource: the same list, JSON- and percent-encoded
+        let encoded: String = keys["keys"]
+            .to_string()
+            .bytes()
+            .map(|b| format!("%{b:02X}"))
+            .collect();
+        let req = actix_test::TestRequest::get()
+            .uri(&format!("/v1/kv/watch/multi?keys={encoded}"))
+            .insert_header(("Last-Event-ID", "110:0"))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        let ids: Vec<String> = read_watch_events(&mut Box::pin(resp.into_body()), 2)
+            .await
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, ["120:0", "130:0"]);
+
+        for req in [
+            actix_test::TestRequest::get()
+                .uri("/v1/kv/watch?accountId=alice.near&contractId=social.near&key=a&key_prefix=b"),
+            actix_test::TestRequest::get()
+                .uri("/v1/kv/watch?accountId=alice.near&contractId=social.near"),
+            actix_test::TestRequest::post()
+                .uri("/v1/kv/watch/multi")
+                .set_json(serde_json::json!({"keys": []})),
+            actix_test::TestRequest::get().uri("/v1/kv/watch/multi?keys=alice.near"),
+        ] {
+            let resp = actix_test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), 400);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_wait_returns_change_or_no_content() {
+        let memory = Arc::new(sample_store());
+        let store: Arc<dyn KvStore> = memory.clone();
+        let state = test_app_state(store.clone());
+        let hub = state.watch_hub.clone();
+        let watch_count = state.watch_count.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(wait_kv_handler),
+        )
+        .await;
+        let wait = |since_block: i64, timeout: u64| {
+            actix_test::TestRequest::get()
+                .uri(&format!(
+                    "/v1/kv/wait?accountId=alice.near&contractId=social.near&key=profile/name&since_block={since_block}&timeout={timeout}"
+                ))
+                .to_request()
+        };
+
+        // Already changed: the newest write answers at once
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, wait(90, 1)).await;
+        assert_eq!(body["data"]["blockHeight"], 120);
+
+        // Changed while waiting, delivered by the next hub round
+        let writer = memory.clone();
+        actix_web::rt::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            writer.insert(write("alice.near", "profile/name", "\"Al\"", 140, 0));
+            hub.poll_round(store.as_ref()).await;
+        });
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, wait(120, 5)).await;
+        assert_eq!(body["data"]["blockHeight"], 140);
+
+        let resp = actix_test::call_service(&app, wait(140, 1)).await;
+        assert_eq!(resp.status(), 204);
+        assert_eq!(watch_count.load(std::sync::atomic::Ordering::Relaxed), 0);
+
+        // A long backlog is not drained: the newest write answers
+        for block_height in 1000..4000 {
+            memory.insert(write("alice.near", "profile/name", "1", block_height, 0));
+        }
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, wait(0, 1)).await;
+        assert_eq!(body["data"]["blockHeight"], 3999);
+
+        for (since_block, timeout) in [(-1, 1), (0, 0), (0, 61)] {
+            let resp = actix_test::call_service(&app, wait(since_block, timeout)).await;
+            assert_eq!(resp.status(), 400);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_conditional_get_etag_and_last_modified() {
+        let memory = Arc::new(sample_store());
+        let store: Arc<dyn KvStore> = memory.clone();
+        let state = test_app_state(store);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(get_kv_handler)
+                .service(query_kv_handler)
+                .service(crate::social_handlers::social_profile_handler),
+        )
+        .await;
+        let get_uri = "/v1/kv/get?accountId=alice.near&contractId=social.near&key=profile/name";
+        let fetch = |uri: &str, etag: Option<&str>| {
+            let mut req = actix_test::TestRequest::get().uri(uri);
+            if let Some(etag) = etag {
+                req = req.insert_header(("If-None-Match", etag));
+            }
+            req.to_request()
+        };
+        let etag_of = |resp: &actix_web::dev::ServiceResponse| {
+            resp.headers()
+                .get("ETag")
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string()
+        };
+
+        let resp = actix_test::call_service(&app, fetch(get_uri, None)).await;
+        assert_eq!(resp.status(), 200);
+        let etag = etag_of(&resp);
+        assert!(etag.starts_with("W/\"120-"));
+        assert_eq!(
+            resp.headers().get("Last-Modified").unwrap(),
+            "Thu, 01 Jan 1970 00:00:00 GMT"
+        );
+
+        let resp = actix_test::call_service(&app, fetch(get_uri, Some(&etag))).await;
+        assert_eq!(resp.status(), 304);
+        assert_eq!(etag_of(&resp), etag);
+        assert!(actix_test::read_body(resp).await.is_empty());
+
+        // Other params validate separately at the same block
+        let decoded = format!("{get_uri}&value_format=json");
+        let resp = actix_test::call_service(&app, fetch(&decoded, Some(&etag))).await;
+        assert_eq!(resp.status(), 200);
+        assert_ne!(etag_of(&resp), etag);
+
+        // A new write changes the ETag
+        memory.insert(write("alice.near", "profile/name", "\"Al\"", 140, 0));
+        let resp = actix_test::call_service(&app, fetch(get_uri, Some(&etag))).await;
+        assert_eq!(resp.status(), 200);
+        assert!(etag_of(&resp).starts_with("W/\"140-"));
+
+        for uri in [
+            "/v1/kv/query?accountId=alice.near&contractId=social.near&key_prefix=profile/",
+            "/v1/kv/query?accountId=alice.near&contractId=social.near&key_prefix=profile/&format=tree",
+            "/v1/social/profile?account_id=alice.near",
+        ] {
+            let resp = actix_test::call_service(&app, fetch(uri, None)).await;
+            assert_eq!(resp.status(), 200);
+            let etag = etag_of(&resp);
+            assert!(etag.starts_with("W/\"140-"), "{uri}: {etag}");
+            let list = format!("W/\"stale\", {etag}");
+            let resp = actix_test::call_service(&app, fetch(uri, Some(&list))).await;
+            assert_eq!(resp.status(), 304, "{uri}");
+        }
+
+        // A different page at the same height and size is not Not Modified
+        let first =
+            "/v1/kv/query?accountId=alice.near&contractId=social.near&key_prefix=profile/&limit=1";
+        let resp = actix_test::call_service(&app, fetch(first, None)).await;
+        let etag = etag_of(&resp);
+        assert!(etag.starts_with("W/\"110-"));
+        memory.insert(write("alice.near", "profile/a", "\"x\"", 110, 1));
+        let resp = actix_test::call_service(&app, fetch(first, Some(&etag))).await;
+        assert_eq!(resp.status(), 200);
+        assert!(etag_of(&resp).starts_with("W/\"110-"));
+        assert_ne!(etag_of(&resp), etag);
+    }
+
+    #[actix_web::test]
+    async fn test_block_kv_groups_by_receipt_and_action() {
+        let store = MemoryStore::default();
+        for (pred, key, receipt, action_index, order_id) in [
+            ("alice.near", "a", "rA", 0, 0),
+            ("alice.near", "b", "rA", 1, 1),
+            ("bob.near", "c", "rB", 0, 2),
+            ("alice.near", "d", "rA", 0, 3),
+        ] {
+            let mut row = write(pred, key, "{\"n\":1}", 200, order_id);
+            row.receipt_id = receipt.to_string();
+            row.action_index = action_index;
+            store.insert(row);
+        }
+        let store: Arc<dyn KvStore> = Arc::new(store);
+        let state = test_app_state(store);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(block_kv_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/blocks/200/kv?value_format=json")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        let data = &body["data"];
+        assert_eq!(data["indexerBlock"], 200);
+        assert_eq!(data["beyondIndexerHead"], false);
+        let grouped: Vec<(&str, Vec<Vec<&str>>)> = data["receipts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| {
+                let actions = r["actions"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|a| {
+                        a["writes"]
+                            .as_array()
+                            .unwrap()
+                            .iter()
+                            .map(|w| w["key"].as_str().unwrap())
+                            .collect()
+                    })
+                    .collect();
+                (r["receiptId"].as_str().unwrap(), actions)
+            })
+            .collect();
+        assert_eq!(
+            grouped,
+            vec![
+                ("rA", vec![vec!["a", "d"], vec!["b"]]),
+                ("rB", vec![vec!["c"]]),
+            ]
+        );
+        assert_eq!(
+            data["receipts"][0]["actions"][0]["writes"][0]["value"]["n"],
+            1
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/blocks/201/kv")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["beyondIndexerHead"], true);
+        assert_eq!(body["data"]["receipts"], serde_json::json!([]));
+
+        for bad in ["/v1/blocks/-1/kv", "/v1/blocks/abc/kv"] {
+            let req = actix_test::TestRequest::get().uri(bad).to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_get_and_query_tree() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(get_kv_handler)
+                .service(query_kv_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/get?accountId=alice.near&contractId=social.near&key=profile/name&value_format=json")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["value"], "Alicia");
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/query?accountId=alice.near&contractId=social.near&key_prefix=profile/&format=tree")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["tree"]["profile"]["bio"], "hi");
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/get?accountId=alice.near&contractId=social.near&key=profile/name&at_block=-1")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_time_range_params() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(history_kv_handler)
+                .service(timeline_kv_handler),
+        )
+        .await;
+
+        // Fixture timestamps are block_height * 1000ns: 101000..=119999 covers blocks 105 and 110
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/timeline?accountId=alice.near&contractId=social.near&from_time=101000&to_time=1970-01-01T00:00:00.000119999Z")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        let heights: Vec<_> = body["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["blockHeight"].clone())
+            .collect();
+        assert_eq!(
+            heights,
+            vec![serde_json::json!(110), serde_json::json!(105)]
+        );
+        assert_eq!(
+            body["meta"]["resolved_range"],
+            serde_json::json!({ "from_block": 105, "to_block": 110 })
+        );
+
+        // No write at or after the lower bound: empty page, flagged as such
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/history?accountId=alice.near&contractId=social.near&key=profile/name&from_time=121000")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"], serde_json::json!([]));
+        assert_eq!(
+            body["meta"]["resolved_range"],
+            serde_json::json!({ "empty": true })
+        );
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/history?accountId=alice.near&contractId=social.near&key=profile/name&from_time=200&to_time=100")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_diff_as_of_blocks() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(diff_kv_handler)
+                .service(diff_tree_kv_handler),
+        )
+        .await;
+
+        // Diff resolves each side to the value as of that block, not an exact-block write
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/diff?accountId=alice.near&contractId=social.near&key=profile/name&block_height_a=110&block_height_b=125&value_format=json")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["a"]["value"], "Alice");
+        assert_eq!(body["data"]["b"]["value"], "Alicia");
+
+        // bio added at 110, name changed at 120
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/diff/tree?accountId=alice.near&contractId=social.near&key_prefix=profile/&block_height_a=105&block_height_b=125")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["added"][0]["key"], "profile/bio");
+        assert_eq!(body["data"]["changed"][0]["b"]["value"], "\"Alicia\"");
+        assert_eq!(body["data"]["removed"], serde_json::json!([]));
+        assert_eq!(body["meta"]["has_more"], false);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/diff/tree?accountId=alice.near&contractId=social.near&key_prefix=profile/&block_height_a=105&block_height_b=125&format=patch")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            body["data"],
+            serde_json::json!([
+                { "op": "replace", "path": "/profile/name", "value": "Alicia" },
+                { "op": "add", "path": "/profile/bio", "value": "hi" },
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_diff_tree_format_resumes_when_capped() {
+        let store = sample_store();
+        for i in 0..=MAX_DIFF_TREE_KEYS {
+            store.insert(write("alice.near", &format!("item/{i:04}"), "1", 200, 0));
+        }
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(store))))
+                .service(diff_tree_kv_handler),
+        )
+        .await;
+        let diff = |after_key: &str| {
+            actix_test::TestRequest::get()
+                .uri(&format!(
+                    "/v1/kv/diff/tree?accountId=alice.near&contractId=social.near&key_prefix=item/&block_height_a=100&block_height_b=300&format=tree{after_key}"
+                ))
+                .to_request()
+        };
+
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, diff("")).await;
+        let added = body["tree"]["added"]["item"].as_object().unwrap();
+        assert_eq!(added.len(), MAX_DIFF_TREE_KEYS);
+        assert_eq!(body["has_more"], true);
+        assert_eq!(body["next_cursor"], "item/0999");
+
+        let body: serde_json::Value =
+            actix_test::call_and_read_body_json(&app, diff("&after_key=item/0999")).await;
+        let added = &body["tree"]["added"]["item"];
+        assert_eq!(*added, serde_json::json!({ "1000": 1 }));
+        assert!(body.get("has_more").is_none() && body.get("next_cursor").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_batch_lookups() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(batch_kv_handler)
+                .service(batch_multi_kv_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/v1/kv/batch")
+            .set_json(serde_json::json!({
+                "accountId": "alice.near",
+                "contractId": "social.near",
+                "keys": ["profile/name", "profile/bio"],
+                "at_block": 105,
+            }))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"][0]["value"], "\"Alice\"");
+        assert_eq!(body["data"][1]["found"], false);
+
+        let req = actix_test::TestRequest::post()
+            .uri("/v1/kv/batch/multi")
+            .set_json(serde_json::json!({
+                "items": [
+                    { "accountId": "alice.near", "contractId": "social.near", "key": "profile/name" },
+                    { "accountId": "carol.near", "contractId": "social.near", "key": "graph/follow/bob.near" },
+                    { "accountId": "bob.near", "contractId": "social.near", "key": "profile/name" },
+                ],
+                "fields": "accountId,value,blockHeight",
+                "value_format": "json",
+            }))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"][0]["accountId"], "alice.near");
+        assert_eq!(
+            body["data"][0]["entry"],
+            serde_json::json!({ "accountId": "alice.near", "value": "Alicia", "blockHeight": 120 })
+        );
+        assert_eq!(body["data"][1]["entry"]["value"], serde_json::Value::Null);
+        assert_eq!(body["data"][1]["entry"]["blockHeight"], 130);
+        assert_eq!(body["data"][2]["found"], false);
+        assert!(body["data"][2].get("entry").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_query_batch() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(query_batch_kv_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::post()
+            .uri("/v1/kv/query/batch")
+            .set_json(serde_json::json!({
+                "items": [
+                    { "accountId": "alice.near", "contractId": "social.near", "key_prefix": "profile/", "limit": 1 },
+                    { "accountId": "carol.near", "contractId": "social.near", "key_prefix": "graph/" },
+                ],
+                "fields": "key",
+            }))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"][0]["key_prefix"], "profile/");
+        assert_eq!(
+            body["data"][0]["data"],
+            serde_json::json!([{ "key": "profile/bio" }])
+        );
+        assert_eq!(body["data"][0]["meta"]["has_more"], true);
+        assert_eq!(body["data"][0]["meta"]["next_cursor"], "profile/bio");
+        assert_eq!(body["data"][1]["meta"]["has_more"], false);
+        assert_eq!(body["data"][1]["data"][0]["key"], "graph/follow/bob.near");
+
+        let req = actix_test::TestRequest::post()
+            .uri("/v1/kv/query/batch")
+            .set_json(serde_json::json!({
+                "items": [{ "accountId": "alice.near", "contractId": "social.near", "key_prefix": "profile/", "after_key": "profile/bio" }],
+                "format": "tree",
+            }))
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"][0]["tree"]["profile"]["name"], "Alicia");
+        assert!(body["data"][0].get("data").is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_query_filters_and_bounds() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(query_kv_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/query?accountId=alice.near&contractId=social.near&where=%24.name%20%3E")
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/query?accountId=alice.near&contractId=social.near&key_pattern=profile/*&format=tree")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["tree"]["profile"]["name"], "Alicia");
+
+        for bad in [
+            "key_pattern=post/(&pattern_syntax=regex",
+            "key_pattern=a/*&key_prefix=a/",
+            "before_key=profile/",
+            "order=desc&after_key=profile/",
+            "start_key=b&end_key=a",
+        ] {
+            let req = actix_test::TestRequest::get()
+                .uri(&format!(
+                    "/v1/kv/query?accountId=alice.near&contractId=social.near&{bad}"
+                ))
+                .to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_counts() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(count_kv_handler)
+                .service(writers_count_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/writers/count?contractId=social.near&key=graph/follow/bob.near&exclude_deleted=true")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"], serde_json::json!({ "count": 1 }));
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/count?accountId=alice.near&contractId=social.near&key_prefix=profile/")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"]["count"], 2);
+    }
+
+    #[actix_web::test]
+    async fn test_contract_timeline_blocks() {
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(test_app_state(Arc::new(sample_store()))))
+                .service(contract_timeline_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/kv/contract/timeline?contractId=social.near&from_block=0&to_block=200&order=asc&limit=1")
+            .to_request();
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["data"][0]["accountId"], "alice.near");
+        assert_eq!(body["meta"]["next_cursor"], "100:0:alice.near:profile/name");
+
+        for bad in [
+            "from_block=0&to_block=1000000",
+            "from_block=0",
+            "from_block=0&to_block=10&cursor=5:alice.near",
+        ] {
+            let req = actix_test::TestRequest::get()
+                .uri(&format!(
+                    "/v1/kv/contract/timeline?contractId=social.near&{bad}"
+                ))
+                .to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            assert_eq!(resp.status(), actix_web::http::StatusCode::BAD_REQUEST);
+        }
+    }
+}
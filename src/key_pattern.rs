@@ -0,0 +1,145 @@
+//! `key_pattern` matching for prefix queries: glob by default, anchored regex
+//! with `pattern_syntax=regex`.
+//!
+//! Glob syntax: `*` matches within one `/`-separated segment, `?` matches one
+//! character other than `/`, and `**` matches across segments (`a/**/b` also
+//! matches `a/b`). Every other character is literal.
+//!
+//! Either way the pattern must match the whole key. Its longest literal prefix
+//! drives the range scan; the pattern itself is applied to each scanned key.
+
+use regex::{Regex, RegexBuilder};
+use regex_syntax::hir::literal::{ExtractKind, Extractor};
+
+/// Compiled-program cap, so a hostile regex cannot blow up memory.
+const MAX_REGEX_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+pub struct KeyPattern {
+    regex: Regex,
+    prefix: String,
+}
+
+impl KeyPattern {
+    pub fn parse(pattern: &str, regex: bool) -> Result<Self, String> {
+        if regex {
+            Self::regex(pattern)
+        } else {
+            Self::glob(pattern)
+        }
+    }
+
+    pub fn glob(pattern: &str) -> Result<Self, String> {
+        let mut source = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.next_if_eq(&'*').is_some() => {
+                    if chars.next_if_eq(&'/').is_some() {
+                        source.push_str("(?:.*/)?");
+                    } else {
+                        source.push_str(".*");
+                    }
+                }
+                '*' => source.push_str("[^/]*"),
+                '?' => source.push_str("[^/]"),
+                _ => source.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+        }
+        source.push('$');
+
+        let prefix_len = pattern.find(['*', '?']).unwrap_or(pattern.len());
+        Ok(KeyPattern {
+            regex: compile(&source)?,
+            prefix: pattern[..prefix_len].to_string(),
+        })
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, String> {
+        let source = format!("^(?:{pattern})$");
+        let regex = compile(&source)?;
+        let hir = regex_syntax::Parser::new()
+            .parse(&source)
+            .map_err(|e| e.to_string())?;
+        let literals = Extractor::new().kind(ExtractKind::Prefix).extract(&hir);
+        let prefix = match literals.longest_common_prefix() {
+            // The common prefix is byte-wise; drop a trailing partial character.
+            Some(bytes) => match std::str::from_utf8(bytes) {
+                Ok(s) => s.to_string(),
+                Err(e) => String::from_utf8_lossy(&bytes[..e.valid_up_to()]).into_owned(),
+            },
+            None => String::new(),
+        };
+        Ok(KeyPattern { regex, prefix })
+    }
+
+    /// Every matching key starts with this; empty means a full partition scan.
+    pub fn literal_prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        self.regex.is_match(key)
+    }
+}
+
+fn compile(source: &str) -> Result<Regex, String> {
+    RegexBuilder::new(source)
+        .size_limit(MAX_REGEX_SIZE)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob() {
+        let p = KeyPattern::glob("graph/*/alice.near").unwrap();
+        assert_eq!(p.literal_prefix(), "graph/");
+        assert!(p.matches("graph/follow/alice.near"));
+        assert!(!p.matches("graph/follow/x/alice.near"));
+        assert!(!p.matches("graph/follow/alice.nearly"));
+        assert!(!p.matches("graph/follow/aliceXnear"));
+
+        let p = KeyPattern::glob("post/**/comment").unwrap();
+        assert_eq!(p.literal_prefix(), "post/");
+        assert!(p.matches("post/comment"));
+        assert!(p.matches("post/1/comment"));
+        assert!(p.matches("post/1/2/comment"));
+        assert!(!p.matches("post/1/comments"));
+
+        let p = KeyPattern::glob("profile/name?").unwrap();
+        assert_eq!(p.literal_prefix(), "profile/name");
+        assert!(p.matches("profile/names"));
+        assert!(!p.matches("profile/name"));
+
+        let p = KeyPattern::glob("**").unwrap();
+        assert_eq!(p.literal_prefix(), "");
+        assert!(p.matches("anything/at/all"));
+    }
+
+    #[test]
+    fn test_regex() {
+        let p = KeyPattern::regex(r"post/\d+/main").unwrap();
+        assert_eq!(p.literal_prefix(), "post/");
+        assert!(p.matches("post/42/main"));
+        // Anchored at both ends
+        assert!(!p.matches("x/post/42/main"));
+        assert!(!p.matches("post/42/main/extra"));
+
+        // Alternation keeps only the common prefix
+        let p = KeyPattern::regex("graph/follow/.*|graph/hide/.*").unwrap();
+        assert_eq!(p.literal_prefix(), "graph/");
+        assert!(p.matches("graph/hide/bob.near"));
+
+        assert_eq!(
+            KeyPattern::regex("(?i)profile").unwrap().literal_prefix(),
+            ""
+        );
+        assert_eq!(KeyPattern::regex(".*/name").unwrap().literal_prefix(), "");
+        assert!(KeyPattern::regex("post/(").is_err());
+        assert!(KeyPattern::regex("a{1000}{1000}").is_err());
+    }
+}
@@ -1,20 +1,44 @@
 mod encrypted_handlers;
 mod handlers;
+mod key_pattern;
+mod memory_store;
+mod min_block;
 mod models;
+mod predicate;
 mod scylladb;
 mod social_handlers;
+mod store;
+#[cfg(test)]
+mod test_support;
 mod tree;
+mod watch;
+mod webhook_handlers;
+mod webhooks;
+mod ws_handlers;
 
 use crate::encrypted_handlers::{
     encrypted_batch_encrypt_handler, encrypted_decrypt_handler, encrypted_encrypt_handler,
     encrypted_prepare_encrypt_handler, encrypted_prepare_decrypt_handler, encrypted_result_handler,
 };
 use crate::handlers::{
-    accounts_handler, batch_kv_handler, contracts_handler, diff_kv_handler, edges_count_handler,
-    edges_handler, get_kv_handler, health_check, history_kv_handler, query_kv_handler,
-    status_handler, timeline_kv_handler, watch_kv_handler, writers_handler,
+    account_timeline_handler, accounts_handler, batch_kv_handler, batch_multi_kv_handler,
+    block_kv_handler, by_receipt_handler, by_tx_handler, contract_timeline_handler,
+    contracts_handler, count_kv_handler, diff_kv_handler, diff_tree_kv_handler,
+    edges_count_handler, edges_handler, get_kv_handler, health_check, history_kv_handler,
+    history_prefix_kv_handler, query_batch_kv_handler, query_kv_handler, status_handler,
+    timeline_kv_handler, wait_kv_handler, watch_kv_handler, watch_multi_kv_get_handler,
+    watch_multi_kv_handler, writers_count_handler, writers_handler,
 };
+use crate::memory_store::MemoryStore;
+use crate::min_block::{wait_for_min_block, IndexerHead};
 use crate::scylladb::ScyllaDb;
+use crate::store::KvStore;
+use crate::watch::WatchHub;
+use crate::webhook_handlers::{
+    create_webhook_handler, delete_webhook_handler, webhook_dead_letters_handler,
+};
+use crate::webhooks::{RetryPolicy, WebhookAccess, WebhookRegistry};
+use crate::ws_handlers::ws_handler;
 use crate::social_handlers::{
     social_account_feed_handler, social_followers_handler, social_following_handler,
     social_get_handler, social_index_handler, social_keys_handler, social_profile_handler,
@@ -32,25 +56,43 @@ use tokio::sync::RwLock;
 use utoipa::OpenApi;
 use utoipa_scalar::{Scalar, Servable};
 
-use crate::models::PROJECT_ID;
+use crate::models::{MIN_BLOCK_WAIT_SECS, PROJECT_ID};
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
         handlers::health_check,
         handlers::status_handler,
+        handlers::block_kv_handler,
         handlers::get_kv_handler,
         handlers::query_kv_handler,
         handlers::history_kv_handler,
+        handlers::history_prefix_kv_handler,
         handlers::writers_handler,
         handlers::diff_kv_handler,
+        handlers::diff_tree_kv_handler,
         handlers::timeline_kv_handler,
+        handlers::account_timeline_handler,
+        handlers::contract_timeline_handler,
         handlers::batch_kv_handler,
+        handlers::batch_multi_kv_handler,
+        handlers::query_batch_kv_handler,
         handlers::accounts_handler,
         handlers::contracts_handler,
         handlers::edges_handler,
         handlers::edges_count_handler,
+        handlers::count_kv_handler,
+        handlers::writers_count_handler,
+        handlers::by_tx_handler,
+        handlers::by_receipt_handler,
         handlers::watch_kv_handler,
+        handlers::watch_multi_kv_handler,
+        handlers::watch_multi_kv_get_handler,
+        handlers::wait_kv_handler,
+        ws_handlers::ws_handler,
+        webhook_handlers::create_webhook_handler,
+        webhook_handlers::delete_webhook_handler,
+        webhook_handlers::webhook_dead_letters_handler,
         social_handlers::social_get_handler,
         social_handlers::social_keys_handler,
         social_handlers::social_index_handler,
@@ -69,25 +111,46 @@ use crate::models::PROJECT_ID;
         models::KvEntry,
         models::HealthResponse,
         models::StatusResponse,
+        models::BlockKvParams,
+        models::BlockKvResponse,
+        models::BlockReceiptWrites,
+        models::BlockActionWrites,
         models::GetParams,
         models::QueryParams,
         models::HistoryParams,
+        models::PrefixHistoryParams,
         models::WritersParams,
         models::ApiError,
         models::ErrorCode,
         models::ErrorResponse,
         models::BatchQuery,
         models::BatchResultItem,
+        models::MultiBatchQuery,
+        models::MultiBatchItem,
+        models::MultiBatchResultItem,
+        models::PrefixBatchQuery,
+        models::PrefixBatchItem,
+        models::PrefixBatchResultItem,
         models::TreeResponse,
         models::DiffParams,
         models::DiffResponse,
+        models::DiffTreeParams,
+        models::TreeDiff,
+        models::KvChange,
         models::TimelineParams,
+        models::AccountTimelineParams,
+        models::ContractTimelineParams,
         models::AccountsQueryParams,
         models::ContractsQueryParams,
         models::EdgesParams,
         models::EdgesCountParams,
         models::EdgeSourceEntry,
         models::EdgesCountResponse,
+        models::KeyCountParams,
+        models::WritersCountParams,
+        models::CountResponse,
+        models::TxLookupParams,
+        models::ReceiptLookupParams,
         models::SocialGetBody,
         models::SocialGetOptions,
         models::SocialKeysBody,
@@ -99,8 +162,21 @@ use crate::models::PROJECT_ID;
         models::IndexEntry,
         models::SocialFollowResponse,
         models::PaginationMeta,
+        models::ResolvedBlockRange,
         models::WatchParams,
+        models::WatchMultiRequest,
+        models::WatchMultiParams,
+        models::WaitParams,
+        models::WsClientMessage,
+        models::WatchFilter,
+        models::WsServerMessage,
         models::WatchEvent,
+        models::WebhookRequest,
+        models::WebhookResponse,
+        models::WebhookPayload,
+        models::WebhookChange,
+        models::DeadLetter,
+        models::DeadLettersParams,
         encrypted_handlers::EncryptedSetBody,
         encrypted_handlers::EncryptedGetBody,
         encrypted_handlers::EncryptedBatchBody,
@@ -124,19 +200,27 @@ use crate::models::PROJECT_ID;
     tags(
         (name = "health", description = "Health check endpoints"),
         (name = "kv", description = "Key-Value storage operations"),
-        (name = "social", description = "SocialDB-compatible convenience API")
+        (name = "social", description = "SocialDB-compatible convenience API"),
+        (name = "webhooks", description = "Signed change delivery to registered URLs")
     )
 )]
 struct ApiDoc;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub scylladb: Arc<RwLock<Option<Arc<ScyllaDb>>>>,
+    /// Storage backend; `None` until the first successful connection.
+    pub store: Arc<RwLock<Option<Arc<dyn KvStore>>>>,
     pub chain_id: ChainId,
     /// Per-IP throttle for scan=1 requests on /v1/kv/accounts.
     pub scan_throttle: Arc<std::sync::Mutex<std::collections::HashMap<String, std::time::Instant>>>,
     /// Active SSE watch connection count.
     pub watch_count: Arc<std::sync::atomic::AtomicUsize>,
+    /// Shared pollers behind every watch connection.
+    pub watch_hub: Arc<WatchHub>,
+    /// Registered webhooks and their delivery tasks.
+    pub webhooks: Arc<WebhookRegistry>,
+    /// Cached indexer head that `min_block` requests wait on.
+    pub indexer_head: Arc<IndexerHead>,
 }
 
 #[actix_web::main]
@@ -159,12 +243,17 @@ async fn main() -> std::io::Result<()> {
 
     tracing::info!(target: PROJECT_ID, %chain_id, "Configuration loaded");
 
+    // MEMORY_FIXTURE serves an NDJSON fixture from memory instead of ScyllaDB
+    let memory_fixture = env::var("MEMORY_FIXTURE").ok().filter(|v| !v.is_empty());
+
     // Validate DB env vars early (they're required by the background connection task)
-    for var in ["SCYLLA_URL", "SCYLLA_USERNAME", "SCYLLA_PASSWORD"] {
-        env::var(var).unwrap_or_else(|_| panic!("{var} must be set"));
+    if memory_fixture.is_none() {
+        for var in ["SCYLLA_URL", "SCYLLA_USERNAME", "SCYLLA_PASSWORD"] {
+            env::var(var).unwrap_or_else(|_| panic!("{var} must be set"));
+        }
     }
 
-    let scylladb: Arc<RwLock<Option<Arc<ScyllaDb>>>> = Arc::new(RwLock::new(None));
+    let scylladb: Arc<RwLock<Option<Arc<dyn KvStore>>>> = Arc::new(RwLock::new(None));
 
     // Configuration for reconnection behavior
     let reconnect_base_secs: u64 = env::var("DB_RECONNECT_INTERVAL_SECS")
@@ -182,7 +271,14 @@ async fn main() -> std::io::Result<()> {
         .unwrap_or(0); // 0 = unlimited
 
     // Initial connection attempt (blocking if fail-fast is enabled)
-    if db_fail_fast {
+    if let Some(ref path) = memory_fixture {
+        let store = MemoryStore::load_fixture(path).map_err(|e| {
+            tracing::error!(target: PROJECT_ID, error = %e, "Failed to load memory fixture");
+            std::io::Error::other(format!("Memory fixture load failed: {}", e))
+        })?;
+        *scylladb.write().await = Some(Arc::new(store));
+        tracing::info!(target: PROJECT_ID, path = %path, "Serving in-memory store from fixture");
+    } else if db_fail_fast {
         tracing::info!(target: PROJECT_ID, "Attempting initial ScyllaDB connection (fail-fast mode)...");
         let session = match ScyllaDb::new_scylla_session().await {
             Ok(s) => s,
@@ -208,10 +304,10 @@ async fn main() -> std::io::Result<()> {
             }
             Err(e) => {
                 tracing::error!(target: PROJECT_ID, error = %e, "ScyllaDB initialization failed on startup");
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("ScyllaDB init failed: {}", e)
-                ));
+                return Err(std::io::Error::other(format!(
+                    "ScyllaDB init failed: {}",
+                    e
+                )));
             }
         }
     } else {
@@ -219,7 +315,7 @@ async fn main() -> std::io::Result<()> {
     }
 
     // Background reconnection task with exponential backoff
-    {
+    if memory_fixture.is_none() {
         let scylladb = Arc::clone(&scylladb);
         tokio::spawn(async move {
             let mut delay_secs = reconnect_base_secs;
@@ -296,6 +392,66 @@ async fn main() -> std::io::Result<()> {
         });
     }
 
+    // min_block requests wait on the same cache, refreshing it while they do
+    let indexer_head = Arc::new(IndexerHead::new(
+        Arc::clone(&indexer_block_cache),
+        std::time::Duration::from_secs(MIN_BLOCK_WAIT_SECS),
+    ));
+
+    // Shared watch pollers, one round per indexer block advance
+    let watch_hub = Arc::new(WatchHub::default());
+    tokio::spawn(
+        Arc::clone(&watch_hub).run(Arc::clone(&scylladb), Arc::clone(&indexer_block_cache)),
+    );
+    let watch_count = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    // Webhooks persist under WEBHOOK_DATA_DIR when set and resume delivery on
+    // startup; otherwise, or if the directory can't be created, they live in memory
+    let webhook_dir = env::var("WEBHOOK_DATA_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty());
+    let webhook_retry = RetryPolicy {
+        attempts: env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5),
+        backoff: std::time::Duration::from_millis(
+            env::var("WEBHOOK_RETRY_BACKOFF_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1000),
+        ),
+    };
+    // Registration needs WEBHOOK_OPERATOR_TOKEN; WEBHOOK_ALLOWED_HOSTS lists
+    // hosts that may resolve to private addresses
+    let webhook_access = WebhookAccess {
+        operator_token: env::var("WEBHOOK_OPERATOR_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty()),
+        allowed_hosts: env::var("WEBHOOK_ALLOWED_HOSTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|host| host.trim().to_string())
+            .filter(|host| !host.is_empty())
+            .collect(),
+    };
+    if webhook_access.operator_token.is_none() {
+        tracing::info!(target: PROJECT_ID, "WEBHOOK_OPERATOR_TOKEN not set, webhook registration disabled");
+    }
+    let webhooks = match &webhook_dir {
+        Some(dir) => WebhookRegistry::open(dir.into(), webhook_retry).unwrap_or_else(|e| {
+            tracing::warn!(target: PROJECT_ID, %dir, error = %e, "Failed to open webhook directory, keeping webhooks in memory");
+            WebhookRegistry::in_memory(webhook_retry)
+        }),
+        None => {
+            tracing::info!(target: PROJECT_ID, "WEBHOOK_DATA_DIR not set, keeping webhooks in memory");
+            WebhookRegistry::in_memory(webhook_retry)
+        }
+    };
+    let webhooks = Arc::new(webhooks.with_access(webhook_access));
+    let resumed = webhooks.resume(&watch_hub, &scylladb);
+    tracing::info!(target: PROJECT_ID, dir = ?webhook_dir, resumed, "Webhooks loaded");
+
     let scan_throttle = Arc::new(std::sync::Mutex::new(std::collections::HashMap::<
         String,
         std::time::Instant,
@@ -304,28 +460,41 @@ async fn main() -> std::io::Result<()> {
     let port = env::var("PORT").unwrap_or_else(|_| "3001".to_string());
     tracing::info!(target: PROJECT_ID, %port, "Binding HTTP server");
 
+    let shutdown_webhooks = Arc::clone(&webhooks);
     HttpServer::new(move || {
         let block_cache = Arc::clone(&indexer_block_cache);
 
         // Configure CORS middleware
         let cors = Cors::default()
             .allow_any_origin()
-            .allowed_methods(vec!["GET", "POST"])
-            .allowed_headers(vec![header::CONTENT_TYPE, header::ACCEPT, header::HeaderName::from_static("x-payment-key")])
+            .allowed_methods(vec!["GET", "POST", "DELETE"])
+            .allowed_headers(vec![
+                header::CONTENT_TYPE,
+                header::ACCEPT,
+                header::AUTHORIZATION,
+                header::HeaderName::from_static("x-payment-key"),
+                header::HeaderName::from_static("x-webhook-secret"),
+                header::IF_NONE_MATCH,
+            ])
             .expose_headers(vec![
                 "X-Results-Truncated",
                 "X-Indexer-Block",
+                "ETag",
             ])
             .max_age(3600);
 
         App::new()
             .app_data(web::JsonConfig::default().limit(262_144))
             .app_data(web::Data::new(AppState {
-                scylladb: Arc::clone(&scylladb),
+                store: Arc::clone(&scylladb),
                 chain_id,
                 scan_throttle: scan_throttle.clone(),
-                watch_count: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+                watch_count: watch_count.clone(),
+                watch_hub: watch_hub.clone(),
+                webhooks: webhooks.clone(),
+                indexer_head: indexer_head.clone(),
             }))
+            .wrap(middleware::from_fn(wait_for_min_block))
             .wrap(cors)
             .wrap_fn({
                 let cache = block_cache;
@@ -342,10 +511,11 @@ async fn main() -> std::io::Result<()> {
                                 header::HeaderValue::from(h),
                             );
                         }
-                        // Default Cache-Control for successful GET API responses.
+                        // Default Cache-Control for successful (or 304) GET API responses.
                         // Handlers that set their own Cache-Control header take precedence.
                         if method == actix_web::http::Method::GET
-                            && res.status().is_success()
+                            && (res.status().is_success()
+                                || res.status() == actix_web::http::StatusCode::NOT_MODIFIED)
                             && !res.headers().contains_key(header::CACHE_CONTROL)
                         {
                             let cc = if path == "/health" || path == "/v1/status" {
@@ -383,18 +553,36 @@ async fn main() -> std::io::Result<()> {
             .service(Scalar::with_url("/docs", ApiDoc::openapi()))
             .service(health_check)
             .service(status_handler)
+            .service(block_kv_handler)
             .service(get_kv_handler)
             .service(query_kv_handler)
             .service(history_kv_handler)
+            .service(history_prefix_kv_handler)
             .service(writers_handler)
             .service(batch_kv_handler)
+            .service(batch_multi_kv_handler)
+            .service(query_batch_kv_handler)
             .service(diff_kv_handler)
+            .service(diff_tree_kv_handler)
             .service(timeline_kv_handler)
+            .service(account_timeline_handler)
+            .service(contract_timeline_handler)
             .service(accounts_handler)
             .service(contracts_handler)
             .service(edges_handler)
             .service(edges_count_handler)
+            .service(count_kv_handler)
+            .service(writers_count_handler)
+            .service(by_tx_handler)
+            .service(by_receipt_handler)
             .service(watch_kv_handler)
+            .service(watch_multi_kv_handler)
+            .service(watch_multi_kv_get_handler)
+            .service(wait_kv_handler)
+            .service(ws_handler)
+            .service(create_webhook_handler)
+            .service(delete_webhook_handler)
+            .service(webhook_dead_letters_handler)
             .service(social_get_handler)
             .service(social_keys_handler)
             .service(social_index_handler)
@@ -414,5 +602,8 @@ async fn main() -> std::io::Result<()> {
     .run()
     .await?;
 
+    // Persist the newest delivery positions before exiting
+    shutdown_webhooks.flush().await;
+
     Ok(())
 }
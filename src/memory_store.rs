@@ -0,0 +1,1573 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::convert::Infallible;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::{RwLock, RwLockReadGuard};
+
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+
+use crate::models::{
+    bigint_to_u64, AccountsParams, ContractTimelineParams, EdgeSourceEntry, HistoryParams, KvEntry,
+    KvHistoryRow, KvRow, PrefixHistoryParams, QueryParams, TimelineParams, WritersParams,
+    MAX_DEDUP_SCAN, MAX_FILTER_SCAN,
+};
+use crate::scylladb::{
+    after_contract_cursor, collect_page, collect_page_budgeted, compute_prefix_end,
+    contract_timeline_cursor, count_live_rows, effective_offset, page_as_of, parse_where, KeyRange,
+    QueryFilter,
+};
+use crate::store::{KvRowStream, KvStore};
+
+/// `(predecessor_id, current_account_id)` — partition of the per-writer tables.
+type WriterPartition = (String, String);
+/// `(predecessor_id, current_account_id, key)` — partition of `s_kv`.
+type KeyPartition = (String, String, String);
+/// `(block_height, order_id, predecessor_id, key)` — clustering of `s_kv_by_contract`.
+type ContractClustering = (i64, i64, String, String);
+
+/// In-memory mirror of the ScyllaDB tables. Each map is named after the table
+/// it stands in for and is keyed the same way (partition key, then clustering
+/// columns), so range scans walk rows in the order CQL would return them.
+#[derive(Default)]
+struct Tables {
+    /// `s_kv`: every write, clustered by `(block_height, order_id)`.
+    history: BTreeMap<KeyPartition, BTreeMap<(i64, i64), KvHistoryRow>>,
+    /// `s_kv_last`: newest write per key.
+    latest: BTreeMap<WriterPartition, BTreeMap<String, KvHistoryRow>>,
+    /// `s_kv_by_block`: newest write per `(block_height, key)`.
+    by_block: BTreeMap<WriterPartition, BTreeMap<(i64, String), KvHistoryRow>>,
+    /// `s_kv_by_contract`: every write to a contract, clustered by
+    /// `(block_height, order_id, predecessor_id, key)`. Block buckets are not modeled.
+    by_contract: BTreeMap<String, BTreeMap<ContractClustering, KvHistoryRow>>,
+    /// `kv_reverse` / `mv_kv_cur_key`: `(contract, key)` → writer → newest write.
+    reverse: BTreeMap<(String, String), BTreeMap<String, KvHistoryRow>>,
+    /// `kv_accounts`: contract → `(key, predecessor_id)`.
+    accounts: BTreeMap<String, BTreeSet<(String, String)>>,
+    /// `all_accounts`.
+    all_accounts: BTreeSet<String>,
+    /// `kv_edges`: `(edge_type, target)` → source → block_height.
+    edges: BTreeMap<(String, String), BTreeMap<String, i64>>,
+    /// `meta.last_processed_block_height`: highest block applied so far.
+    indexer_block: i64,
+}
+
+impl Tables {
+    fn apply(&mut self, row: KvHistoryRow) {
+        let version = (row.block_height, row.order_id);
+        let partition = (row.predecessor_id.clone(), row.current_account_id.clone());
+
+        self.indexer_block = self.indexer_block.max(row.block_height);
+        self.all_accounts.insert(row.predecessor_id.clone());
+        self.accounts
+            .entry(row.current_account_id.clone())
+            .or_default()
+            .insert((row.key.clone(), row.predecessor_id.clone()));
+
+        self.by_contract
+            .entry(row.current_account_id.clone())
+            .or_default()
+            .insert(
+                (
+                    row.block_height,
+                    row.order_id,
+                    row.predecessor_id.clone(),
+                    row.key.clone(),
+                ),
+                row.clone(),
+            );
+
+        let by_block = self.by_block.entry(partition.clone()).or_default();
+        let block_key = (row.block_height, row.key.clone());
+        if by_block
+            .get(&block_key)
+            .is_none_or(|cur| cur.order_id <= row.order_id)
+        {
+            by_block.insert(block_key, row.clone());
+        }
+
+        let is_newest = self
+            .latest
+            .get(&partition)
+            .and_then(|keys| keys.get(&row.key))
+            .is_none_or(|cur| (cur.block_height, cur.order_id) <= version);
+        if is_newest {
+            if let Some((edge_type, target)) = edge_for_key(&row.key) {
+                let sources = self
+                    .edges
+                    .entry((edge_type.to_string(), target.to_string()))
+                    .or_default();
+                if row.value == "null" {
+                    sources.remove(&row.predecessor_id);
+                } else {
+                    sources.insert(row.predecessor_id.clone(), row.block_height);
+                }
+            }
+            self.reverse
+                .entry((row.current_account_id.clone(), row.key.clone()))
+                .or_default()
+                .insert(row.predecessor_id.clone(), row.clone());
+            self.latest
+                .entry(partition)
+                .or_default()
+                .insert(row.key.clone(), row.clone());
+        }
+
+        self.history
+            .entry((
+                row.predecessor_id.clone(),
+                row.current_account_id.clone(),
+                row.key.clone(),
+            ))
+            .or_default()
+            .insert(version, row);
+    }
+}
+
+/// `graph/<kind>/<target>` keys produce a `graph/<kind>` edge from the writer
+/// to `<target>`; writing `null` removes it.
+fn edge_for_key(key: &str) -> Option<(&str, &str)> {
+    if !key.starts_with("graph/") {
+        return None;
+    }
+    let (edge_type, target) = key.rsplit_once('/')?;
+    if edge_type == "graph" || target.is_empty() {
+        return None;
+    }
+    Some((edge_type, target))
+}
+
+/// Project a history row onto the `s_kv_last` column set.
+fn kv_row(row: &KvHistoryRow) -> KvRow {
+    KvRow {
+        predecessor_id: row.predecessor_id.clone(),
+        current_account_id: row.current_account_id.clone(),
+        key: row.key.clone(),
+        value: row.value.clone(),
+        block_height: row.block_height,
+        block_timestamp: row.block_timestamp,
+        receipt_id: row.receipt_id.clone(),
+        tx_hash: row.tx_hash.clone(),
+    }
+}
+
+/// Wrap already-materialized rows so they can go through `collect_page`.
+fn rows_stream<T>(rows: Vec<T>) -> impl futures::Stream<Item = Result<T, Infallible>> + Unpin {
+    stream::iter(rows.into_iter().map(Ok))
+}
+
+/// `KvStore` backed by in-process BTreeMaps, for local development, demos and
+/// tests. Rows are `KvHistoryRow` writes; the latest-value, by-block, reverse,
+/// accounts and edges views are derived on insert.
+#[derive(Default)]
+pub struct MemoryStore {
+    tables: RwLock<Tables>,
+}
+
+impl MemoryStore {
+    /// Load an NDJSON fixture file: one `KvHistoryRow` JSON object per line.
+    pub fn load_fixture(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read fixture '{}': {}", path.display(), e))?;
+        Self::from_ndjson(&contents)
+    }
+
+    pub fn from_ndjson(contents: &str) -> anyhow::Result<Self> {
+        let store = Self::default();
+        for (idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let row: KvHistoryRow = serde_json::from_str(line)
+                .map_err(|e| anyhow::anyhow!("Invalid fixture row on line {}: {}", idx + 1, e))?;
+            store.insert(row);
+        }
+        Ok(store)
+    }
+
+    /// Apply one write to every derived view, as the indexer would.
+    pub fn insert(&self, row: KvHistoryRow) {
+        self.tables
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .apply(row);
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Tables> {
+        self.tables.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Writes matching `filter`, in `mv_kv_by_tx` / `mv_kv_by_receipt` /
+    /// `mv_kv_by_block` clustering order. Scans all of `history`; fixtures are small.
+    fn writes_where(
+        &self,
+        filter: impl Fn(&KvHistoryRow) -> bool,
+        limit: usize,
+        offset: usize,
+    ) -> (Vec<KvEntry>, bool, usize) {
+        let mut rows: Vec<KvHistoryRow> = self
+            .read()
+            .history
+            .values()
+            .flat_map(|writes| writes.values())
+            .filter(|row| filter(row))
+            .cloned()
+            .collect();
+        rows.sort_by(|a, b| {
+            (a.block_height, a.order_id, &a.predecessor_id, &a.current_account_id, &a.key).cmp(&(
+                b.block_height,
+                b.order_id,
+                &b.predecessor_id,
+                &b.current_account_id,
+                &b.key,
+            ))
+        });
+        let mut entries: Vec<KvEntry> = rows
+            .into_iter()
+            .skip(offset)
+            .take(limit + 1)
+            .map(KvEntry::from)
+            .collect();
+        let has_more = entries.len() > limit;
+        entries.truncate(limit);
+        (entries, has_more, 0)
+    }
+
+    /// `at_block` rows for `query_kv_with_pagination`: every version in the key
+    /// range from `history`, in `history_range` order.
+    fn history_versions(&self, params: &QueryParams, range: &KeyRange) -> Vec<KvHistoryRow> {
+        let partition = |key: &str| {
+            (
+                params.predecessor_id.clone(),
+                params.current_account_id.clone(),
+                key.to_string(),
+            )
+        };
+        let bounds = (
+            Bound::Included(partition(&range.lower)),
+            Bound::Excluded(partition(&range.upper)),
+        );
+
+        let tables = self.read();
+        tables
+            .history
+            .range(bounds)
+            .flat_map(|(_, writes)| writes.values().cloned())
+            .collect()
+    }
+
+    /// Current rows of a `(contract, key)` reverse partition, in `predecessor_id` order.
+    fn reverse_rows(&self, contract_id: &str, key: &str, after_account: Option<&str>) -> Vec<KvRow> {
+        let tables = self.read();
+        let Some(writers) = tables
+            .reverse
+            .get(&(contract_id.to_string(), key.to_string()))
+        else {
+            return Vec::new();
+        };
+        let lower = after_account.map_or(Bound::Unbounded, Bound::Excluded);
+        writers
+            .range::<str, _>((lower, Bound::Unbounded))
+            .map(|(_, row)| kv_row(row))
+            .collect()
+    }
+}
+
+#[async_trait]
+impl KvStore for MemoryStore {
+    async fn health_check(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    async fn get_kv(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<Option<KvEntry>> {
+        let tables = self.read();
+        Ok(tables
+            .latest
+            .get(&(predecessor_id.to_string(), current_account_id.to_string()))
+            .and_then(|keys| keys.get(key))
+            .map(|row| KvEntry::from(kv_row(row))))
+    }
+
+    async fn get_kv_last(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let tables = self.read();
+        Ok(tables
+            .latest
+            .get(&(predecessor_id.to_string(), current_account_id.to_string()))
+            .and_then(|keys| keys.get(key))
+            .map(|row| row.value.clone()))
+    }
+
+    async fn query_writers(
+        &self,
+        params: &WritersParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
+        let rows = self.reverse_rows(
+            &params.current_account_id,
+            &params.key,
+            params.after_account.as_deref(),
+        );
+
+        let exclude_deleted = params.exclude_deleted.unwrap_or(false);
+        let (predicate, budget) = parse_where(params.where_clause.as_deref())?;
+        let offset = effective_offset(params.after_account.as_deref(), params.offset);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
+            &mut rows_stream(rows),
+            params.limit,
+            offset,
+            budget,
+            |row: KvRow| {
+                let entry = KvEntry::from(row);
+                if predicate.is_some() {
+                    last_scanned = Some(entry.predecessor_id.clone());
+                }
+                if let Some(ref pred) = params.predecessor_id {
+                    if entry.predecessor_id != *pred {
+                        return None;
+                    }
+                }
+                if exclude_deleted && entry.value == "null" {
+                    return None;
+                }
+                if predicate.as_ref().is_some_and(|p| !p.matches(&entry.value)) {
+                    return None;
+                }
+                Some(entry)
+            },
+        )
+        .await;
+
+        let next_cursor = if page.truncated {
+            last_scanned
+        } else {
+            page.items.last().map(|e| e.predecessor_id.clone())
+        };
+        Ok((
+            page.items,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
+    }
+
+    async fn query_accounts(
+        &self,
+        params: &AccountsParams,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)> {
+        let rows = self.reverse_rows(
+            &params.current_account_id,
+            &params.key,
+            params.after_account.as_deref(),
+        );
+
+        let exclude_deleted = params.exclude_deleted.unwrap_or(false);
+        let offset = effective_offset(params.after_account.as_deref(), params.offset);
+        let page = collect_page(&mut rows_stream(rows), params.limit, offset, None, |row: KvRow| {
+            if exclude_deleted && row.value == "null" {
+                return None;
+            }
+            Some(row.predecessor_id)
+        })
+        .await;
+
+        Ok((page.items, page.has_more, page.dropped_rows))
+    }
+
+    async fn query_accounts_by_contract(
+        &self,
+        contract_id: &str,
+        key: Option<&str>,
+        limit: usize,
+        offset: usize,
+        after_account: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, bool, usize)> {
+        let needs_dedup = key.is_none();
+        let writers: Vec<String> = {
+            let tables = self.read();
+            tables
+                .accounts
+                .get(contract_id)
+                .map(|rows| {
+                    rows.iter()
+                        .filter(|(k, _)| key.is_none_or(|want| k == want))
+                        .map(|(_, pred)| pred.clone())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut seen = HashSet::new();
+        let mut accounts = Vec::new();
+        let target_count = offset + limit + 1;
+        let mut truncated = false;
+
+        for pred in writers {
+            if needs_dedup {
+                if seen.len() >= MAX_DEDUP_SCAN {
+                    truncated = true;
+                    break;
+                }
+                if !seen.insert(pred.clone()) {
+                    continue;
+                }
+            }
+            if after_account.is_some_and(|cursor| pred.as_str() <= cursor) {
+                continue;
+            }
+            accounts.push(pred);
+            if accounts.len() >= target_count {
+                break;
+            }
+        }
+
+        let mut result: Vec<String> = if after_account.is_some() {
+            accounts
+        } else {
+            accounts.into_iter().skip(offset).collect()
+        };
+        let has_more = result.len() > limit;
+        result.truncate(limit);
+
+        Ok((result, has_more, truncated, 0))
+    }
+
+    async fn query_all_accounts(
+        &self,
+        limit: usize,
+        after_account: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)> {
+        let tables = self.read();
+        let lower = after_account.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut accounts: Vec<String> = tables
+            .all_accounts
+            .range::<str, _>((lower, Bound::Unbounded))
+            .take(limit + 1)
+            .cloned()
+            .collect();
+        let has_more = accounts.len() > limit;
+        accounts.truncate(limit);
+        Ok((accounts, has_more, 0))
+    }
+
+    async fn query_all_contracts(
+        &self,
+        limit: usize,
+        after_contract: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)> {
+        let tables = self.read();
+        let lower = after_contract.map_or(Bound::Unbounded, Bound::Excluded);
+        let mut contracts: Vec<String> = tables
+            .accounts
+            .range::<str, _>((lower, Bound::Unbounded))
+            .take(limit + 1)
+            .map(|(contract, _)| contract.clone())
+            .collect();
+        let has_more = contracts.len() > limit;
+        contracts.truncate(limit);
+        Ok((contracts, has_more, 0))
+    }
+
+    async fn query_contracts_by_account(
+        &self,
+        account_id: &str,
+        limit: usize,
+        after_contract: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)> {
+        let tables = self.read();
+        let mut contracts: Vec<String> = tables
+            .latest
+            .range((account_id.to_string(), String::new())..)
+            .take_while(|((pred, _), _)| pred == account_id)
+            .map(|((_, contract), _)| contract)
+            .filter(|contract| after_contract.is_none_or(|c| contract.as_str() > c))
+            .take(limit + 1)
+            .cloned()
+            .collect();
+        let has_more = contracts.len() > limit;
+        contracts.truncate(limit);
+        Ok((contracts, has_more, 0))
+    }
+
+    async fn query_kv_with_pagination(
+        &self,
+        params: &QueryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
+        let filter = QueryFilter::from_params(params)?;
+        let range = KeyRange::new(params, filter.scan_prefix(params));
+        if let Some(block_height) = params.at_block.filter(|_| !range.is_empty()) {
+            // History rows keep their signer/receipt metadata, as on ScyllaDB.
+            let mut rows = self.history_versions(params, &range);
+            if params.order.eq_ignore_ascii_case("desc") {
+                rows.reverse();
+            }
+            return Ok(page_as_of(rows_stream(rows), params, &filter, block_height).await);
+        }
+        let mut rows: Vec<KvEntry> = if range.is_empty() {
+            Vec::new()
+        } else {
+            let tables = self.read();
+            match tables.latest.get(&(
+                params.predecessor_id.clone(),
+                params.current_account_id.clone(),
+            )) {
+                Some(keys) => keys
+                    .range::<str, _>((
+                        Bound::Included(range.lower.as_str()),
+                        Bound::Excluded(range.upper.as_str()),
+                    ))
+                    .map(|(_, row)| KvEntry::from(kv_row(row)))
+                    .collect(),
+                None => Vec::new(),
+            }
+        };
+        if params.order.eq_ignore_ascii_case("desc") {
+            rows.reverse();
+        }
+
+        let exclude_deleted = params.exclude_deleted.unwrap_or(false);
+        let cursor = params.after_key.as_deref().or(params.before_key.as_deref());
+        let offset = effective_offset(cursor, params.offset);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
+            &mut rows_stream(rows),
+            params.limit,
+            offset,
+            filter.budget(),
+            |entry: KvEntry| {
+                if filter.is_active() {
+                    last_scanned = Some(entry.key.clone());
+                }
+                if exclude_deleted && entry.value == "null" {
+                    return None;
+                }
+                if !filter.matches(&entry) {
+                    return None;
+                }
+                Some(entry)
+            },
+        )
+        .await;
+
+        let next_cursor = if page.truncated {
+            last_scanned
+        } else {
+            page.items.last().map(|e| e.key.clone())
+        };
+        Ok((
+            page.items,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
+    }
+
+    async fn get_kv_at_block(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key: &str,
+        block_height: i64,
+    ) -> anyhow::Result<Option<KvEntry>> {
+        let tables = self.read();
+        Ok(tables
+            .history
+            .get(&(
+                predecessor_id.to_string(),
+                current_account_id.to_string(),
+                key.to_string(),
+            ))
+            .and_then(|writes| writes.range(..=(block_height, i64::MAX)).next_back())
+            .map(|(_, row)| KvEntry::from(row.clone())))
+    }
+
+    async fn get_kv_timeline(
+        &self,
+        params: &TimelineParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)> {
+        let (entries, has_more, _, dropped, next_cursor) = self
+            .get_kv_prefix_history(&PrefixHistoryParams::from(params))
+            .await?;
+        Ok((entries, has_more, dropped, next_cursor))
+    }
+
+    async fn get_kv_prefix_history(
+        &self,
+        params: &PrefixHistoryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
+        let is_asc = params.order.eq_ignore_ascii_case("asc");
+
+        let cursor = match &params.cursor {
+            Some(c) if !c.is_empty() => Some(
+                crate::models::parse_timeline_cursor(c).map_err(|e| anyhow::anyhow!("{e}"))?,
+            ),
+            _ => None,
+        };
+
+        let mut from_block = params.from_block.unwrap_or(0);
+        let mut to_block = params.to_block.unwrap_or(i64::MAX);
+        if let Some((cb, _)) = &cursor {
+            if is_asc {
+                from_block = from_block.max(*cb);
+            } else {
+                to_block = to_block.min(*cb);
+            }
+        }
+
+        // s_kv_by_block clusters (block_height DESC, key ASC); asc reverses both.
+        let mut rows: Vec<KvHistoryRow> = {
+            let tables = self.read();
+            tables
+                .by_block
+                .get(&(
+                    params.predecessor_id.clone(),
+                    params.current_account_id.clone(),
+                ))
+                .map(|writes| {
+                    writes
+                        .range((from_block, String::new())..)
+                        .take_while(|((bh, _), _)| *bh <= to_block)
+                        .map(|(_, row)| row.clone())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        if is_asc {
+            rows.sort_by(|a, b| a.block_height.cmp(&b.block_height).then(b.key.cmp(&a.key)));
+        } else {
+            rows.sort_by(|a, b| b.block_height.cmp(&a.block_height).then(a.key.cmp(&b.key)));
+        }
+
+        let budget = (!params.key_prefix.is_empty()).then_some(MAX_FILTER_SCAN);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
+            &mut rows_stream(rows),
+            params.limit,
+            0,
+            budget,
+            |row: KvHistoryRow| {
+                if let Some((cb, ref ck)) = cursor {
+                    if row.block_height == cb {
+                        if is_asc {
+                            if row.key.as_str() >= ck.as_str() {
+                                return None;
+                            }
+                        } else if row.key.as_str() <= ck.as_str() {
+                            return None;
+                        }
+                    }
+                }
+                last_scanned = Some((row.block_height, row.key.clone()));
+                if !row.key.starts_with(params.key_prefix.as_str()) {
+                    return None;
+                }
+                let key = row.key.clone();
+                Some((KvEntry::from(row), key))
+            },
+        )
+        .await;
+
+        let next_cursor = if page.truncated {
+            last_scanned.map(|(bh, key)| format!("{bh}:{key}"))
+        } else {
+            page.items
+                .last()
+                .map(|(e, key)| format!("{}:{key}", e.block_height))
+        };
+        let entries: Vec<KvEntry> = page.items.into_iter().map(|(e, _)| e).collect();
+
+        Ok((
+            entries,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
+    }
+
+    async fn get_contract_timeline(
+        &self,
+        params: &ContractTimelineParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)> {
+        let is_asc = params.order.eq_ignore_ascii_case("asc");
+
+        let cursor = match &params.cursor {
+            Some(c) if !c.is_empty() => Some(
+                crate::models::parse_contract_timeline_cursor(c)
+                    .map_err(|e| anyhow::anyhow!("{e}"))?,
+            ),
+            _ => None,
+        };
+
+        let mut rows: Vec<KvHistoryRow> = self
+            .read()
+            .by_contract
+            .get(&params.current_account_id)
+            .map(|writes| {
+                writes
+                    .range((params.from_block, i64::MIN, String::new(), String::new())..)
+                    .take_while(|((bh, ..), _)| *bh <= params.to_block)
+                    .map(|(_, row)| row.clone())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !is_asc {
+            rows.reverse();
+        }
+
+        let page = collect_page(
+            &mut rows_stream(rows),
+            params.limit,
+            0,
+            None,
+            |row| match &cursor {
+                Some(c) if !after_contract_cursor(&row, c, is_asc) => None,
+                _ => Some(row),
+            },
+        )
+        .await;
+
+        let next_cursor = page.items.last().map(contract_timeline_cursor);
+        let entries = page.items.into_iter().map(KvEntry::from).collect();
+        Ok((entries, page.has_more, page.dropped_rows, next_cursor))
+    }
+
+    async fn query_edges(
+        &self,
+        edge_type: &str,
+        target: &str,
+        limit: usize,
+        offset: usize,
+        after_source: Option<&str>,
+    ) -> anyhow::Result<(Vec<EdgeSourceEntry>, bool, usize)> {
+        let rows: Vec<EdgeSourceEntry> = {
+            let tables = self.read();
+            let lower = after_source.map_or(Bound::Unbounded, Bound::Excluded);
+            tables
+                .edges
+                .get(&(edge_type.to_string(), target.to_string()))
+                .map(|sources| {
+                    sources
+                        .range::<str, _>((lower, Bound::Unbounded))
+                        .map(|(source, block_height)| EdgeSourceEntry {
+                            source: source.clone(),
+                            block_height: crate::models::bigint_to_u64(*block_height),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let offset = effective_offset(after_source, offset);
+        let page = collect_page(&mut rows_stream(rows), limit, offset, None, Some).await;
+
+        Ok((page.items, page.has_more, page.dropped_rows))
+    }
+
+    async fn count_edges(&self, edge_type: &str, target: &str) -> anyhow::Result<usize> {
+        let tables = self.read();
+        Ok(tables
+            .edges
+            .get(&(edge_type.to_string(), target.to_string()))
+            .map_or(0, |sources| sources.len()))
+    }
+
+    async fn count_keys(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key_prefix: Option<&str>,
+        exclude_deleted: bool,
+    ) -> anyhow::Result<(usize, bool)> {
+        let rows: Vec<KvRow> = {
+            let tables = self.read();
+            let Some(keys) = tables
+                .latest
+                .get(&(predecessor_id.to_string(), current_account_id.to_string()))
+            else {
+                return Ok((0, false));
+            };
+            let prefix = key_prefix.unwrap_or("");
+            let prefix_end = compute_prefix_end(prefix);
+            keys.range::<str, _>((
+                Bound::Included(prefix),
+                Bound::Excluded(prefix_end.as_str()),
+            ))
+            .map(|(_, row)| kv_row(row))
+            .collect()
+        };
+        if exclude_deleted {
+            return count_live_rows(rows_stream(rows)).await;
+        }
+        Ok((rows.len(), false))
+    }
+
+    async fn count_writers(
+        &self,
+        current_account_id: &str,
+        key: &str,
+        exclude_deleted: bool,
+    ) -> anyhow::Result<(usize, bool)> {
+        let rows = self.reverse_rows(current_account_id, key, None);
+        if exclude_deleted {
+            return count_live_rows(rows_stream(rows)).await;
+        }
+        Ok((rows.len(), false))
+    }
+
+    async fn get_kv_history(
+        &self,
+        params: &HistoryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
+        let is_asc = params.order.eq_ignore_ascii_case("asc");
+
+        let cursor = match &params.cursor {
+            Some(c) if !c.is_empty() => Some(
+                crate::models::parse_history_cursor(c).map_err(|e| anyhow::anyhow!("{e}"))?,
+            ),
+            _ => None,
+        };
+
+        let mut from_block = params.from_block.unwrap_or(0);
+        let mut to_block = params.to_block.unwrap_or(i64::MAX);
+        if let Some((cb, _)) = cursor {
+            if is_asc {
+                from_block = from_block.max(cb);
+            } else {
+                to_block = to_block.min(cb);
+            }
+        }
+
+        let mut rows: Vec<KvHistoryRow> = {
+            let tables = self.read();
+            tables
+                .history
+                .get(&(
+                    params.predecessor_id.clone(),
+                    params.current_account_id.clone(),
+                    params.key.clone(),
+                ))
+                .filter(|_| from_block <= to_block)
+                .map(|writes| {
+                    writes
+                        .range((from_block, i64::MIN)..=(to_block, i64::MAX))
+                        .map(|(_, row)| row.clone())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        if !is_asc {
+            rows.reverse();
+        }
+
+        let budget = params.signer_id.is_some().then_some(MAX_FILTER_SCAN);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
+            &mut rows_stream(rows),
+            params.limit,
+            0,
+            budget,
+            |row: KvHistoryRow| {
+                if let Some((cb, co)) = cursor {
+                    if row.block_height == cb {
+                        if is_asc {
+                            if row.order_id <= co {
+                                return None;
+                            }
+                        } else if row.order_id >= co {
+                            return None;
+                        }
+                    }
+                }
+                last_scanned = Some((row.block_height, row.order_id));
+                if params.signer_id.as_deref().is_some_and(|signer| row.signer_id != signer) {
+                    return None;
+                }
+                let oid = row.order_id;
+                Some((KvEntry::from(row), oid))
+            },
+        )
+        .await;
+
+        let next_cursor = if page.truncated {
+            last_scanned.map(|(bh, oid)| format!("{bh}:{oid}"))
+        } else {
+            page.items
+                .last()
+                .map(|(e, oid)| format!("{}:{oid}", e.block_height))
+        };
+        let entries: Vec<KvEntry> = page.items.into_iter().map(|(e, _)| e).collect();
+
+        Ok((
+            entries,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
+    }
+
+    async fn query_kv_by_tx(
+        &self,
+        tx_hash: &str,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
+        Ok(self.writes_where(|row| row.tx_hash == tx_hash, limit, offset))
+    }
+
+    async fn query_kv_by_receipt(
+        &self,
+        receipt_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
+        Ok(self.writes_where(|row| row.receipt_id == receipt_id, limit, offset))
+    }
+
+    async fn query_kv_by_block(
+        &self,
+        block_height: i64,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
+        Ok(self.writes_where(|row| row.block_height == block_height, limit, 0))
+    }
+
+    async fn first_block_at_or_after(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        let tables = self.read();
+        let partition = (predecessor_id.to_string(), current_account_id.to_string());
+        Ok(tables.by_block.get(&partition).and_then(|rows| {
+            rows.values()
+                .find(|row| bigint_to_u64(row.block_timestamp) >= timestamp_ns)
+                .map(|row| bigint_to_u64(row.block_height))
+        }))
+    }
+
+    async fn last_block_at_or_before(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        let tables = self.read();
+        let partition = (predecessor_id.to_string(), current_account_id.to_string());
+        Ok(tables.by_block.get(&partition).and_then(|rows| {
+            rows.values()
+                .rev()
+                .find(|row| bigint_to_u64(row.block_timestamp) <= timestamp_ns)
+                .map(|row| bigint_to_u64(row.block_height))
+        }))
+    }
+
+    async fn get_indexer_block_height(&self) -> anyhow::Result<Option<u64>> {
+        let tables = self.read();
+        Ok((tables.indexer_block > 0).then_some(tables.indexer_block as u64))
+    }
+
+    async fn stream_reverse_kv(
+        &self,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<KvRowStream> {
+        // mv_kv_cur_key order: block_height DESC, order_id DESC, predecessor_id DESC.
+        let mut rows: Vec<KvHistoryRow> = {
+            let tables = self.read();
+            tables
+                .reverse
+                .get(&(current_account_id.to_string(), key.to_string()))
+                .map(|writers| writers.values().cloned().collect())
+                .unwrap_or_default()
+        };
+        rows.sort_by(|a, b| {
+            (b.block_height, b.order_id, &b.predecessor_id).cmp(&(
+                a.block_height,
+                a.order_id,
+                &a.predecessor_id,
+            ))
+        });
+
+        let rows: Vec<anyhow::Result<KvRow>> = rows.iter().map(|row| Ok(kv_row(row))).collect();
+        Ok(stream::iter(rows).boxed())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sample_store, write};
+
+    fn history_params(order: &str) -> HistoryParams {
+        HistoryParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key: "profile/name".to_string(),
+            limit: 10,
+            order: order.to_string(),
+            from_block: None,
+            to_block: None,
+            fields: None,
+            value_format: None,
+            cursor: None,
+            signer_id: None,
+            from_time: None,
+            to_time: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latest_view_keeps_newest_write() {
+        let store = sample_store();
+        // Out-of-order insert must not regress the latest value
+        store.insert(write("alice.near", "profile/name", "\"Old\"", 90, 0));
+
+        let entry = store
+            .get_kv("alice.near", "social.near", "profile/name")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.value, "\"Alicia\"");
+        assert_eq!(entry.block_height, 120);
+
+        let (entries, ..) = store.get_kv_history(&history_params("desc")).await.unwrap();
+        let heights: Vec<u64> = entries.iter().map(|e| e.block_height).collect();
+        assert_eq!(heights, vec![120, 100, 90]);
+    }
+
+    #[tokio::test]
+    async fn test_history_signer_filter_and_metadata() {
+        let store = sample_store();
+        let mut relayed = write("alice.near", "profile/name", "\"Relayed\"", 130, 0);
+        relayed.signer_id = "relayer.near".to_string();
+        relayed.shard_id = 3;
+        relayed.action_index = 2;
+        store.insert(relayed);
+
+        let mut params = history_params("desc");
+        params.signer_id = Some("relayer.near".to_string());
+        let (entries, ..) = store.get_kv_history(&params).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].signer_id.as_deref(), Some("relayer.near"));
+        assert_eq!(entries[0].shard_id, Some(3));
+        assert_eq!(entries[0].action_index, Some(2));
+
+        params.signer_id = Some("alice.near".to_string());
+        let (entries, ..) = store.get_kv_history(&params).await.unwrap();
+        assert_eq!(entries.len(), 2);
+
+        // A sparse signer stops after MAX_FILTER_SCAN rows and resumes from the
+        // last row scanned
+        for block_height in 200..200 + MAX_FILTER_SCAN as i64 + 5 {
+            store.insert(write("alice.near", "profile/name", "1", block_height, 0));
+        }
+        params.signer_id = Some("relayer.near".to_string());
+        let (entries, has_more, truncated, _, cursor) =
+            store.get_kv_history(&params).await.unwrap();
+        assert!(entries.is_empty() && has_more && truncated);
+        assert_eq!(cursor.as_deref(), Some("205:0"));
+        params.cursor = cursor;
+        let (entries, has_more, truncated, ..) = store.get_kv_history(&params).await.unwrap();
+        assert_eq!(entries[0].block_height, 130);
+        assert!(!has_more && !truncated);
+
+        // Latest-value reads come from s_kv_last, which has no signer metadata
+        let latest = store
+            .get_kv("alice.near", "social.near", "profile/name")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(latest.signer_id.is_none());
+        let json = serde_json::to_value(&latest).unwrap();
+        assert!(json.get("signerId").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_where_filters_query_and_writers() {
+        let store = MemoryStore::default();
+        for (i, kind) in ["nft", "ft", "nft"].iter().enumerate() {
+            let value = format!(r#"{{"type":"{kind}","price":{}}}"#, i * 10);
+            store.insert(write(
+                "alice.near",
+                &format!("item/{i}"),
+                &value,
+                100,
+                i as i64,
+            ));
+        }
+        store.insert(write("bob.near", "item/0", r#"{"type":"ft"}"#, 101, 0));
+
+        let mut params = QueryParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key_prefix: Some("item/".to_string()),
+            exclude_deleted: None,
+            limit: 10,
+            offset: 0,
+            fields: None,
+            format: None,
+            value_format: None,
+            after_key: None,
+            where_clause: Some(r#"$.type == "nft" && $.price > 5"#.to_string()),
+            ..Default::default()
+        };
+        let (entries, has_more, truncated, _, cursor) =
+            store.query_kv_with_pagination(&params).await.unwrap();
+        let keys: Vec<_> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["item/2"]);
+        assert!(!has_more && !truncated);
+        assert_eq!(cursor.as_deref(), Some("item/2"));
+
+        params.at_block = Some(100);
+        params.where_clause = Some("exists($.price)".to_string());
+        let (entries, _, _, _, _) = store.query_kv_with_pagination(&params).await.unwrap();
+        assert_eq!(entries.len(), 3);
+
+        let params = WritersParams {
+            current_account_id: "social.near".to_string(),
+            key: "item/0".to_string(),
+            predecessor_id: None,
+            exclude_deleted: None,
+            limit: 10,
+            offset: 0,
+            fields: None,
+            value_format: None,
+            after_account: None,
+            where_clause: Some(r#"$.type == "ft""#.to_string()),
+        };
+        let (writers, _, _, _, cursor) = store.query_writers(&params).await.unwrap();
+        assert_eq!(writers.len(), 1);
+        assert_eq!(cursor.as_deref(), Some("bob.near"));
+    }
+
+    #[tokio::test]
+    async fn test_key_pattern_filters_query() {
+        let store = sample_store();
+        store.insert(write("alice.near", "graph/hide/bob.near", "true", 107, 0));
+        store.insert(write("alice.near", "graph/follow/dan.near", "\"\"", 108, 0));
+        let mut params = QueryParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key_prefix: None,
+            exclude_deleted: None,
+            limit: 10,
+            offset: 0,
+            fields: None,
+            format: None,
+            value_format: None,
+            after_key: None,
+            key_pattern: Some("graph/*/bob.near".to_string()),
+            ..Default::default()
+        };
+        let (entries, has_more, truncated, _, _) =
+            store.query_kv_with_pagination(&params).await.unwrap();
+        let keys: Vec<_> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["graph/follow/bob.near", "graph/hide/bob.near"]);
+        assert!(!has_more && !truncated);
+
+        params.limit = 1;
+        params.after_key = Some("graph/follow/bob.near".to_string());
+        let (entries, _, _, _, _) = store.query_kv_with_pagination(&params).await.unwrap();
+        assert_eq!(entries[0].key, "graph/hide/bob.near");
+
+        params.limit = 10;
+        params.after_key = None;
+        params.key_pattern = Some(r"profile/(name|bio)".to_string());
+        params.pattern_syntax = Some("regex".to_string());
+        params.at_block = Some(105);
+        let (entries, _, _, _, _) = store.query_kv_with_pagination(&params).await.unwrap();
+        let keys: Vec<_> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["profile/name"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_key_range_and_desc_order() {
+        let store = sample_store();
+        let query = |q: &str| {
+            actix_web::web::Query::<QueryParams>::from_query(&format!(
+                "accountId=alice.near&contractId=social.near&{q}"
+            ))
+            .unwrap()
+            .into_inner()
+        };
+        let keys = |entries: &[KvEntry]| entries.iter().map(|e| e.key.clone()).collect::<Vec<_>>();
+
+        let (entries, has_more, _, _, cursor) = store
+            .query_kv_with_pagination(&query("order=desc&limit=2"))
+            .await
+            .unwrap();
+        assert_eq!(keys(&entries), vec!["profile/name", "profile/bio"]);
+        assert!(has_more);
+        assert_eq!(cursor.as_deref(), Some("profile/bio"));
+
+        let (entries, has_more, _, _, _) = store
+            .query_kv_with_pagination(&query("order=desc&limit=2&before_key=profile/bio"))
+            .await
+            .unwrap();
+        assert_eq!(keys(&entries), vec!["graph/follow/bob.near"]);
+        assert!(!has_more);
+
+        let (entries, _, _, _, _) = store
+            .query_kv_with_pagination(&query("start_key=profile/&end_key=profile/name"))
+            .await
+            .unwrap();
+        assert_eq!(keys(&entries), vec!["profile/bio"]);
+        let (entries, _, _, _, _) = store
+            .query_kv_with_pagination(&query(
+                "start_key=profile/bio&start_inclusive=false&end_key=profile/name&end_inclusive=true",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(keys(&entries), vec!["profile/name"]);
+
+        let (entries, _, _, _, _) = store
+            .query_kv_with_pagination(&query("order=desc&at_block=105"))
+            .await
+            .unwrap();
+        assert_eq!(
+            keys(&entries),
+            vec!["profile/name", "graph/follow/bob.near"]
+        );
+        assert_eq!(entries[0].value, "\"Alice\"");
+    }
+
+    #[tokio::test]
+    async fn test_contract_timeline_pages_across_writers() {
+        let store = sample_store();
+        store.insert(write("bob.near", "profile/name", "\"Bob\"", 110, 0));
+        let mut params = ContractTimelineParams {
+            current_account_id: "social.near".to_string(),
+            from_block: 100,
+            to_block: 125,
+            limit: 2,
+            order: "desc".to_string(),
+            fields: None,
+            value_format: None,
+            cursor: None,
+        };
+
+        let mut pages = Vec::new();
+        loop {
+            let (entries, has_more, _, next_cursor) =
+                store.get_contract_timeline(&params).await.unwrap();
+            pages.push(
+                entries
+                    .iter()
+                    .map(|e| format!("{}:{}", e.block_height, e.predecessor_id))
+                    .collect::<Vec<_>>(),
+            );
+            if !has_more {
+                break;
+            }
+            params.cursor = next_cursor;
+        }
+        // Carol's tombstone at 130 is outside the range
+        assert_eq!(
+            pages,
+            vec![
+                vec!["120:alice.near", "110:bob.near"],
+                vec!["110:alice.near", "106:carol.near"],
+                vec!["105:alice.near", "100:alice.near"],
+            ]
+        );
+
+        params.order = "asc".to_string();
+        params.cursor = Some("110:0:alice.near:profile/bio".to_string());
+        let (entries, has_more, _, next_cursor) =
+            store.get_contract_timeline(&params).await.unwrap();
+        let writers: Vec<_> = entries.iter().map(|e| e.predecessor_id.as_str()).collect();
+        assert_eq!(writers, vec!["bob.near", "alice.near"]);
+        assert!(!has_more);
+        assert_eq!(next_cursor.as_deref(), Some("120:0:alice.near:profile/name"));
+    }
+
+    #[tokio::test]
+    async fn test_count_keys_and_writers() {
+        let store = sample_store();
+        let count = |prefix, exclude_deleted| {
+            store.count_keys("alice.near", "social.near", prefix, exclude_deleted)
+        };
+        assert_eq!(count(Some("profile/"), false).await.unwrap(), (2, false));
+        assert_eq!(count(None, false).await.unwrap(), (3, false));
+        assert_eq!(
+            store
+                .count_keys("dan.near", "social.near", None, false)
+                .await
+                .unwrap(),
+            (0, false)
+        );
+
+        let writers = |exclude_deleted| {
+            store.count_writers("social.near", "graph/follow/bob.near", exclude_deleted)
+        };
+        assert_eq!(writers(false).await.unwrap(), (2, false));
+        assert_eq!(writers(true).await.unwrap(), (1, false));
+    }
+
+    #[tokio::test]
+    async fn test_history_cursor_resumes_within_block() {
+        let store = MemoryStore::default();
+        for oid in 0..3 {
+            store.insert(write("alice.near", "profile/name", &format!("\"v{oid}\""), 100, oid));
+        }
+        let mut params = history_params("asc");
+        params.limit = 2;
+        let (first, has_more, _, _, cursor) = store.get_kv_history(&params).await.unwrap();
+        assert_eq!(first.len(), 2);
+        assert!(has_more);
+        assert_eq!(cursor.as_deref(), Some("100:1"));
+
+        params.cursor = cursor;
+        let (second, has_more, ..) = store.get_kv_history(&params).await.unwrap();
+        assert_eq!(second.len(), 1);
+        assert_eq!(second[0].value, "\"v2\"");
+        assert!(!has_more);
+    }
+
+    #[tokio::test]
+    async fn test_as_of_block_reads() {
+        let store = sample_store();
+        let at = |bh| store.get_kv_at_block("alice.near", "social.near", "profile/name", bh);
+        assert!(at(99).await.unwrap().is_none());
+        assert_eq!(at(100).await.unwrap().unwrap().value, "\"Alice\"");
+        assert_eq!(at(119).await.unwrap().unwrap().value, "\"Alice\"");
+        assert_eq!(at(500).await.unwrap().unwrap().value, "\"Alicia\"");
+
+        let params = QueryParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key_prefix: Some("profile/".to_string()),
+            exclude_deleted: None,
+            limit: 10,
+            offset: 0,
+            fields: None,
+            format: None,
+            value_format: None,
+            after_key: None,
+            at_block: Some(105),
+            ..Default::default()
+        };
+        // profile/bio is first written at 110, so only profile/name exists at 105
+        let (entries, has_more, _, _, _) = store.query_kv_with_pagination(&params).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "profile/name");
+        assert_eq!(entries[0].value, "\"Alice\"");
+        assert!(!has_more);
+
+        // The fold reads at most MAX_FILTER_SCAN versions, stopping between keys
+        for key in 0..12 {
+            for block_height in 1..=1000 {
+                let (key, value) = (format!("log/{key:02}"), block_height.to_string());
+                store.insert(write("alice.near", &key, &value, block_height, 0));
+            }
+        }
+        let mut params = QueryParams {
+            key_prefix: Some("log/".to_string()),
+            at_block: Some(500),
+            ..params
+        };
+        let (entries, has_more, truncated, _, cursor) =
+            store.query_kv_with_pagination(&params).await.unwrap();
+        assert_eq!(entries.len(), 10);
+        assert!(entries.iter().all(|e| e.value == "500"));
+        assert!(has_more && truncated);
+        assert_eq!(cursor.as_deref(), Some("log/09"));
+        params.after_key = cursor;
+        let (entries, has_more, truncated, ..) =
+            store.query_kv_with_pagination(&params).await.unwrap();
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["log/10", "log/11"]);
+        assert!(!has_more && !truncated);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_query_and_cursor() {
+        let store = sample_store();
+        let mut params = QueryParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key_prefix: Some("profile/".to_string()),
+            exclude_deleted: None,
+            limit: 1,
+            offset: 0,
+            fields: None,
+            format: None,
+            value_format: None,
+            after_key: None,
+            ..Default::default()
+        };
+        let (entries, has_more, _, _, _) = store.query_kv_with_pagination(&params).await.unwrap();
+        assert_eq!(entries[0].key, "profile/bio");
+        assert!(has_more);
+
+        params.after_key = Some("profile/bio".to_string());
+        let (entries, has_more, _, _, _) = store.query_kv_with_pagination(&params).await.unwrap();
+        assert_eq!(entries[0].key, "profile/name");
+        assert!(!has_more);
+    }
+
+    #[tokio::test]
+    async fn test_timeline_orders_by_block() {
+        let store = sample_store();
+        let params = TimelineParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            limit: 2,
+            order: "desc".to_string(),
+            from_block: Some(101),
+            to_block: None,
+            fields: None,
+            value_format: None,
+            cursor: None,
+            from_time: None,
+            to_time: None,
+        };
+        let (entries, has_more, _, cursor) = store.get_kv_timeline(&params).await.unwrap();
+        let heights: Vec<u64> = entries.iter().map(|e| e.block_height).collect();
+        assert_eq!(heights, vec![120, 110]);
+        assert!(has_more);
+        assert_eq!(cursor.as_deref(), Some("110:profile/bio"));
+    }
+
+    #[tokio::test]
+    async fn test_prefix_history_filters_and_resumes() {
+        let store = sample_store();
+        let mut params = PrefixHistoryParams {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key_prefix: "profile/".to_string(),
+            limit: 2,
+            order: "asc".to_string(),
+            from_block: None,
+            to_block: None,
+            fields: None,
+            value_format: None,
+            cursor: None,
+        };
+        let (first, has_more, _, _, cursor) = store.get_kv_prefix_history(&params).await.unwrap();
+        let heights: Vec<u64> = first.iter().map(|e| e.block_height).collect();
+        assert_eq!(heights, vec![100, 110]);
+        assert!(has_more);
+
+        // graph/follow/bob.near at 105 is skipped by the prefix
+        params.cursor = cursor;
+        let (rest, has_more, ..) = store.get_kv_prefix_history(&params).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].block_height, 120);
+        assert!(!has_more);
+
+        // A sparse prefix stops after MAX_FILTER_SCAN rows and resumes from the
+        // last row scanned
+        for block_height in 200..200 + MAX_FILTER_SCAN as i64 + 5 {
+            store.insert(write("alice.near", "widget/app", "1", block_height, 0));
+        }
+        params.order = "desc".to_string();
+        params.cursor = None;
+        let (entries, has_more, truncated, _, cursor) =
+            store.get_kv_prefix_history(&params).await.unwrap();
+        assert!(entries.is_empty() && has_more && truncated);
+        assert_eq!(cursor.as_deref(), Some("205:widget/app"));
+        params.cursor = cursor;
+        let (entries, _, truncated, ..) = store.get_kv_prefix_history(&params).await.unwrap();
+        assert_eq!(entries[0].block_height, 120);
+        assert!(!truncated);
+    }
+
+    #[tokio::test]
+    async fn test_reverse_accounts_and_edges() {
+        let store = sample_store();
+
+        let params = WritersParams {
+            current_account_id: "social.near".to_string(),
+            key: "graph/follow/bob.near".to_string(),
+            predecessor_id: None,
+            exclude_deleted: Some(true),
+            limit: 10,
+            offset: 0,
+            fields: None,
+            value_format: None,
+            after_account: None,
+            where_clause: None,
+        };
+        let (writers, _, _, _, _) = store.query_writers(&params).await.unwrap();
+        let writers: Vec<&str> = writers.iter().map(|e| e.predecessor_id.as_str()).collect();
+        assert_eq!(writers, vec!["alice.near"]);
+
+        // Unfollow (null) removes the edge but keeps the account mapping
+        assert_eq!(store.count_edges("graph/follow", "bob.near").await.unwrap(), 1);
+        let (accounts, _, _, _) = store
+            .query_accounts_by_contract("social.near", None, 10, 0, None)
+            .await
+            .unwrap();
+        assert_eq!(accounts, vec!["alice.near", "carol.near"]);
+
+        let (contracts, _, _) = store
+            .query_contracts_by_account("carol.near", 10, None)
+            .await
+            .unwrap();
+        assert_eq!(contracts, vec!["social.near"]);
+        assert_eq!(store.get_indexer_block_height().await.unwrap(), Some(130));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_by_tx_and_receipt() {
+        let store = sample_store();
+        let mut second = write("alice.near", "profile/image/url", "\"u\"", 120, 1);
+        second.receipt_id = "r120b".to_string();
+        store.insert(second);
+
+        let (entries, has_more, _) = store.query_kv_by_tx("t120", 10, 0).await.unwrap();
+        let keys: Vec<&str> = entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, vec!["profile/name", "profile/image/url"]);
+        assert!(!has_more);
+
+        let (entries, _, _) = store.query_kv_by_receipt("r120b", 10, 0).await.unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key, "profile/image/url");
+
+        let (entries, has_more, _) = store.query_kv_by_tx("t120", 1, 1).await.unwrap();
+        assert_eq!(entries[0].key, "profile/image/url");
+        assert!(!has_more);
+    }
+
+    #[tokio::test]
+    async fn test_stream_reverse_kv_newest_first() {
+        let store = sample_store();
+        store.insert(write("dave.near", "graph/follow/bob.near", "\"\"", 140, 0));
+        let rows: Vec<KvRow> = store
+            .stream_reverse_kv("social.near", "graph/follow/bob.near")
+            .await
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+            .await;
+        let heights: Vec<i64> = rows.iter().map(|r| r.block_height).collect();
+        assert_eq!(heights, vec![140, 130, 105]);
+    }
+
+    #[test]
+    fn test_from_ndjson_reports_line() {
+        let ok = "{\"predecessor_id\":\"a.near\",\"current_account_id\":\"c.near\",\"key\":\"k\",\"value\":\"1\",\"block_height\":5}\n\n";
+        assert!(MemoryStore::from_ndjson(ok).is_ok());
+
+        let bad = format!("{ok}{{\"key\":\"k\"}}\n");
+        let err = MemoryStore::from_ndjson(&bad).err().unwrap().to_string();
+        assert!(err.contains("line 3"), "{err}");
+    }
+}
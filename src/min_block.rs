@@ -0,0 +1,186 @@
+//! Read-your-writes: `min_block=` holds any `/v1` request until the indexer
+//! has reached that block.
+//!
+//! Waits read the cached indexer head that also feeds `X-Indexer-Block`.
+//! While requests are waiting, the head is re-read from the store every
+//! `MIN_BLOCK_REFRESH_MILLIS`, shared by all of them, rather than on the
+//! cache's usual 5s schedule.
+
+use crate::models::{ApiError, MIN_BLOCK_MAX_AHEAD, MIN_BLOCK_REFRESH_MILLIS, MIN_BLOCK_WAIT_SECS};
+use crate::store::KvStore;
+use crate::AppState;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web;
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+/// The cached indexer block height, and how long `min_block` may wait on it.
+pub struct IndexerHead {
+    block: Arc<AtomicU64>,
+    max_wait: Duration,
+    /// Last store read on behalf of waiters.
+    last_refresh: Mutex<Option<Instant>>,
+}
+
+impl Default for IndexerHead {
+    fn default() -> Self {
+        Self::new(Arc::default(), Duration::from_secs(MIN_BLOCK_WAIT_SECS))
+    }
+}
+
+impl IndexerHead {
+    pub fn new(block: Arc<AtomicU64>, max_wait: Duration) -> Self {
+        Self {
+            block,
+            max_wait,
+            last_refresh: Mutex::new(None),
+        }
+    }
+
+    /// Wait until the head reaches `min_block`, or report where it stands.
+    /// A block more than `MIN_BLOCK_MAX_AHEAD` past a known head is reported
+    /// at once rather than waited on.
+    pub(crate) async fn wait_for(
+        &self,
+        min_block: u64,
+        store: &RwLock<Option<Arc<dyn KvStore>>>,
+    ) -> Result<(), ApiError> {
+        let deadline = Instant::now() + self.max_wait;
+        loop {
+            let head = self.block.load(Ordering::Acquire);
+            if head >= min_block {
+                return Ok(());
+            }
+            let now = Instant::now();
+            let out_of_reach = head > 0 && min_block - head > MIN_BLOCK_MAX_AHEAD;
+            if now >= deadline || out_of_reach {
+                return Err(ApiError::IndexerBehind {
+                    min_block,
+                    indexer_block: head,
+                });
+            }
+            self.refresh(store).await;
+            if self.block.load(Ordering::Acquire) < min_block {
+                let pause = Duration::from_millis(MIN_BLOCK_REFRESH_MILLIS);
+                tokio::time::sleep(pause.min(deadline - now)).await;
+            }
+        }
+    }
+
+    /// Re-read the head from the store, at most once per refresh interval
+    /// across all waiters.
+    async fn refresh(&self, store: &RwLock<Option<Arc<dyn KvStore>>>) {
+        let mut last_refresh = self.last_refresh.lock().await;
+        let interval = Duration::from_millis(MIN_BLOCK_REFRESH_MILLIS);
+        if last_refresh.is_some_and(|t| t.elapsed() < interval) {
+            return;
+        }
+        *last_refresh = Some(Instant::now());
+        // Clone the Arc so the RwLock is not held across .await
+        let db = store.read().await.clone();
+        if let Some(db) = db {
+            if let Ok(Some(head)) = db.get_indexer_block_height().await {
+                self.block.fetch_max(head, Ordering::AcqRel);
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MinBlockParams {
+    min_block: Option<u64>,
+}
+
+/// Middleware: hold `/v1` requests carrying `min_block=` until the indexer
+/// reaches it; 409 with the current head once `MIN_BLOCK_WAIT_SECS` pass.
+pub async fn wait_for_min_block<B: MessageBody>(
+    req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    if req.path().starts_with("/v1/") && req.query_string().contains("min_block") {
+        let min_block = match web::Query::<MinBlockParams>::from_query(req.query_string()) {
+            Ok(params) => params.min_block,
+            Err(_) => {
+                let error = ApiError::InvalidParameter(
+                    "min_block: must be a non-negative integer".to_string(),
+                );
+                return Ok(req.error_response(error).map_into_right_body());
+            }
+        };
+        let state = req.app_data::<web::Data<AppState>>().cloned();
+        if let (Some(min_block), Some(state)) = (min_block, state) {
+            if let Err(e) = state.indexer_head.wait_for(min_block, &state.store).await {
+                return Ok(req.error_response(e).map_into_right_body());
+            }
+        }
+    }
+    next.call(req)
+        .await
+        .map(ServiceResponse::map_into_left_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sample_store, test_app_state, write};
+    use actix_web::{test as actix_test, App};
+
+    #[actix_web::test]
+    async fn test_min_block_waits_for_indexer() {
+        let memory = Arc::new(sample_store());
+        let store: Arc<dyn KvStore> = memory.clone();
+        let state = AppState {
+            indexer_head: Arc::new(IndexerHead::new(Arc::default(), Duration::from_secs(1))),
+            ..test_app_state(store)
+        };
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .wrap(actix_web::middleware::from_fn(wait_for_min_block))
+                .service(crate::handlers::get_kv_handler),
+        )
+        .await;
+        let get = |min_block: &str| {
+            actix_test::TestRequest::get()
+                .uri(&format!(
+                    "/v1/kv/get?accountId=alice.near&contractId=social.near&key=profile/name&min_block={min_block}"
+                ))
+                .to_request()
+        };
+
+        // Already indexed: the first refresh of the empty cache reads block 130
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, get("130")).await;
+        assert_eq!(body["data"]["value"], "\"Alicia\"");
+
+        let resp = actix_test::call_service(&app, get("-1")).await;
+        assert_eq!(resp.status(), 400);
+
+        // Indexed while waiting
+        let writer = memory.clone();
+        actix_web::rt::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            writer.insert(write("alice.near", "profile/name", "\"Al\"", 150, 0));
+        });
+        let body: serde_json::Value = actix_test::call_and_read_body_json(&app, get("150")).await;
+        assert_eq!(body["data"]["value"], "\"Al\"");
+
+        // Never indexed within the wait
+        let resp = actix_test::call_service(&app, get("200")).await;
+        assert_eq!(resp.status(), 409);
+        assert_eq!(resp.headers().get("Retry-After").unwrap(), "1");
+        let body: serde_json::Value = actix_test::read_body_json(resp).await;
+        assert_eq!(body["code"], "INDEXER_BEHIND");
+        assert_eq!(body["error"], "Indexer at block 150, behind min_block 200");
+
+        // Out of reach: rejected without waiting
+        let started = Instant::now();
+        let resp = actix_test::call_service(&app, get(&u64::MAX.to_string())).await;
+        assert_eq!(resp.status(), 409);
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+}
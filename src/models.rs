@@ -3,6 +3,8 @@ use scylla::DeserializeRow;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
+use crate::store::Unsupported;
+
 // Shared validation constants
 pub const MAX_OFFSET: usize = 100_000;
 pub const MAX_PREFIX_LENGTH: usize = 1000;
@@ -10,6 +12,7 @@ pub const MAX_ACCOUNT_ID_LENGTH: usize = 256;
 pub const MAX_KEY_LENGTH: usize = 10000;
 pub const MAX_BATCH_KEYS: usize = 100;
 pub const MAX_BATCH_KEY_LENGTH: usize = 1024;
+pub const MAX_BATCH_QUERIES: usize = 20;
 pub const MAX_SOCIAL_RESULTS: usize = 1000;
 pub const MAX_SOCIAL_KEYS: usize = 100;
 pub const MAX_STREAM_ERRORS: usize = 10;
@@ -17,6 +20,20 @@ pub const MAX_DEDUP_SCAN: usize = 100_000;
 pub const MAX_EDGE_TYPE_LENGTH: usize = 256;
 pub const MAX_SCAN_LIMIT: usize = 1000;
 pub const MAX_CURSOR_LENGTH: usize = 1024;
+pub const MAX_DIFF_TREE_KEYS: usize = 1000;
+pub const MAX_HASH_LENGTH: usize = 64;
+pub const MAX_PREDICATE_LENGTH: usize = 1000;
+/// Raw rows an app-side filter (`where=`, `key_pattern`, `signer_id`, history
+/// `key_prefix`) may scan before returning a truncated page.
+pub const MAX_FILTER_SCAN: usize = 10_000;
+/// Rows `/kv/count` and `/kv/writers/count` scan when `COUNT(*)` can't be used.
+pub const MAX_COUNT_SCAN: usize = 100_000;
+/// Contracts `/account/timeline` merges; the page is `truncated` past this.
+pub const MAX_TIMELINE_CONTRACTS: usize = 100;
+/// Widest `from_block..=to_block` span one `/kv/contract/timeline` request may cover.
+pub const MAX_CONTRACT_TIMELINE_SPAN: i64 = 1_000_000;
+/// Writes `/blocks/{height}/kv` returns before flagging the block `truncated`.
+pub const MAX_BLOCK_WRITES: usize = 10_000;
 pub const PROJECT_ID: &str = "near-garden";
 
 // Raw row from ScyllaDB s_kv_last (matches table schema exactly)
@@ -32,21 +49,30 @@ pub struct KvRow {
     pub tx_hash: String,
 }
 
-// Raw row from ScyllaDB s_kv (history table with additional fields)
-#[derive(DeserializeRow, Debug, Clone)]
+// Raw row from ScyllaDB s_kv (history table with additional fields).
+// Also the NDJSON line format for MemoryStore fixtures; receipt metadata is optional there.
+#[derive(DeserializeRow, Deserialize, Debug, Clone)]
 pub struct KvHistoryRow {
     pub predecessor_id: String,
     pub current_account_id: String,
     pub key: String,
     pub block_height: i64,
+    #[serde(default)]
     pub order_id: i64,
     pub value: String,
+    #[serde(default)]
     pub block_timestamp: i64,
+    #[serde(default)]
     pub receipt_id: String,
+    #[serde(default)]
     pub tx_hash: String,
+    #[serde(default)]
     pub signer_id: String,
+    #[serde(default)]
     pub shard_id: i32,
+    #[serde(default)]
     pub receipt_index: i32,
+    #[serde(default)]
     pub action_index: i32,
 }
 
@@ -101,6 +127,20 @@ pub struct KvEntry {
     /// True when the entry represents a deletion (value is the literal string "null").
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub is_deleted: bool,
+    /// Transaction signer. Differs from `accountId` for relayed and meta-transaction
+    /// writes. History-backed responses only (`s_kv`); omitted elsewhere.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shard_id: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt_index: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action_index: Option<u32>,
+    /// Position within the block (`s_kv` / `s_kv_by_block` clustering). Only
+    /// surfaced through cursors and watch event ids, never serialized.
+    #[serde(skip)]
+    pub order_id: Option<i64>,
 }
 
 impl KvEntry {
@@ -155,6 +195,22 @@ impl KvEntry {
             if field_set.contains("isDeleted") && self.is_deleted {
                 map.insert("isDeleted".to_string(), serde_json::json!(true));
             }
+            if let (true, Some(signer_id)) = (field_set.contains("signerId"), &self.signer_id) {
+                map.insert("signerId".to_string(), serde_json::json!(signer_id));
+            }
+            if let (true, Some(shard_id)) = (field_set.contains("shardId"), self.shard_id) {
+                map.insert("shardId".to_string(), serde_json::json!(shard_id));
+            }
+            if let (true, Some(receipt_index)) =
+                (field_set.contains("receiptIndex"), self.receipt_index)
+            {
+                map.insert("receiptIndex".to_string(), serde_json::json!(receipt_index));
+            }
+            if let (true, Some(action_index)) =
+                (field_set.contains("actionIndex"), self.action_index)
+            {
+                map.insert("actionIndex".to_string(), serde_json::json!(action_index));
+            }
 
             serde_json::Value::Object(map)
         } else {
@@ -174,6 +230,12 @@ pub fn bigint_to_u64(val: i64) -> u64 {
     val.max(0) as u64
 }
 
+/// ScyllaDB int (i32) index columns such as `shard_id`. Negative values are
+/// the indexer's "unknown" sentinel, so they map to `None`, never index 0.
+pub fn int_to_u32(val: i32) -> Option<u32> {
+    u32::try_from(val).ok()
+}
+
 impl From<KvRow> for KvEntry {
     fn from(row: KvRow) -> Self {
         let is_deleted = row.value == "null";
@@ -187,6 +249,11 @@ impl From<KvRow> for KvEntry {
             receipt_id: row.receipt_id,
             tx_hash: row.tx_hash,
             is_deleted,
+            signer_id: None,
+            shard_id: None,
+            receipt_index: None,
+            action_index: None,
+            order_id: None,
         }
     }
 }
@@ -204,6 +271,12 @@ impl From<KvHistoryRow> for KvEntry {
             receipt_id: row.receipt_id,
             tx_hash: row.tx_hash,
             is_deleted,
+            // Empty signer only occurs in hand-written fixtures
+            signer_id: Some(row.signer_id).filter(|s| !s.is_empty()),
+            shard_id: int_to_u32(row.shard_id),
+            receipt_index: int_to_u32(row.receipt_index),
+            action_index: int_to_u32(row.action_index),
+            order_id: Some(row.order_id),
         }
     }
 }
@@ -221,12 +294,17 @@ impl From<KvTimelineRow> for KvEntry {
             receipt_id: row.receipt_id,
             tx_hash: row.tx_hash,
             is_deleted,
+            signer_id: None,
+            shard_id: None,
+            receipt_index: None,
+            action_index: None,
+            order_id: Some(row.order_id),
         }
     }
 }
 
 // Pagination metadata returned in all paginated responses
-#[derive(Serialize, utoipa::ToSchema)]
+#[derive(Default, Serialize, utoipa::ToSchema)]
 pub struct PaginationMeta {
     pub has_more: bool,
     #[serde(skip_serializing_if = "std::ops::Not::not")]
@@ -237,6 +315,24 @@ pub struct PaginationMeta {
     /// Number of rows skipped due to deserialization errors. Omitted when zero.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub dropped_rows: Option<u32>,
+    /// Block bounds that `from_time`/`to_time` resolved to. Omitted when no
+    /// time bound was given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_range: Option<ResolvedBlockRange>,
+}
+
+/// Effective block range after resolving `from_time`/`to_time` and
+/// intersecting with any explicit `from_block`/`to_block`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, utoipa::ToSchema)]
+pub struct ResolvedBlockRange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from_block: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_block: Option<u64>,
+    /// True when a time bound matched no write, so the page is necessarily empty.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub empty: bool,
 }
 
 // Standardized paginated response for all list endpoints
@@ -258,6 +354,9 @@ pub struct TreeResponse {
     /// True when results were capped by the limit parameter.
     #[serde(skip_serializing_if = "std::ops::Not::not")]
     pub has_more: bool,
+    /// Resume token when `has_more`: pass it back as `after_key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -280,6 +379,9 @@ pub struct GetParams {
     /// Value format: "raw" (default) or "json" (decoded).
     #[serde(default)]
     pub value_format: Option<String>,
+    /// Point-in-time read: newest value written at or before this block height.
+    #[serde(default)]
+    pub at_block: Option<i64>,
 }
 
 const VALID_FIELDS: &[&str] = &[
@@ -292,6 +394,10 @@ const VALID_FIELDS: &[&str] = &[
     "receiptId",
     "txHash",
     "isDeleted",
+    "signerId",
+    "shardId",
+    "receiptIndex",
+    "actionIndex",
 ];
 
 /// Parse a comma-separated fields string into a set of field names.
@@ -344,6 +450,27 @@ pub fn should_decode(value_format: &Option<String>) -> Result<bool, ApiError> {
     }
 }
 
+/// Parse a `from_time`/`to_time` value: either nanoseconds since the Unix
+/// epoch (the unit of `block_timestamp`) or an RFC 3339 timestamp.
+pub fn parse_time_param(value: &str, name: &str) -> Result<u64, ApiError> {
+    if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) {
+        return value.parse().map_err(|_| {
+            ApiError::InvalidParameter(format!("{name}: nanosecond timestamp out of range"))
+        });
+    }
+    let parsed = chrono::DateTime::parse_from_rfc3339(value).map_err(|_| {
+        ApiError::InvalidParameter(format!(
+            "{name}: expected RFC 3339 timestamp or nanoseconds since epoch"
+        ))
+    })?;
+    parsed
+        .timestamp_nanos_opt()
+        .and_then(|ns| u64::try_from(ns).ok())
+        .ok_or_else(|| {
+            ApiError::InvalidParameter(format!("{name}: must be between 1970 and 2262"))
+        })
+}
+
 pub fn parse_history_cursor(cursor: &str) -> Result<(i64, i64), ApiError> {
     let (bh_str, oid_str) = cursor.split_once(':').ok_or_else(|| {
         ApiError::InvalidParameter("cursor: expected format block_height:order_id".to_string())
@@ -377,6 +504,36 @@ pub fn parse_timeline_cursor(cursor: &str) -> Result<(i64, String), ApiError> {
     Ok((block_height, key.to_string()))
 }
 
+/// Account-timeline cursor: the timeline cursor with the contract id spliced in.
+pub fn parse_account_timeline_cursor(cursor: &str) -> Result<(i64, String, String), ApiError> {
+    let (block_height, rest) = parse_timeline_cursor(cursor)?;
+    let (contract, key) = rest.split_once(':').ok_or_else(|| {
+        ApiError::InvalidParameter(
+            "cursor: expected format block_height:contract_id:key".to_string(),
+        )
+    })?;
+    Ok((block_height, contract.to_string(), key.to_string()))
+}
+
+/// Contract-timeline cursor: `block_height:order_id:accountId:key`, the
+/// clustering columns of `s_kv_by_contract` after the partition key.
+pub fn parse_contract_timeline_cursor(
+    cursor: &str,
+) -> Result<(i64, i64, String, String), ApiError> {
+    let format_err = || {
+        ApiError::InvalidParameter(
+            "cursor: expected format block_height:order_id:accountId:key".to_string(),
+        )
+    };
+    let (history_cursor, rest) = match cursor.match_indices(':').nth(1) {
+        Some((i, _)) => (&cursor[..i], &cursor[i + 1..]),
+        None => return Err(format_err()),
+    };
+    let (block_height, order_id) = parse_history_cursor(history_cursor)?;
+    let (predecessor_id, key) = rest.split_once(':').ok_or_else(format_err)?;
+    Ok((block_height, order_id, predecessor_id.to_string(), key.to_string()))
+}
+
 pub fn validate_limit(limit: usize) -> Result<(), ApiError> {
     if limit == 0 || limit > 1000 {
         return Err(ApiError::InvalidParameter(
@@ -412,6 +569,65 @@ pub struct QueryParams {
     /// Cannot be combined with offset > 0.
     #[serde(default)]
     pub after_key: Option<String>,
+    /// Cursor for `order=desc`: return entries with key before this value (exclusive).
+    /// Cannot be combined with offset > 0.
+    #[serde(default)]
+    pub before_key: Option<String>,
+    /// Key order: "asc" (default) or "desc".
+    #[serde(default = "default_order_asc")]
+    pub order: String,
+    /// Lower key bound, inclusive unless `start_inclusive=false`.
+    #[serde(default)]
+    pub start_key: Option<String>,
+    #[serde(default)]
+    pub start_inclusive: Option<bool>,
+    /// Upper key bound, exclusive unless `end_inclusive=true`.
+    #[serde(default)]
+    pub end_key: Option<String>,
+    #[serde(default)]
+    pub end_inclusive: Option<bool>,
+    /// Point-in-time read: newest value written at or before this block height.
+    #[serde(default)]
+    pub at_block: Option<i64>,
+    /// Value predicate on the decoded value, e.g. `$.type == "nft"`. Evaluated
+    /// app-side; scans stop after `MAX_FILTER_SCAN` rows (`meta.truncated`).
+    #[serde(default, rename = "where")]
+    pub where_clause: Option<String>,
+    /// Whole-key pattern, e.g. `graph/*/alice.near`. Its literal prefix drives
+    /// the scan; cannot be combined with `key_prefix`.
+    #[serde(default)]
+    pub key_pattern: Option<String>,
+    /// `key_pattern` syntax: "glob" (default) or "regex" (anchored).
+    #[serde(default)]
+    pub pattern_syntax: Option<String>,
+}
+
+/// Matches the serde defaults, so internal queries only set what they use.
+impl Default for QueryParams {
+    fn default() -> Self {
+        Self {
+            predecessor_id: String::new(),
+            current_account_id: String::new(),
+            key_prefix: None,
+            exclude_deleted: None,
+            limit: default_limit(),
+            offset: 0,
+            fields: None,
+            format: None,
+            value_format: None,
+            after_key: None,
+            before_key: None,
+            order: default_order_asc(),
+            start_key: None,
+            start_inclusive: None,
+            end_key: None,
+            end_inclusive: None,
+            at_block: None,
+            where_clause: None,
+            key_pattern: None,
+            pattern_syntax: None,
+        }
+    }
 }
 
 // GET /v1/kv/writers — replaces /v1/kv/reverse and /v1/kv/by-key
@@ -439,6 +655,10 @@ pub struct WritersParams {
     /// Cannot be combined with offset > 0.
     #[serde(default)]
     pub after_account: Option<String>,
+    /// Value predicate on the decoded value, e.g. `$.type == "nft"`. Evaluated
+    /// app-side; scans stop after `MAX_FILTER_SCAN` rows (`meta.truncated`).
+    #[serde(default, rename = "where")]
+    pub where_clause: Option<String>,
 }
 
 fn default_limit() -> usize {
@@ -466,6 +686,15 @@ pub struct HistoryParams {
     pub value_format: Option<String>,
     #[serde(default)]
     pub cursor: Option<String>,
+    /// Only return writes whose transaction was signed by this account.
+    #[serde(default)]
+    pub signer_id: Option<String>,
+    /// Lower time bound (RFC 3339 or nanoseconds), resolved to a block height.
+    #[serde(default)]
+    pub from_time: Option<String>,
+    /// Upper time bound (RFC 3339 or nanoseconds), resolved to a block height.
+    #[serde(default)]
+    pub to_time: Option<String>,
 }
 
 fn default_history_limit() -> usize {
@@ -476,6 +705,10 @@ fn default_order_desc() -> String {
     "desc".to_string()
 }
 
+fn default_order_asc() -> String {
+    "asc".to_string()
+}
+
 // Internal accounts query parameters (used by social handlers, not exposed in API)
 #[derive(Deserialize, Clone)]
 pub struct AccountsParams {
@@ -552,6 +785,77 @@ pub struct DiffResponse {
     pub b: Option<KvEntry>,
 }
 
+// GET /v1/kv/diff/tree
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct DiffTreeParams {
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    pub key_prefix: String,
+    pub block_height_a: i64,
+    pub block_height_b: i64,
+    /// Response format: omit for added/removed/changed lists, `"tree"` for
+    /// nested JSON, `"patch"` for RFC 6902 operations turning A into B.
+    #[serde(default)]
+    pub format: Option<String>,
+    /// Value format: "raw" (default) or "json" (decoded). List format only.
+    #[serde(default)]
+    pub value_format: Option<String>,
+    /// Cursor: compare keys after this value (exclusive), from `meta.next_cursor`.
+    #[serde(default)]
+    pub after_key: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct KvChange {
+    pub key: String,
+    pub a: KvEntry,
+    pub b: KvEntry,
+}
+
+/// Keys under a prefix that differ between two block heights. Deleted
+/// (`null`) values count as absent.
+#[derive(Serialize, Default, utoipa::ToSchema)]
+pub struct TreeDiff {
+    pub added: Vec<KvEntry>,
+    pub removed: Vec<KvEntry>,
+    pub changed: Vec<KvChange>,
+}
+
+impl TreeDiff {
+    /// Both inputs must be sorted by key, as `query_kv_with_pagination` returns them.
+    pub fn between(a: Vec<KvEntry>, b: Vec<KvEntry>) -> Self {
+        let mut diff = Self::default();
+        let mut a = a.into_iter().peekable();
+        let mut b = b.into_iter().peekable();
+        loop {
+            let ordering = match (a.peek(), b.peek()) {
+                (Some(x), Some(y)) => x.key.cmp(&y.key),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => break,
+            };
+            match ordering {
+                std::cmp::Ordering::Less => diff.removed.extend(a.next()),
+                std::cmp::Ordering::Greater => diff.added.extend(b.next()),
+                std::cmp::Ordering::Equal => {
+                    if let (Some(x), Some(y)) = (a.next(), b.next()) {
+                        if x.value != y.value {
+                            diff.changed.push(KvChange {
+                                key: x.key.clone(),
+                                a: x,
+                                b: y,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
 #[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
 pub struct TimelineParams {
     #[serde(rename = "accountId")]
@@ -572,6 +876,97 @@ pub struct TimelineParams {
     pub value_format: Option<String>,
     #[serde(default)]
     pub cursor: Option<String>,
+    /// Lower time bound (RFC 3339 or nanoseconds), resolved to a block height.
+    #[serde(default)]
+    pub from_time: Option<String>,
+    /// Upper time bound (RFC 3339 or nanoseconds), resolved to a block height.
+    #[serde(default)]
+    pub to_time: Option<String>,
+}
+
+// GET /v1/account/timeline — one writer's writes across every contract, block-ordered
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct AccountTimelineParams {
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default = "default_order_desc")]
+    pub order: String,
+    #[serde(default)]
+    pub from_block: Option<i64>,
+    #[serde(default)]
+    pub to_block: Option<i64>,
+    #[serde(default)]
+    pub fields: Option<String>,
+    #[serde(default)]
+    pub value_format: Option<String>,
+    /// `block_height:contract_id:key` of the last entry on the previous page.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+// GET /v1/kv/contract/timeline — every write to one contract, all writers, block-ordered
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct ContractTimelineParams {
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    pub from_block: i64,
+    pub to_block: i64,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default = "default_order_desc")]
+    pub order: String,
+    #[serde(default)]
+    pub fields: Option<String>,
+    #[serde(default)]
+    pub value_format: Option<String>,
+    /// `block_height:order_id:accountId:key` of the last entry on the previous page.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+// GET /v1/kv/history/prefix — every write under a key prefix, block-ordered
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct PrefixHistoryParams {
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    pub key_prefix: String,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default = "default_order_desc")]
+    pub order: String,
+    #[serde(default)]
+    pub from_block: Option<i64>,
+    #[serde(default)]
+    pub to_block: Option<i64>,
+    #[serde(default)]
+    pub fields: Option<String>,
+    #[serde(default)]
+    pub value_format: Option<String>,
+    /// Resume token from `meta.next_cursor`. Format: `block_height:key`.
+    #[serde(default)]
+    pub cursor: Option<String>,
+}
+
+/// A timeline is a prefix history with an empty prefix.
+impl From<&TimelineParams> for PrefixHistoryParams {
+    fn from(params: &TimelineParams) -> Self {
+        Self {
+            predecessor_id: params.predecessor_id.clone(),
+            current_account_id: params.current_account_id.clone(),
+            key_prefix: String::new(),
+            limit: params.limit,
+            order: params.order.clone(),
+            from_block: params.from_block,
+            to_block: params.to_block,
+            fields: None,
+            value_format: None,
+            cursor: params.cursor.clone(),
+        }
+    }
 }
 
 // Batch query structs
@@ -582,6 +977,9 @@ pub struct BatchQuery {
     #[serde(rename = "contractId")]
     pub current_account_id: String,
     pub keys: Vec<String>,
+    /// Point-in-time read: newest value written at or before this block height.
+    #[serde(default)]
+    pub at_block: Option<i64>,
 }
 
 #[derive(Serialize, utoipa::ToSchema)]
@@ -593,6 +991,91 @@ pub struct BatchResultItem {
     pub error: Option<String>,
 }
 
+// POST /v1/kv/batch/multi — lookups across writers and contracts
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct MultiBatchQuery {
+    pub items: Vec<MultiBatchItem>,
+    /// Point-in-time read: newest value written at or before this block height.
+    #[serde(default)]
+    pub at_block: Option<i64>,
+    #[serde(default)]
+    pub fields: Option<String>,
+    #[serde(default)]
+    pub value_format: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Clone, utoipa::ToSchema)]
+pub struct MultiBatchItem {
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    pub key: String,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct MultiBatchResultItem {
+    #[serde(flatten)]
+    pub item: MultiBatchItem,
+    pub found: bool,
+    /// Full entry, narrowed by `fields` and decoded by `value_format`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<KvEntry>)]
+    pub entry: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// POST /v1/kv/query/batch — several prefix queries in one request
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct PrefixBatchQuery {
+    pub items: Vec<PrefixBatchItem>,
+    /// Response format for every item. Use `"tree"` for nested JSON; omit for lists.
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub fields: Option<String>,
+    #[serde(default)]
+    pub value_format: Option<String>,
+    #[serde(default)]
+    pub exclude_deleted: Option<bool>,
+    /// Point-in-time read applied to every item.
+    #[serde(default)]
+    pub at_block: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Clone, utoipa::ToSchema)]
+pub struct PrefixBatchItem {
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    /// Resume token from this item's `meta.next_cursor`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_key: Option<String>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct PrefixBatchResultItem {
+    #[serde(flatten)]
+    pub item: PrefixBatchItem,
+    /// Entries (list format), narrowed by `fields` and decoded by `value_format`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Vec<KvEntry>>)]
+    pub data: Option<serde_json::Value>,
+    /// Nested JSON (`format=tree`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<PaginationMeta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
 // ===== Social API types =====
 
 // POST /v1/social/get request body
@@ -698,6 +1181,12 @@ pub struct SocialAccountFeedParams {
     #[serde(default)]
     #[serde(alias = "contractId")]
     pub contract_id: Option<String>,
+    /// Lower time bound (RFC 3339 or nanoseconds), resolved to a block height.
+    #[serde(default)]
+    pub from_time: Option<String>,
+    /// Upper time bound (RFC 3339 or nanoseconds), resolved to a block height.
+    #[serde(default)]
+    pub to_time: Option<String>,
 }
 
 // Social API response types
@@ -728,6 +1217,10 @@ pub enum ErrorCode {
     DatabaseError,
     DatabaseUnavailable,
     TooManyRequests,
+    NotFound,
+    IndexerBehind,
+    NotImplemented,
+    Unauthorized,
 }
 
 /// Structured error response returned by all endpoints on failure.
@@ -743,6 +1236,17 @@ pub enum ApiError {
     DatabaseError(String),
     DatabaseUnavailable,
     TooManyRequests(String),
+    NotFound(String),
+    /// `min_block` was not indexed within `MIN_BLOCK_WAIT_SECS`.
+    IndexerBehind {
+        min_block: u64,
+        indexer_block: u64,
+    },
+    /// The backend lacks the table or view behind this endpoint, or the
+    /// endpoint is disabled in this deployment.
+    NotImplemented(String),
+    /// Missing or wrong credentials.
+    Unauthorized(String),
 }
 
 impl ApiError {
@@ -752,6 +1256,10 @@ impl ApiError {
             ApiError::DatabaseError(_) => ErrorCode::DatabaseError,
             ApiError::DatabaseUnavailable => ErrorCode::DatabaseUnavailable,
             ApiError::TooManyRequests(_) => ErrorCode::TooManyRequests,
+            ApiError::NotFound(_) => ErrorCode::NotFound,
+            ApiError::IndexerBehind { .. } => ErrorCode::IndexerBehind,
+            ApiError::NotImplemented(_) => ErrorCode::NotImplemented,
+            ApiError::Unauthorized(_) => ErrorCode::Unauthorized,
         }
     }
 }
@@ -763,6 +1271,17 @@ impl fmt::Display for ApiError {
             ApiError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
             ApiError::DatabaseUnavailable => write!(f, "Database unavailable"),
             ApiError::TooManyRequests(msg) => write!(f, "{}", msg),
+            ApiError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            ApiError::IndexerBehind {
+                min_block,
+                indexer_block,
+            } => write!(
+                f,
+                "Indexer at block {}, behind min_block {}",
+                indexer_block, min_block
+            ),
+            ApiError::NotImplemented(msg) => write!(f, "Not implemented: {}", msg),
+            ApiError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
         }
     }
 }
@@ -774,10 +1293,17 @@ impl ResponseError for ApiError {
             ApiError::DatabaseError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::DatabaseUnavailable => StatusCode::SERVICE_UNAVAILABLE,
             ApiError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::IndexerBehind { .. } => StatusCode::CONFLICT,
+            ApiError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
         };
 
         let mut response = HttpResponse::build(status);
-        if matches!(self, ApiError::TooManyRequests(_)) {
+        if matches!(
+            self,
+            ApiError::TooManyRequests(_) | ApiError::IndexerBehind { .. }
+        ) {
             response.insert_header(("Retry-After", "1"));
         }
         response.json(ErrorResponse {
@@ -789,6 +1315,12 @@ impl ResponseError for ApiError {
 
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
+        if let Some(unsupported) = err.downcast_ref::<Unsupported>() {
+            tracing::warn!(target: "fastkv-server", error = %unsupported, "Unsupported query");
+            return ApiError::NotImplemented(
+                "this deployment lacks the index behind this endpoint".to_string(),
+            );
+        }
         // Log full error internally for debugging, but return generic message to client
         // to prevent information disclosure (paths, IPs, schema details)
         tracing::error!(
@@ -824,6 +1356,36 @@ pub struct EdgesParams {
     pub after_source: Option<String>,
 }
 
+// GET /v1/kv/by-tx query params
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct TxLookupParams {
+    pub tx_hash: String,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub fields: Option<String>,
+    /// Value format: "raw" (default) or "json" (decoded).
+    #[serde(default)]
+    pub value_format: Option<String>,
+}
+
+// GET /v1/kv/by-receipt query params
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct ReceiptLookupParams {
+    pub receipt_id: String,
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub fields: Option<String>,
+    /// Value format: "raw" (default) or "json" (decoded).
+    #[serde(default)]
+    pub value_format: Option<String>,
+}
+
 // GET /v1/kv/edges/count query params
 #[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
 pub struct EdgesCountParams {
@@ -831,6 +1393,40 @@ pub struct EdgesCountParams {
     pub target: String,
 }
 
+// GET /v1/kv/count query params
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct KeyCountParams {
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+    /// Skip keys whose current value is null (deleted). Requires a capped scan.
+    #[serde(default)]
+    pub exclude_deleted: Option<bool>,
+}
+
+// GET /v1/kv/writers/count query params
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct WritersCountParams {
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
+    pub key: String,
+    /// Skip writers whose current value is null (deleted). Requires a capped scan.
+    #[serde(default)]
+    pub exclude_deleted: Option<bool>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct CountResponse {
+    pub count: usize,
+    /// True when the scan hit `MAX_COUNT_SCAN`; `count` is then a lower bound.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    #[schema(default = false)]
+    pub approximate: bool,
+}
+
 #[derive(Debug, Clone, Serialize, utoipa::ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct EdgeSourceEntry {
@@ -838,6 +1434,50 @@ pub struct EdgeSourceEntry {
     pub block_height: u64,
 }
 
+// GET /v1/blocks/{height}/kv query params
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct BlockKvParams {
+    /// Value format: "raw" (default) or "json" (decoded).
+    #[serde(default)]
+    pub value_format: Option<String>,
+}
+
+/// Every write the indexer stored for one block, grouped by receipt then action.
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockKvResponse {
+    pub block_height: u64,
+    /// `meta.last_processed_block_height` when the request was served; null if unknown.
+    pub indexer_block: Option<u64>,
+    /// True when `blockHeight` is above `indexerBlock`: an empty `receipts`
+    /// then means "not indexed yet" rather than "no writes".
+    pub beyond_indexer_head: bool,
+    /// Receipts in execution order of their first write.
+    pub receipts: Vec<BlockReceiptWrites>,
+    /// True when the block had more than `MAX_BLOCK_WRITES` writes; the tail is omitted.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dropped_rows: Option<u32>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockReceiptWrites {
+    pub receipt_id: String,
+    pub tx_hash: String,
+    pub shard_id: Option<u32>,
+    pub receipt_index: Option<u32>,
+    pub actions: Vec<BlockActionWrites>,
+}
+
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockActionWrites {
+    pub action_index: Option<u32>,
+    pub writes: Vec<KvEntry>,
+}
+
 // StatusResponse for /v1/status
 #[derive(Serialize, utoipa::ToSchema)]
 pub struct StatusResponse {
@@ -855,10 +1495,19 @@ pub struct EdgesCountResponse {
 
 // ===== SSE Watch API types =====
 
-pub const MAX_CONCURRENT_WATCHES: usize = 100;
+pub const MAX_CONCURRENT_WATCHES: usize = 10_000;
 pub const MIN_POLL_INTERVAL: u64 = 2;
 pub const MAX_POLL_INTERVAL: u64 = 30;
 pub const SSE_HEARTBEAT_SECS: u64 = 15;
+/// Writes one watch subscription reads per poll; any backlog carries over to the next poll.
+pub const MAX_WATCH_EVENTS_PER_POLL: usize = 1000;
+/// Topics a `WatchHub` round polls at once. Separate from request fan-out,
+/// since a round reads every topic followed by any connection.
+pub const WATCH_POLL_CONCURRENCY: usize = 64;
+/// Keys one multi-key watch connection, or topics one WebSocket, may follow.
+pub const MAX_WATCH_KEYS: usize = 50;
+/// Longest `/v1/kv/wait` holds a request.
+pub const MAX_WAIT_TIMEOUT_SECS: u64 = 60;
 
 /// Parameters for the SSE key watch endpoint.
 #[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
@@ -870,14 +1519,47 @@ pub struct WatchParams {
     #[serde(rename = "contractId")]
     pub current_account_id: String,
     /// Key to watch for changes.
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Watch every key under this prefix instead of a single `key`.
+    #[serde(default)]
+    pub key_prefix: Option<String>,
+}
+
+/// Body of the multi-key SSE watch endpoint.
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct WatchMultiRequest {
+    /// Keys to follow, across writers and contracts (max `MAX_WATCH_KEYS`).
+    pub keys: Vec<MultiBatchItem>,
+}
+
+/// Query form of `WatchMultiRequest`, for `EventSource`, which can only GET.
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct WatchMultiParams {
+    /// The `keys` array of the POST body as JSON, URL-encoded:
+    /// `[{"accountId":…,"contractId":…,"key":…}]`.
+    pub keys: String,
+}
+
+/// Parameters for the long-poll key wait endpoint.
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct WaitParams {
+    /// NEAR account that wrote the data (signer/predecessor).
+    #[serde(rename = "accountId")]
+    pub predecessor_id: String,
+    /// Contract where the data is stored.
+    #[serde(rename = "contractId")]
+    pub current_account_id: String,
     pub key: String,
-    /// Poll interval in seconds (default 5, clamped to 2–30).
-    #[serde(default = "default_watch_interval")]
-    pub interval: u64,
+    /// Respond once the key has a write above this block height.
+    pub since_block: i64,
+    /// Seconds to wait before responding 204 (default 30, max `MAX_WAIT_TIMEOUT_SECS`).
+    #[serde(default = "default_wait_timeout")]
+    pub timeout: u64,
 }
 
-fn default_watch_interval() -> u64 {
-    5
+fn default_wait_timeout() -> u64 {
+    30
 }
 
 /// SSE event payload emitted when a watched key changes.
@@ -894,6 +1576,178 @@ pub struct WatchEvent {
     pub current_account_id: String,
 }
 
+impl From<KvEntry> for WatchEvent {
+    fn from(entry: KvEntry) -> Self {
+        Self {
+            key: entry.key,
+            value: entry.value,
+            block_height: entry.block_height,
+            block_timestamp: entry.block_timestamp,
+            predecessor_id: entry.predecessor_id,
+            current_account_id: entry.current_account_id,
+        }
+    }
+}
+
+/// Message from a `/v1/ws` client.
+#[derive(Deserialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsClientMessage {
+    /// Follow `topic` under a client-chosen subscription `id`.
+    Subscribe {
+        id: String,
+        topic: WatchFilter,
+        /// Deliver writes from this block on. Without it, a key opens with
+        /// its current value and other topics follow new writes only.
+        #[serde(default)]
+        from_block: Option<i64>,
+    },
+    /// Stop following subscription `id`.
+    Unsubscribe { id: String },
+}
+
+/// What a `/v1/ws` subscription or a webhook follows.
+#[derive(Serialize, Deserialize, Clone, utoipa::ToSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WatchFilter {
+    /// Every write to one key.
+    Key {
+        #[serde(rename = "accountId")]
+        predecessor_id: String,
+        #[serde(rename = "contractId")]
+        current_account_id: String,
+        key: String,
+    },
+    /// The newest write per key and block under a prefix.
+    Prefix {
+        #[serde(rename = "accountId")]
+        predecessor_id: String,
+        #[serde(rename = "contractId")]
+        current_account_id: String,
+        key_prefix: String,
+    },
+    /// Sources writing `{edge_type}/{target}`, as `/v1/kv/edges` lists them.
+    Edge {
+        edge_type: String,
+        target: String,
+        /// Defaults to the social contract.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        contract_id: Option<String>,
+    },
+    /// Accounts writing `index/{action}/{key}`, as `/v1/social/index` lists them.
+    SocialIndex {
+        action: String,
+        key: String,
+        /// Defaults to the social contract.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        contract_id: Option<String>,
+    },
+}
+
+/// Message to a `/v1/ws` client.
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsServerMessage {
+    /// A `subscribe` or `unsubscribe` took effect.
+    Ack { id: String, op: String },
+    /// A message was rejected; `id` is omitted when the message did not parse.
+    Error {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        id: Option<String>,
+        error: String,
+    },
+    /// One write to subscription `id`'s topic.
+    Change { id: String, event: WatchEvent },
+    /// Sent every `SSE_HEARTBEAT_SECS`.
+    Heartbeat,
+}
+
+// ===== Read-your-writes =====
+
+/// Longest a `min_block` request waits for the indexer.
+pub const MIN_BLOCK_WAIT_SECS: u64 = 10;
+/// How often waiting requests re-read the indexer head from the store.
+pub const MIN_BLOCK_REFRESH_MILLIS: u64 = 250;
+/// How far past the indexer head a `min_block` may be and still be waited on;
+/// anything further can't be reached within `MIN_BLOCK_WAIT_SECS`.
+pub const MIN_BLOCK_MAX_AHEAD: u64 = 100;
+
+// ===== Webhook API types =====
+
+pub const MAX_WEBHOOKS: usize = 1000;
+/// Registered webhooks per client IP.
+pub const MAX_WEBHOOKS_PER_CLIENT: usize = 20;
+pub const MAX_WEBHOOK_URL_LENGTH: usize = 2048;
+pub const MIN_WEBHOOK_SECRET_LENGTH: usize = 16;
+pub const MAX_WEBHOOK_SECRET_LENGTH: usize = 256;
+/// Per-attempt timeout for a webhook delivery POST.
+pub const WEBHOOK_DELIVERY_TIMEOUT_SECS: u64 = 10;
+/// How long registry changes gather before `webhooks.json` is rewritten.
+pub const WEBHOOK_SAVE_DEBOUNCE_MILLIS: u64 = 500;
+/// Size at which a webhook's dead-letter log is rotated; one rotation is kept.
+pub const MAX_DEAD_LETTER_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Body of `POST /v1/webhooks`.
+#[derive(Deserialize, utoipa::ToSchema)]
+pub struct WebhookRequest {
+    /// `http(s)` URL that receives change payloads.
+    pub url: String,
+    /// What to deliver; the same filters `/v1/ws` subscribes to.
+    pub filter: WatchFilter,
+    /// HMAC-SHA256 key for `X-Webhook-Signature`, also required to manage
+    /// the webhook.
+    pub secret: String,
+}
+
+/// A registered webhook, without its secret.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WebhookResponse {
+    pub id: String,
+    pub url: String,
+    pub filter: WatchFilter,
+    /// RFC 3339 registration time.
+    pub created_at: String,
+}
+
+/// JSON body POSTed to a webhook URL.
+#[derive(Serialize, utoipa::ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookPayload {
+    pub webhook_id: String,
+    /// Writes matching the filter, in write order.
+    pub changes: Vec<WebhookChange>,
+}
+
+/// One write in a webhook payload.
+#[derive(Serialize, utoipa::ToSchema)]
+pub struct WebhookChange {
+    /// `block_height:order_id`, as in `/v1/kv/watch` event ids.
+    pub id: String,
+    #[serde(flatten)]
+    pub event: WatchEvent,
+}
+
+/// A payload that exhausted its delivery attempts.
+#[derive(Serialize, Deserialize, utoipa::ToSchema)]
+pub struct DeadLetter {
+    pub webhook_id: String,
+    /// RFC 3339 time of the last attempt.
+    pub failed_at: String,
+    pub attempts: u32,
+    /// Error of the last attempt: a status code or transport error.
+    pub error: String,
+    /// The undelivered `WebhookPayload`.
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+}
+
+#[derive(Deserialize, Clone, utoipa::ToSchema, utoipa::IntoParams)]
+pub struct DeadLettersParams {
+    /// Newest dead letters to return (default 100, max 1000).
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -993,6 +1847,7 @@ mod tests {
             truncated: false,
             next_cursor: Some("abc".to_string()),
             dropped_rows: None,
+            ..Default::default()
         };
         let json = serde_json::to_value(&meta).unwrap();
         assert_eq!(json["has_more"], true);
@@ -1005,6 +1860,7 @@ mod tests {
             truncated: true,
             next_cursor: None,
             dropped_rows: None,
+            ..Default::default()
         };
         let json = serde_json::to_value(&meta_no_cursor).unwrap();
         assert_eq!(json["truncated"], true);
@@ -1018,6 +1874,7 @@ mod tests {
             truncated: false,
             next_cursor: Some("last_key".to_string()),
             dropped_rows: None,
+            ..Default::default()
         };
         let json = serde_json::to_value(&meta).unwrap();
         assert_eq!(json["has_more"], false);
@@ -1032,11 +1889,32 @@ mod tests {
             truncated: false,
             next_cursor: None,
             dropped_rows: Some(3),
+            ..Default::default()
         };
         let json = serde_json::to_value(&meta).unwrap();
         assert_eq!(json["dropped_rows"], 3);
     }
 
+    #[test]
+    fn test_parse_time_param() {
+        assert_eq!(
+            parse_time_param("1700000000000000000", "from_time").unwrap(),
+            1_700_000_000_000_000_000
+        );
+        assert_eq!(
+            parse_time_param("2023-11-14T22:13:20Z", "from_time").unwrap(),
+            1_700_000_000_000_000_000
+        );
+        assert_eq!(
+            parse_time_param("2023-11-14T23:13:20.5+01:00", "to_time").unwrap(),
+            1_700_000_000_500_000_000
+        );
+        assert!(parse_time_param("1969-12-31T23:59:59Z", "from_time").is_err());
+        assert!(parse_time_param("yesterday", "from_time").is_err());
+        assert!(parse_time_param("", "to_time").is_err());
+        assert!(parse_time_param("99999999999999999999", "to_time").is_err());
+    }
+
     #[test]
     fn test_parse_field_set_valid() {
         let input = Some("key,value,blockHeight".to_string());
@@ -1075,6 +1953,16 @@ mod tests {
         assert_eq!(json["code"], "INVALID_PARAMETER");
     }
 
+    #[test]
+    fn test_unsupported_query_is_not_implemented() {
+        let err = ApiError::from(anyhow::Error::new(Unsupported("mv_kv_by_tx")));
+        assert_eq!(err.error_response().status(), StatusCode::NOT_IMPLEMENTED);
+        assert!(matches!(err.code(), ErrorCode::NotImplemented));
+
+        let err = ApiError::from(anyhow::anyhow!("timeout"));
+        assert!(matches!(err.code(), ErrorCode::DatabaseError));
+    }
+
     #[test]
     fn test_parse_history_cursor() {
         let (bh, oid) = parse_history_cursor("139000500:3").unwrap();
@@ -1110,4 +1998,61 @@ mod tests {
         assert!(parse_timeline_cursor("123").is_err());
         assert!(parse_timeline_cursor("-1:key").is_err());
     }
+
+    fn kv(key: &str, value: &str) -> KvEntry {
+        KvEntry::from(KvRow {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key: key.to_string(),
+            value: value.to_string(),
+            block_height: 1,
+            block_timestamp: 0,
+            receipt_id: String::new(),
+            tx_hash: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_tree_diff_between() {
+        let a = vec![kv("p/a", "1"), kv("p/b", "2"), kv("p/c", "3")];
+        let b = vec![kv("p/b", "2"), kv("p/c", "4"), kv("p/d", "5")];
+        let diff = TreeDiff::between(a, b);
+        let keys = |v: &[KvEntry]| v.iter().map(|e| e.key.clone()).collect::<Vec<_>>();
+        assert_eq!(keys(&diff.removed), vec!["p/a"]);
+        assert_eq!(keys(&diff.added), vec!["p/d"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].key, "p/c");
+        assert_eq!(diff.changed[0].a.value, "3");
+        assert_eq!(diff.changed[0].b.value, "4");
+    }
+
+    #[test]
+    fn test_history_metadata_fields_selectable() {
+        let entry = KvEntry::from(KvHistoryRow {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key: "profile/name".to_string(),
+            block_height: 10,
+            order_id: 0,
+            value: "\"Alice\"".to_string(),
+            block_timestamp: 0,
+            receipt_id: String::new(),
+            tx_hash: String::new(),
+            signer_id: "relayer.near".to_string(),
+            shard_id: 4,
+            receipt_index: 1,
+            action_index: -1,
+        });
+        let fields = parse_field_set(&Some("signerId,shardId,actionIndex".to_string())).unwrap();
+        let json = entry.to_json_with_fields(&fields);
+        assert_eq!(
+            json,
+            serde_json::json!({ "signerId": "relayer.near", "shardId": 4 })
+        );
+
+        // -1 is the unknown sentinel, not action 0
+        let full = serde_json::to_value(&entry).unwrap();
+        assert_eq!(full["receiptIndex"], 1);
+        assert!(full.get("actionIndex").is_none());
+    }
 }
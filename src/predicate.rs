@@ -0,0 +1,344 @@
+//! `where=` value predicates: a small JSON-path expression language evaluated
+//! against the decoded value of each scanned row.
+//!
+//! ```text
+//! expr    := and ("||" and)*
+//! and     := unary ("&&" unary)*
+//! unary   := "!" unary | "(" expr ")" | "exists(" path ")" | path op literal
+//! path    := "$" ("." ident | "[" index "]" | "[" string "]")*
+//! op      := "==" | "!=" | ">" | ">=" | "<" | "<="
+//! literal := JSON string | number | true | false | null
+//! ```
+//!
+//! Comparisons against a missing path are false (including `!=`). Ordering
+//! operators compare numbers numerically and strings lexicographically; any
+//! other pairing is false. Rows whose value is not valid JSON never match.
+
+use serde_json::Value;
+
+const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    Exists(Vec<Segment>),
+    Compare(Vec<Segment>, Op, Value),
+}
+
+/// A parsed `where=` expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate(Expr);
+
+impl Predicate {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            src: input,
+            pos: 0,
+            depth: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_ws();
+        if parser.pos < input.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Predicate(expr))
+    }
+
+    /// Evaluate against a raw stored value (a JSON-serialized string).
+    pub fn matches(&self, raw_value: &str) -> bool {
+        serde_json::from_str::<Value>(raw_value).is_ok_and(|value| eval(&self.0, &value))
+    }
+}
+
+fn eval(expr: &Expr, value: &Value) -> bool {
+    match expr {
+        Expr::Or(terms) => terms.iter().any(|e| eval(e, value)),
+        Expr::And(terms) => terms.iter().all(|e| eval(e, value)),
+        Expr::Not(inner) => !eval(inner, value),
+        Expr::Exists(path) => resolve(value, path).is_some_and(|v| !v.is_null()),
+        Expr::Compare(path, op, literal) => {
+            resolve(value, path).is_some_and(|actual| compare(actual, *op, literal))
+        }
+    }
+}
+
+fn resolve<'a>(mut value: &'a Value, path: &[Segment]) -> Option<&'a Value> {
+    for segment in path {
+        value = match segment {
+            Segment::Field(name) => value.get(name)?,
+            Segment::Index(i) => value.get(*i)?,
+        };
+    }
+    Some(value)
+}
+
+fn compare(actual: &Value, op: Op, literal: &Value) -> bool {
+    use std::cmp::Ordering;
+    let ordering = match (actual, literal) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    };
+    match op {
+        Op::Eq => ordering.map_or(actual == literal, |o| o == Ordering::Equal),
+        Op::Ne => ordering.map_or(actual != literal, |o| o != Ordering::Equal),
+        Op::Gt => ordering == Some(Ordering::Greater),
+        Op::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        Op::Lt => ordering == Some(Ordering::Less),
+        Op::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, msg: &str) -> String {
+        format!("{msg} at position {}", self.pos)
+    }
+
+    fn rest(&self) -> &str {
+        &self.src[self.pos..]
+    }
+
+    fn skip_ws(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.src.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_ws();
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{token}'")))
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(self.error("expression nested too deeply"));
+        }
+        let mut terms = vec![self.and()?];
+        while self.eat("||") {
+            terms.push(self.and()?);
+        }
+        self.depth -= 1;
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::Or(terms)
+        })
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut terms = vec![self.unary()?];
+        while self.eat("&&") {
+            terms.push(self.unary()?);
+        }
+        Ok(if terms.len() == 1 {
+            terms.remove(0)
+        } else {
+            Expr::And(terms)
+        })
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            self.depth += 1;
+            if self.depth > MAX_DEPTH {
+                return Err(self.error("expression nested too deeply"));
+            }
+            let inner = self.unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(inner)));
+        }
+        if self.eat("(") {
+            let inner = self.expr()?;
+            self.expect(")")?;
+            return Ok(inner);
+        }
+        if self.eat("exists") {
+            self.expect("(")?;
+            let path = self.path()?;
+            self.expect(")")?;
+            return Ok(Expr::Exists(path));
+        }
+        let path = self.path()?;
+        let op = self.op()?;
+        let literal = self.literal()?;
+        Ok(Expr::Compare(path, op, literal))
+    }
+
+    fn path(&mut self) -> Result<Vec<Segment>, String> {
+        self.expect("$")?;
+        let mut segments = Vec::new();
+        loop {
+            if self.rest().starts_with('.') {
+                self.pos += 1;
+                let len = self
+                    .rest()
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+                    .unwrap_or(self.rest().len());
+                if len == 0 {
+                    return Err(self.error("expected field name after '.'"));
+                }
+                segments.push(Segment::Field(self.rest()[..len].to_string()));
+                self.pos += len;
+            } else if self.rest().starts_with('[') {
+                self.pos += 1;
+                self.skip_ws();
+                if self.rest().starts_with('"') {
+                    match self.literal()? {
+                        Value::String(name) => segments.push(Segment::Field(name)),
+                        _ => unreachable!("quoted literal is a string"),
+                    }
+                } else {
+                    let len = self
+                        .rest()
+                        .find(|c: char| !c.is_ascii_digit())
+                        .unwrap_or(self.rest().len());
+                    let index = self.rest()[..len]
+                        .parse()
+                        .map_err(|_| self.error("expected array index or quoted field"))?;
+                    segments.push(Segment::Index(index));
+                    self.pos += len;
+                }
+                self.expect("]")?;
+            } else {
+                return Ok(segments);
+            }
+        }
+    }
+
+    fn op(&mut self) -> Result<Op, String> {
+        // Two-character operators first so ">=" is not read as ">".
+        for (token, op) in [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+        ] {
+            if self.eat(token) {
+                return Ok(op);
+            }
+        }
+        Err(self.error("expected comparison operator"))
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        self.skip_ws();
+        let rest = self.rest();
+        let len = if rest.starts_with('"') {
+            let mut escaped = false;
+            let mut end = None;
+            for (i, c) in rest.char_indices().skip(1) {
+                match c {
+                    _ if escaped => escaped = false,
+                    '\\' => escaped = true,
+                    '"' => {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            end.ok_or_else(|| self.error("unterminated string"))?
+        } else {
+            rest.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')))
+                .unwrap_or(rest.len())
+        };
+        let value = serde_json::from_str::<Value>(&rest[..len])
+            .ok()
+            .filter(|v| !v.is_array() && !v.is_object())
+            .ok_or_else(|| self.error("expected JSON literal"))?;
+        self.pos += len;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(expr: &str, value: &str) -> bool {
+        Predicate::parse(expr).unwrap().matches(value)
+    }
+
+    #[test]
+    fn test_comparisons_and_exists() {
+        let nft = r#"{"type":"nft","price":12.5,"image":{"ipfs_cid":"bafy"},"tags":["art"]}"#;
+        assert!(matches(r#"$.type == "nft""#, nft));
+        assert!(matches("$.price > 10", nft));
+        assert!(!matches("$.price <= 12", nft));
+        assert!(matches("exists($.image.ipfs_cid)", nft));
+        assert!(!matches("exists($.image.url)", nft));
+        assert!(matches(r#"$.tags[0] == "art""#, nft));
+        assert!(matches(r#"$["type"] != "ft""#, nft));
+        assert!(matches(
+            r#"$.type == "ft" || ($.price >= 12.5 && !exists($.sold))"#,
+            nft
+        ));
+
+        // Missing paths and mismatched types never match
+        assert!(!matches(r#"$.missing != "x""#, nft));
+        assert!(!matches(r#"$.type > 3"#, nft));
+        // Top-level scalars and non-JSON values
+        assert!(matches(r#"$ == "Alice""#, r#""Alice""#));
+        assert!(!matches("$ == 1", "not json"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for bad in [
+            "",
+            "$.price >",
+            "$.price ~ 3",
+            "price > 3",
+            r#"$.type == "nft"#,
+            "$.a == [1]",
+            "exists($.a",
+            "$.a == 1 extra",
+            "$.",
+        ] {
+            assert!(Predicate::parse(bad).is_err(), "{bad:?} should not parse");
+        }
+        let deep = format!("{}$.a == 1{}", "(".repeat(40), ")".repeat(40));
+        assert!(Predicate::parse(&deep).is_err());
+        let nots = format!("{}$.a == 1", "!".repeat(40));
+        assert!(Predicate::parse(&nots).is_err());
+    }
+}
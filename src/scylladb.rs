@@ -1,13 +1,16 @@
 use scylla::client::session::Session;
 use scylla::client::session_builder::SessionBuilder;
-use scylla::errors::NextRowError;
 use scylla::statement::prepared::PreparedStatement;
 
+use crate::key_pattern::KeyPattern;
 use crate::models::{
-    bigint_to_u64, AccountsParams, ContractAccountRow, ContractKeyRow, ContractRow, EdgeRow, EdgeSourceEntry,
-    HistoryParams, KvEntry, KvHistoryRow, KvRow, KvTimelineRow, QueryParams, TimelineParams,
-    WritersParams, MAX_DEDUP_SCAN,
+    bigint_to_u64, AccountsParams, ContractAccountRow, ContractKeyRow, ContractRow, ContractTimelineParams, EdgeRow, EdgeSourceEntry,
+    HistoryParams, KvEntry, KvHistoryRow, KvRow, KvTimelineRow, PrefixHistoryParams, QueryParams, TimelineParams,
+    WritersParams, MAX_COUNT_SCAN, MAX_DEDUP_SCAN, MAX_FILTER_SCAN,
 };
+use crate::predicate::Predicate;
+use crate::store::{KvRowStream, KvStore, Unsupported};
+use async_trait::async_trait;
 use fastnear_primitives::types::ChainId;
 use futures::stream::StreamExt;
 use futures::Stream;
@@ -36,7 +39,7 @@ pub struct PageResult<T> {
 ///   Collects ALL valid items up to `cap` raw rows scanned.
 ///   Sets `truncated = true` if cap hit. Does NOT apply offset/limit
 ///   (caller post-sorts then slices). `has_more` is left as `false`.
-pub async fn collect_page<T, R, S, F>(
+pub async fn collect_page<T, R, E, S, F>(
     stream: &mut S,
     limit: usize,
     offset: usize,
@@ -44,7 +47,8 @@ pub async fn collect_page<T, R, S, F>(
     mut transform: F,
 ) -> PageResult<T>
 where
-    S: Stream<Item = Result<R, NextRowError>> + Unpin,
+    S: Stream<Item = Result<R, E>> + Unpin,
+    E: std::fmt::Display,
     F: FnMut(R) -> Option<T>,
 {
     let mut items = match scan_cap {
@@ -115,6 +119,282 @@ where
     }
 }
 
+/// Overfetch-mode `collect_page` with a budget on raw rows scanned, for
+/// app-side filters (`where=`) that may reject most of a partition.
+///
+/// Stops after `max_scanned` rows. If the page is not full by then and the
+/// stream still has rows, sets both `truncated` and `has_more`: the caller
+/// must resume from the last row *scanned*, not the last item returned.
+/// `max_scanned = None` is plain overfetch `collect_page`.
+pub async fn collect_page_budgeted<T, R, E, S, F>(
+    stream: &mut S,
+    limit: usize,
+    offset: usize,
+    max_scanned: Option<usize>,
+    transform: F,
+) -> PageResult<T>
+where
+    S: Stream<Item = Result<R, E>> + Unpin,
+    E: std::fmt::Display,
+    F: FnMut(R) -> Option<T>,
+{
+    let Some(budget) = max_scanned else {
+        return collect_page(stream, limit, offset, None, transform).await;
+    };
+
+    let mut scanned = 0usize;
+    let page = {
+        let mut budgeted = stream.by_ref().inspect(|_| scanned += 1).take(budget);
+        collect_page(&mut budgeted, limit, offset, None, transform).await
+    };
+    if page.has_more || scanned < budget {
+        return page;
+    }
+    let truncated = stream.next().await.is_some();
+    PageResult {
+        has_more: truncated,
+        truncated,
+        ..page
+    }
+}
+
+/// Parses a scan's `where=` clause. Returns `(predicate, scan_budget)`;
+/// both are `None` without a clause.
+pub(crate) fn parse_where(
+    where_clause: Option<&str>,
+) -> anyhow::Result<(Option<Predicate>, Option<usize>)> {
+    let predicate = where_clause
+        .map(Predicate::parse)
+        .transpose()
+        .map_err(|e| anyhow::anyhow!("where: {e}"))?;
+    let budget = predicate.is_some().then_some(MAX_FILTER_SCAN);
+    Ok((predicate, budget))
+}
+
+/// App-side row filters of a prefix query: `key_pattern` and `where=`.
+pub(crate) struct QueryFilter {
+    key_pattern: Option<KeyPattern>,
+    predicate: Option<Predicate>,
+}
+
+impl QueryFilter {
+    pub(crate) fn from_params(params: &QueryParams) -> anyhow::Result<Self> {
+        let regex = params.pattern_syntax.as_deref() == Some("regex");
+        let key_pattern = params
+            .key_pattern
+            .as_deref()
+            .map(|p| KeyPattern::parse(p, regex))
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("key_pattern: {e}"))?;
+        let (predicate, _) = parse_where(params.where_clause.as_deref())?;
+        Ok(QueryFilter {
+            key_pattern,
+            predicate,
+        })
+    }
+
+    /// Key prefix to range-scan: the pattern's literal prefix when a
+    /// `key_pattern` is set (`None` if it has none), otherwise `key_prefix`.
+    pub(crate) fn scan_prefix<'a>(&'a self, params: &'a QueryParams) -> Option<&'a str> {
+        match &self.key_pattern {
+            Some(pattern) => Some(pattern.literal_prefix()).filter(|p| !p.is_empty()),
+            None => params.key_prefix.as_deref(),
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.key_pattern.is_some() || self.predicate.is_some()
+    }
+
+    /// Scan budget for `collect_page_budgeted`; `None` without filters.
+    pub(crate) fn budget(&self) -> Option<usize> {
+        self.is_active().then_some(MAX_FILTER_SCAN)
+    }
+
+    pub(crate) fn matches(&self, entry: &KvEntry) -> bool {
+        let key = &entry.key;
+        let value = &entry.value;
+        self.key_pattern.as_ref().is_none_or(|p| p.matches(key))
+            && self.predicate.as_ref().is_none_or(|p| p.matches(value))
+    }
+}
+
+/// Counts non-null rows, reading at most `MAX_COUNT_SCAN`. Returns
+/// `(count, approximate)`; `approximate` means rows were left unread.
+pub(crate) async fn count_live_rows<S, E>(mut rows: S) -> anyhow::Result<(usize, bool)>
+where
+    S: Stream<Item = Result<KvRow, E>> + Unpin,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let mut count = 0;
+    let mut scanned = 0;
+    while let Some(row) = rows.next().await {
+        if scanned == MAX_COUNT_SCAN {
+            return Ok((count, true));
+        }
+        scanned += 1;
+        if row?.value != "null" {
+            count += 1;
+        }
+    }
+    Ok((count, false))
+}
+
+/// Descending counterpart of `latest_as_of` for `ORDER BY key DESC` scans
+/// (key DESC, block_height DESC, order_id DESC): the first row of each key at
+/// or before `block_height` is its newest write.
+pub fn latest_as_of_desc<S, E>(
+    mut rows: S,
+    block_height: i64,
+) -> impl Stream<Item = Result<KvHistoryRow, E>> + Unpin
+where
+    S: Stream<Item = Result<KvHistoryRow, E>> + Unpin,
+{
+    Box::pin(async_stream::stream! {
+        let mut last_key: Option<String> = None;
+        while let Some(row_result) = rows.next().await {
+            match row_result {
+                Ok(row) => {
+                    if row.block_height <= block_height && last_key.as_deref() != Some(&row.key) {
+                        last_key = Some(row.key.clone());
+                        yield Ok(row);
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+    })
+}
+
+/// Folds a key-ordered `s_kv` stream (key ASC, block_height ASC, order_id ASC)
+/// into one row per key: the newest write at or before `block_height`.
+/// Keys with no such write yield nothing; row errors pass through unchanged.
+pub fn latest_as_of<S, E>(
+    mut rows: S,
+    block_height: i64,
+) -> impl Stream<Item = Result<KvHistoryRow, E>> + Unpin
+where
+    S: Stream<Item = Result<KvHistoryRow, E>> + Unpin,
+{
+    Box::pin(async_stream::stream! {
+        let mut pending: Option<KvHistoryRow> = None;
+        while let Some(row_result) = rows.next().await {
+            match row_result {
+                Ok(row) => {
+                    if let Some(prev) = pending.take_if(|p| p.key != row.key) {
+                        yield Ok(prev);
+                    }
+                    if row.block_height <= block_height {
+                        pending = Some(row);
+                    }
+                }
+                Err(e) => yield Err(e),
+            }
+        }
+        if let Some(row) = pending {
+            yield Ok(row);
+        }
+    })
+}
+
+/// Where a budgeted `at_block` scan stopped.
+#[derive(Default)]
+struct KeyScan {
+    /// Rows were left unread.
+    truncated: bool,
+    /// Key of the last row read; every version of it was read.
+    last_key: Option<String>,
+}
+
+/// Caps a key-ordered `s_kv` scan at `budget` rows, rounded up to the end of
+/// the current key so the fold downstream never sees a key half-read.
+fn budget_by_key<'a, S, E>(
+    mut rows: S,
+    budget: usize,
+    scan: &'a mut KeyScan,
+) -> impl Stream<Item = Result<KvHistoryRow, E>> + Unpin + 'a
+where
+    S: Stream<Item = Result<KvHistoryRow, E>> + Unpin + 'a,
+    E: 'a,
+{
+    Box::pin(async_stream::stream! {
+        let mut scanned = 0usize;
+        while let Some(row_result) = rows.next().await {
+            if let Ok(row) = &row_result {
+                if scanned >= budget && scan.last_key.as_deref() != Some(&row.key) {
+                    scan.truncated = true;
+                    break;
+                }
+                scan.last_key = Some(row.key.clone());
+            }
+            scanned += 1;
+            yield row_result;
+        }
+    })
+}
+
+/// Pages an `at_block` query from a raw `s_kv` scan in `history_range` (or,
+/// for `order=desc`, `history_range_desc`) order: folds it to each key's
+/// newest write at or before `block_height`, then applies the query's filters
+/// and offset. At most `MAX_FILTER_SCAN` versions are read (see
+/// `budget_by_key`); past that the page is `truncated` and `next_cursor` is
+/// the last key read.
+pub(crate) async fn page_as_of<S, E>(
+    rows: S,
+    params: &QueryParams,
+    filter: &QueryFilter,
+    block_height: i64,
+) -> (Vec<KvEntry>, bool, bool, usize, Option<String>)
+where
+    S: Stream<Item = Result<KvHistoryRow, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    let exclude_deleted = params.exclude_deleted.unwrap_or(false);
+    let cursor = params.after_key.as_deref().or(params.before_key.as_deref());
+    let offset = effective_offset(cursor, params.offset);
+    let mut scan = KeyScan::default();
+    let page = {
+        let rows = budget_by_key(rows, MAX_FILTER_SCAN, &mut scan);
+        let mut rows = if params.order.eq_ignore_ascii_case("desc") {
+            latest_as_of_desc(rows, block_height).left_stream()
+        } else {
+            latest_as_of(rows, block_height).right_stream()
+        };
+        collect_page(
+            &mut rows,
+            params.limit,
+            offset,
+            None,
+            |row: KvHistoryRow| {
+                let entry = KvEntry::from(row);
+                if exclude_deleted && entry.value == "null" {
+                    return None;
+                }
+                if !filter.matches(&entry) {
+                    return None;
+                }
+                Some(entry)
+            },
+        )
+        .await
+    };
+
+    // A full page ends before the cap, so its own last key resumes it
+    let truncated = scan.truncated && !page.has_more;
+    let next_cursor = if truncated {
+        scan.last_key
+    } else {
+        page.items.last().map(|e| e.key.clone())
+    };
+    (
+        page.items,
+        page.has_more || truncated,
+        truncated,
+        page.dropped_rows,
+        next_cursor,
+    )
+}
+
 /// Validate that a CQL identifier (keyspace/table name) contains only safe characters.
 pub(crate) fn validate_identifier(name: &str, label: &str) -> anyhow::Result<()> {
     if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
@@ -132,14 +412,16 @@ pub struct ScyllaDb {
     get_kv_last: PreparedStatement,
     query_kv_no_prefix: PreparedStatement,
     query_kv_cursor: PreparedStatement,
-    pub(crate) reverse_kv: PreparedStatement,
+    reverse_kv: PreparedStatement,
     reverse_list: PreparedStatement,
     reverse_list_cursor: PreparedStatement,
     history_asc: PreparedStatement,
     history_desc: PreparedStatement,
-    get_kv_at_block: PreparedStatement,
+    history_range: PreparedStatement,
     timeline_asc: PreparedStatement,
     timeline_desc: PreparedStatement,
+    block_at_or_after_time: PreparedStatement,
+    block_at_or_before_time: PreparedStatement,
     accounts_by_contract: PreparedStatement,
     accounts_by_contract_key: PreparedStatement,
     accounts_all: PreparedStatement,
@@ -150,18 +432,21 @@ pub struct ScyllaDb {
     edges_list: PreparedStatement,
     edges_list_cursor: PreparedStatement,
     edges_count: PreparedStatement,
+    count_kv: PreparedStatement,
+    count_writers: PreparedStatement,
     prefix_query: PreparedStatement,
     prefix_cursor_query: PreparedStatement,
+    range_query_desc: PreparedStatement,
+    history_range_desc: PreparedStatement,
+    /// Optional views and tables: `None` when the cluster lacks them.
+    kv_by_tx: Option<PreparedStatement>,
+    kv_by_receipt: Option<PreparedStatement>,
+    kv_by_block: Option<PreparedStatement>,
+    contract_timeline_asc: Option<PreparedStatement>,
+    contract_timeline_desc: Option<PreparedStatement>,
     meta_query: PreparedStatement,
 
-    pub scylla_session: Session,
-    pub table_name: String,
-    pub history_table_name: String,
-    pub reverse_view_name: String,
-    pub kv_accounts_table_name: String,
-    pub all_accounts_table_name: String,
-    pub kv_edges_table_name: String,
-    pub kv_reverse_table_name: String,
+    scylla_session: Session,
 }
 
 pub fn create_rustls_client_config() -> anyhow::Result<Arc<ClientConfig>> {
@@ -241,14 +526,6 @@ impl ScyllaDb {
         Ok(())
     }
 
-    pub async fn health_check(&self) -> anyhow::Result<()> {
-        // Simple query to verify connection
-        let mut stmt = scylla::statement::Statement::new("SELECT now() FROM system.local");
-        stmt.set_request_timeout(Some(std::time::Duration::from_secs(10)));
-        self.scylla_session.query_unpaged(stmt, &[]).await?;
-        Ok(())
-    }
-
     pub async fn new(chain_id: ChainId, scylla_session: Session) -> anyhow::Result<Self> {
         // Support custom keyspace or use default pattern
         let keyspace = env::var("KEYSPACE").unwrap_or_else(|_| format!("fastdata_{chain_id}"));
@@ -271,6 +548,14 @@ impl ScyllaDb {
             env::var("KV_EDGES_TABLE_NAME").unwrap_or_else(|_| "kv_edges".to_string());
         let kv_reverse_table_name =
             env::var("KV_REVERSE_TABLE_NAME").unwrap_or_else(|_| "kv_reverse".to_string());
+        let kv_by_tx_view_name =
+            env::var("KV_BY_TX_VIEW_NAME").unwrap_or_else(|_| "mv_kv_by_tx".to_string());
+        let kv_by_receipt_view_name =
+            env::var("KV_BY_RECEIPT_VIEW_NAME").unwrap_or_else(|_| "mv_kv_by_receipt".to_string());
+        let kv_by_block_view_name =
+            env::var("KV_BY_BLOCK_VIEW_NAME").unwrap_or_else(|_| "mv_kv_by_block".to_string());
+        let kv_by_contract_table_name = env::var("KV_BY_CONTRACT_TABLE_NAME")
+            .unwrap_or_else(|_| "s_kv_by_contract".to_string());
 
         validate_identifier(&table_name, "TABLE_NAME")?;
         validate_identifier(&history_table_name, "HISTORY_TABLE_NAME")?;
@@ -279,6 +564,10 @@ impl ScyllaDb {
         validate_identifier(&all_accounts_table_name, "ALL_ACCOUNTS_TABLE_NAME")?;
         validate_identifier(&kv_edges_table_name, "KV_EDGES_TABLE_NAME")?;
         validate_identifier(&kv_reverse_table_name, "KV_REVERSE_TABLE_NAME")?;
+        validate_identifier(&kv_by_tx_view_name, "KV_BY_TX_VIEW_NAME")?;
+        validate_identifier(&kv_by_receipt_view_name, "KV_BY_RECEIPT_VIEW_NAME")?;
+        validate_identifier(&kv_by_block_view_name, "KV_BY_BLOCK_VIEW_NAME")?;
+        validate_identifier(&kv_by_contract_table_name, "KV_BY_CONTRACT_TABLE_NAME")?;
 
         let columns = "predecessor_id, current_account_id, key, value, block_height, block_timestamp, receipt_id, tx_hash";
         let history_columns = "predecessor_id, current_account_id, key, block_height, order_id, value, block_timestamp, receipt_id, tx_hash, signer_id, shard_id, receipt_index, action_index";
@@ -330,9 +619,9 @@ impl ScyllaDb {
                 &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key = ? AND block_height >= ? AND block_height <= ? ORDER BY block_height DESC, order_id DESC", history_columns, history_table_name),
                 scylla::frame::types::Consistency::LocalOne,
             ).await?,
-            get_kv_at_block: Self::prepare_query(
+            history_range: Self::prepare_query(
                 &scylla_session,
-                &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key = ? AND block_height = ?", history_columns, history_table_name),
+                &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key >= ? AND key < ?", history_columns, history_table_name),
                 scylla::frame::types::Consistency::LocalOne,
             ).await?,
             timeline_desc: Self::prepare_query(
@@ -345,6 +634,18 @@ impl ScyllaDb {
                 &format!("SELECT {} FROM s_kv_by_block WHERE predecessor_id = ? AND current_account_id = ? AND block_height >= ? AND block_height <= ? ORDER BY block_height ASC, key DESC", timeline_columns),
                 scylla::frame::types::Consistency::LocalOne,
             ).await?,
+            // block_timestamp is not a clustering column, so these filter within one
+            // partition. Timestamps grow with block height, so LIMIT 1 stops at the boundary.
+            block_at_or_after_time: Self::prepare_query(
+                &scylla_session,
+                "SELECT block_height FROM s_kv_by_block WHERE predecessor_id = ? AND current_account_id = ? AND block_timestamp >= ? ORDER BY block_height ASC LIMIT 1 ALLOW FILTERING",
+                scylla::frame::types::Consistency::LocalOne,
+            ).await?,
+            block_at_or_before_time: Self::prepare_query(
+                &scylla_session,
+                "SELECT block_height FROM s_kv_by_block WHERE predecessor_id = ? AND current_account_id = ? AND block_timestamp <= ? ORDER BY block_height DESC LIMIT 1 ALLOW FILTERING",
+                scylla::frame::types::Consistency::LocalOne,
+            ).await?,
             // LocalQuorum for kv_accounts: this table is populated asynchronously so
             // LocalOne reads could return stale/partial results after recent writes.
             accounts_by_contract: Self::prepare_query(
@@ -397,6 +698,16 @@ impl ScyllaDb {
                 &format!("SELECT COUNT(*) FROM {} WHERE edge_type = ? AND target = ?", kv_edges_table_name),
                 scylla::frame::types::Consistency::LocalOne,
             ).await?,
+            count_kv: Self::prepare_query(
+                &scylla_session,
+                &format!("SELECT COUNT(*) FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key >= ? AND key < ?", table_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await?,
+            count_writers: Self::prepare_query(
+                &scylla_session,
+                &format!("SELECT COUNT(*) FROM {} WHERE current_account_id = ? AND key = ?", kv_reverse_table_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await?,
             prefix_query: Self::prepare_query(
                 &scylla_session,
                 &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key >= ? AND key < ?", columns, table_name),
@@ -407,19 +718,47 @@ impl ScyllaDb {
                 &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key > ? AND key < ?", columns, table_name),
                 scylla::frame::types::Consistency::LocalOne,
             ).await?,
+            range_query_desc: Self::prepare_query(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key >= ? AND key < ? ORDER BY key DESC", columns, table_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await?,
+            history_range_desc: Self::prepare_query(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE predecessor_id = ? AND current_account_id = ? AND key >= ? AND key < ? ORDER BY key DESC", history_columns, history_table_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await?,
+            kv_by_tx: Self::prepare_optional(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE tx_hash = ?", history_columns, kv_by_tx_view_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await,
+            kv_by_receipt: Self::prepare_optional(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE receipt_id = ?", history_columns, kv_by_receipt_view_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await,
+            kv_by_block: Self::prepare_optional(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE block_height = ?", history_columns, kv_by_block_view_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await,
+            contract_timeline_asc: Self::prepare_optional(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE current_account_id = ? AND block_bucket = ? AND block_height >= ? AND block_height <= ? ORDER BY block_height ASC, order_id ASC, predecessor_id ASC, key ASC", history_columns, kv_by_contract_table_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await,
+            contract_timeline_desc: Self::prepare_optional(
+                &scylla_session,
+                &format!("SELECT {} FROM {} WHERE current_account_id = ? AND block_bucket = ? AND block_height >= ? AND block_height <= ? ORDER BY block_height DESC, order_id DESC, predecessor_id DESC, key DESC", history_columns, kv_by_contract_table_name),
+                scylla::frame::types::Consistency::LocalOne,
+            ).await,
             meta_query: Self::prepare_query(
                 &scylla_session,
                 "SELECT last_processed_block_height FROM meta WHERE suffix = ?",
                 scylla::frame::types::Consistency::LocalOne,
             ).await?,
             scylla_session,
-            table_name,
-            history_table_name,
-            reverse_view_name,
-            kv_accounts_table_name,
-            all_accounts_table_name,
-            kv_edges_table_name,
-            kv_reverse_table_name,
         })
     }
 
@@ -434,7 +773,99 @@ impl ScyllaDb {
         Ok(scylla_db_session.prepare(query).await?)
     }
 
-    pub async fn get_kv(
+    /// `prepare_query` for a table or view that only some endpoints need.
+    /// A failure is logged and yields `None`, so the session still connects
+    /// and only those endpoints report `Unsupported`.
+    pub async fn prepare_optional(
+        scylla_db_session: &Session,
+        query_text: &str,
+        consistency: scylla::frame::types::Consistency,
+    ) -> Option<PreparedStatement> {
+        match Self::prepare_query(scylla_db_session, query_text, consistency).await {
+            Ok(statement) => Some(statement),
+            Err(e) => {
+                tracing::warn!(
+                    target: "fastkv-server",
+                    query = query_text,
+                    error = %e,
+                    "Optional statement not prepared; its endpoints are disabled"
+                );
+                None
+            }
+        }
+    }
+
+    /// Runs one of the `block_at_*_time` lookups and returns the matching block.
+    async fn block_at_time(
+        &self,
+        statement: &PreparedStatement,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        let timestamp = i64::try_from(timestamp_ns).unwrap_or(i64::MAX);
+        let result = self
+            .scylla_session
+            .execute_unpaged(statement, (predecessor_id, current_account_id, timestamp))
+            .await?
+            .into_rows_result()?;
+
+        let block_height = result
+            .rows::<(i64,)>()?
+            .next()
+            .transpose()?
+            .map(|row| bigint_to_u64(row.0));
+
+        Ok(block_height)
+    }
+
+    /// Point-in-time variant of `query_kv_with_pagination`: scans every version
+    /// in the key range from `s_kv` and keeps the newest one at or before
+    /// `block_height` per key. Keys first written after that block are skipped.
+    async fn query_kv_as_of(
+        &self,
+        params: &QueryParams,
+        block_height: i64,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
+        let filter = QueryFilter::from_params(params)?;
+        let range = KeyRange::new(params, filter.scan_prefix(params));
+        if range.is_empty() {
+            return Ok((Vec::new(), false, false, 0, None));
+        }
+        let statement = if params.order.eq_ignore_ascii_case("desc") {
+            &self.history_range_desc
+        } else {
+            &self.history_range
+        };
+        let rows_stream = self
+            .scylla_session
+            .execute_iter(
+                statement.clone(),
+                (
+                    &params.predecessor_id,
+                    &params.current_account_id,
+                    &range.lower,
+                    &range.upper,
+                ),
+            )
+            .await?
+            .rows_stream::<KvHistoryRow>()?;
+
+        Ok(page_as_of(rows_stream, params, &filter, block_height).await)
+    }
+}
+
+#[async_trait]
+impl KvStore for ScyllaDb {
+    async fn health_check(&self) -> anyhow::Result<()> {
+        // Simple query to verify connection
+        let mut stmt = scylla::statement::Statement::new("SELECT now() FROM system.local");
+        stmt.set_request_timeout(Some(std::time::Duration::from_secs(10)));
+        self.scylla_session.query_unpaged(stmt, &[]).await?;
+        Ok(())
+    }
+
+    async fn get_kv(
         &self,
         predecessor_id: &str,
         current_account_id: &str,
@@ -455,7 +886,7 @@ impl ScyllaDb {
         Ok(entry)
     }
 
-    pub async fn get_kv_last(
+    async fn get_kv_last(
         &self,
         predecessor_id: &str,
         current_account_id: &str,
@@ -481,10 +912,10 @@ impl ScyllaDb {
     /// Supports cursor pagination via `after_account`.
     /// Optionally filters to a specific writer (predecessor_id).
     /// Returns (entries, has_more, truncated, dropped_rows).
-    pub async fn query_writers(
+    async fn query_writers(
         &self,
         params: &WritersParams,
-    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize)> {
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
         let mut rows_stream = match &params.after_account {
             Some(cursor) => self
                 .scylla_session
@@ -506,14 +937,19 @@ impl ScyllaDb {
 
         let exclude_deleted = params.exclude_deleted.unwrap_or(false);
         let pred_filter = params.predecessor_id.clone();
+        let (predicate, budget) = parse_where(params.where_clause.as_deref())?;
         let offset = effective_offset(params.after_account.as_deref(), params.offset);
-        let page = collect_page(
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
             &mut rows_stream,
             params.limit,
             offset,
-            None,
+            budget,
             |row: KvRow| {
                 let entry = KvEntry::from(row);
+                if predicate.is_some() {
+                    last_scanned = Some(entry.predecessor_id.clone());
+                }
                 if let Some(ref pred) = pred_filter {
                     if entry.predecessor_id != *pred {
                         return None;
@@ -522,15 +958,29 @@ impl ScyllaDb {
                 if exclude_deleted && entry.value == "null" {
                     return None;
                 }
+                if predicate.as_ref().is_some_and(|p| !p.matches(&entry.value)) {
+                    return None;
+                }
                 Some(entry)
             },
         )
         .await;
 
-        Ok((page.items, page.has_more, page.truncated, page.dropped_rows))
+        let next_cursor = if page.truncated {
+            last_scanned
+        } else {
+            page.items.last().map(|e| e.predecessor_id.clone())
+        };
+        Ok((
+            page.items,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
     }
 
-    pub async fn query_accounts(
+    async fn query_accounts(
         &self,
         params: &AccountsParams,
     ) -> anyhow::Result<(Vec<String>, bool, usize)> {
@@ -575,7 +1025,7 @@ impl ScyllaDb {
 
     /// Returns `(accounts, has_more, truncated, dropped_rows)`.
     /// `truncated` is true if scan hit MAX_DEDUP_SCAN.
-    pub async fn query_accounts_by_contract(
+    async fn query_accounts_by_contract(
         &self,
         contract_id: &str,
         key: Option<&str>,
@@ -666,7 +1116,7 @@ impl ScyllaDb {
     /// 64-bit token space.
     ///
     /// Returns `(accounts, has_more, dropped_rows)`.
-    pub async fn query_all_accounts(
+    async fn query_all_accounts(
         &self,
         limit: usize,
         after_account: Option<&str>,
@@ -722,7 +1172,7 @@ impl ScyllaDb {
     /// Deduplicates consecutive rows with the same `current_account_id`.
     ///
     /// Returns `(contracts, has_more, dropped_rows)`.
-    pub async fn query_all_contracts(
+    async fn query_all_contracts(
         &self,
         limit: usize,
         after_contract: Option<&str>,
@@ -778,7 +1228,7 @@ impl ScyllaDb {
     /// is a cheap single-partition query.
     ///
     /// Returns `(contracts, has_more, dropped_rows)`.
-    pub async fn query_contracts_by_account(
+    async fn query_contracts_by_account(
         &self,
         account_id: &str,
         limit: usize,
@@ -823,106 +1273,154 @@ impl ScyllaDb {
         Ok((page.items, page.has_more, page.dropped_rows))
     }
 
-    /// Returns (entries, has_more, dropped_rows).
-    pub async fn query_kv_with_pagination(
+    async fn query_kv_with_pagination(
         &self,
         params: &QueryParams,
-    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
-        let mut rows_stream = match (&params.key_prefix, &params.after_key) {
-            // Prefix + cursor: key > cursor AND key < prefix_end
-            (Some(prefix), Some(cursor)) => {
-                let prefix_end = compute_prefix_end(prefix);
-                self.scylla_session
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
+        if let Some(block_height) = params.at_block {
+            return self.query_kv_as_of(params, block_height).await;
+        }
+
+        let filter = QueryFilter::from_params(params)?;
+        let desc = params.order.eq_ignore_ascii_case("desc");
+        let mut rows_stream = if desc || params.start_key.is_some() || params.end_key.is_some() {
+            // Explicit range or reverse order: one bounded slice, cursor folded in.
+            let range = KeyRange::new(params, filter.scan_prefix(params));
+            if range.is_empty() {
+                return Ok((Vec::new(), false, false, 0, None));
+            }
+            let statement = if desc {
+                &self.range_query_desc
+            } else {
+                &self.prefix_query
+            };
+            self.scylla_session
+                .execute_iter(
+                    statement.clone(),
+                    (
+                        &params.predecessor_id,
+                        &params.current_account_id,
+                        &range.lower,
+                        &range.upper,
+                    ),
+                )
+                .await?
+                .rows_stream::<KvRow>()?
+        } else {
+            match (filter.scan_prefix(params), &params.after_key) {
+                // Prefix + cursor: key > cursor AND key < prefix_end
+                (Some(prefix), Some(cursor)) => {
+                    let prefix_end = compute_prefix_end(prefix);
+                    self.scylla_session
+                        .execute_iter(
+                            self.prefix_cursor_query.clone(),
+                            (
+                                &params.predecessor_id,
+                                &params.current_account_id,
+                                cursor,
+                                &prefix_end,
+                            ),
+                        )
+                        .await?
+                        .rows_stream::<KvRow>()?
+                }
+                // Prefix only: key >= prefix AND key < prefix_end
+                (Some(prefix), None) => {
+                    let prefix_end = compute_prefix_end(prefix);
+                    self.scylla_session
+                        .execute_iter(
+                            self.prefix_query.clone(),
+                            (
+                                &params.predecessor_id,
+                                &params.current_account_id,
+                                prefix,
+                                &prefix_end,
+                            ),
+                        )
+                        .await?
+                        .rows_stream::<KvRow>()?
+                }
+                // No prefix + cursor: key > cursor
+                (None, Some(cursor)) => self
+                    .scylla_session
                     .execute_iter(
-                        self.prefix_cursor_query.clone(),
-                        (
-                            &params.predecessor_id,
-                            &params.current_account_id,
-                            cursor,
-                            &prefix_end,
-                        ),
+                        self.query_kv_cursor.clone(),
+                        (&params.predecessor_id, &params.current_account_id, cursor),
                     )
                     .await?
-                    .rows_stream::<KvRow>()?
-            }
-            // Prefix only: key >= prefix AND key < prefix_end
-            (Some(prefix), None) => {
-                let prefix_end = compute_prefix_end(prefix);
-                self.scylla_session
+                    .rows_stream::<KvRow>()?,
+                // No prefix, no cursor: all keys
+                (None, None) => self
+                    .scylla_session
                     .execute_iter(
-                        self.prefix_query.clone(),
-                        (
-                            &params.predecessor_id,
-                            &params.current_account_id,
-                            prefix.as_str(),
-                            &prefix_end,
-                        ),
+                        self.query_kv_no_prefix.clone(),
+                        (&params.predecessor_id, &params.current_account_id),
                     )
                     .await?
-                    .rows_stream::<KvRow>()?
+                    .rows_stream::<KvRow>()?,
             }
-            // No prefix + cursor: key > cursor
-            (None, Some(cursor)) => self
-                .scylla_session
-                .execute_iter(
-                    self.query_kv_cursor.clone(),
-                    (&params.predecessor_id, &params.current_account_id, cursor),
-                )
-                .await?
-                .rows_stream::<KvRow>()?,
-            // No prefix, no cursor: all keys
-            (None, None) => self
-                .scylla_session
-                .execute_iter(
-                    self.query_kv_no_prefix.clone(),
-                    (&params.predecessor_id, &params.current_account_id),
-                )
-                .await?
-                .rows_stream::<KvRow>()?,
         };
 
         let exclude_deleted = params.exclude_deleted.unwrap_or(false);
-        let offset = effective_offset(params.after_key.as_deref(), params.offset);
-        let page = collect_page(
+        let cursor = params.after_key.as_deref().or(params.before_key.as_deref());
+        let offset = effective_offset(cursor, params.offset);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
             &mut rows_stream,
             params.limit,
             offset,
-            None,
+            filter.budget(),
             |row: KvRow| {
                 let entry = KvEntry::from(row);
+                if filter.is_active() {
+                    last_scanned = Some(entry.key.clone());
+                }
                 if exclude_deleted && entry.value == "null" {
                     return None;
                 }
+                if !filter.matches(&entry) {
+                    return None;
+                }
                 Some(entry)
             },
         )
         .await;
 
-        Ok((page.items, page.has_more, page.dropped_rows))
+        let next_cursor = if page.truncated {
+            last_scanned
+        } else {
+            page.items.last().map(|e| e.key.clone())
+        };
+        Ok((
+            page.items,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
     }
 
-    pub async fn get_kv_at_block(
+    async fn get_kv_at_block(
         &self,
         predecessor_id: &str,
         current_account_id: &str,
         key: &str,
         block_height: i64,
     ) -> anyhow::Result<Option<KvEntry>> {
-        let result = self
+        // history_desc over [0, block_height]: the first row is the newest
+        // write at or before the requested block.
+        let mut rows_stream = self
             .scylla_session
-            .execute_unpaged(
-                &self.get_kv_at_block,
-                (predecessor_id, current_account_id, key, block_height),
+            .execute_iter(
+                self.history_desc.clone(),
+                (predecessor_id, current_account_id, key, 0i64, block_height),
             )
             .await?
-            .into_rows_result()?;
+            .rows_stream::<KvHistoryRow>()?;
 
-        // Multiple rows can exist per block_height (different order_id values);
-        // .last() takes the highest order_id since clustering order is ASC.
-        let mut last_ok: Option<KvHistoryRow> = None;
-        for row_result in result.rows::<KvHistoryRow>()? {
+        while let Some(row_result) = rows_stream.next().await {
             match row_result {
-                Ok(row) => last_ok = Some(row),
+                Ok(row) => return Ok(Some(KvEntry::from(row))),
                 Err(e) => {
                     tracing::warn!(
                         target: "fastkv-server",
@@ -932,15 +1430,24 @@ impl ScyllaDb {
                 }
             }
         }
-        let entry = last_ok.map(KvEntry::from);
 
-        Ok(entry)
+        Ok(None)
     }
 
-    pub async fn get_kv_timeline(
+    async fn get_kv_timeline(
         &self,
         params: &TimelineParams,
     ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)> {
+        let (entries, has_more, _, dropped, next_cursor) = self
+            .get_kv_prefix_history(&PrefixHistoryParams::from(params))
+            .await?;
+        Ok((entries, has_more, dropped, next_cursor))
+    }
+
+    async fn get_kv_prefix_history(
+        &self,
+        params: &PrefixHistoryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
         let is_asc = params.order.eq_ignore_ascii_case("asc");
 
         let cursor = match &params.cursor {
@@ -977,11 +1484,15 @@ impl ScyllaDb {
             .await?
             .rows_stream::<KvTimelineRow>()?;
 
-        let page = collect_page(
+        // Clustering is block-first, so the prefix can't be pushed down:
+        // filter app-side on a budget
+        let budget = (!params.key_prefix.is_empty()).then_some(MAX_FILTER_SCAN);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
             &mut rows_stream,
             params.limit,
             0,
-            None,
+            budget,
             |row: KvTimelineRow| {
                 if let Some((cb, ref ck)) = cursor {
                     if row.block_height == cb {
@@ -994,23 +1505,114 @@ impl ScyllaDb {
                         }
                     }
                 }
+                last_scanned = Some((row.block_height, row.key.clone()));
+                if !row.key.starts_with(params.key_prefix.as_str()) {
+                    return None;
+                }
                 let key = row.key.clone();
                 Some((KvEntry::from(row), key))
             },
         )
         .await;
 
-        let next_cursor = page
-            .items
-            .last()
-            .map(|(e, key)| format!("{}:{key}", e.block_height));
+        let next_cursor = if page.truncated {
+            last_scanned.map(|(bh, key)| format!("{bh}:{key}"))
+        } else {
+            page.items
+                .last()
+                .map(|(e, key)| format!("{}:{key}", e.block_height))
+        };
         let entries: Vec<KvEntry> = page.items.into_iter().map(|(e, _)| e).collect();
 
-        Ok((entries, page.has_more, page.dropped_rows, next_cursor))
+        Ok((
+            entries,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
+    }
+
+    async fn get_contract_timeline(
+        &self,
+        params: &ContractTimelineParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)> {
+        let is_asc = params.order.eq_ignore_ascii_case("asc");
+        let stmt = if is_asc {
+            &self.contract_timeline_asc
+        } else {
+            &self.contract_timeline_desc
+        };
+        // Written by the indexer, not derived by a view: absent on older clusters
+        let stmt = stmt.as_ref().ok_or(Unsupported("s_kv_by_contract"))?;
+
+        let cursor = match &params.cursor {
+            Some(c) if !c.is_empty() => Some(
+                crate::models::parse_contract_timeline_cursor(c)
+                    .map_err(|e| anyhow::anyhow!("{e}"))?,
+            ),
+            _ => None,
+        };
+
+        let mut from_block = params.from_block;
+        let mut to_block = params.to_block;
+        if let Some((cb, ..)) = &cursor {
+            if is_asc {
+                from_block = from_block.max(*cb);
+            } else {
+                to_block = to_block.min(*cb);
+            }
+        }
+        if from_block > to_block {
+            return Ok((Vec::new(), false, 0, None));
+        }
+
+        let mut buckets: Vec<i64> =
+            (from_block / CONTRACT_BLOCK_BUCKET..=to_block / CONTRACT_BLOCK_BUCKET).collect();
+        if !is_asc {
+            buckets.reverse();
+        }
+
+        // One partition per bucket, walked in page order. Once the page is full
+        // the next non-empty bucket only has to prove `has_more` (limit 0).
+        let mut rows: Vec<KvHistoryRow> = Vec::new();
+        let mut has_more = false;
+        let mut dropped_rows = 0;
+        for bucket in buckets {
+            let mut rows_stream = self
+                .scylla_session
+                .execute_iter(
+                    stmt.clone(),
+                    (&params.current_account_id, bucket, from_block, to_block),
+                )
+                .await?
+                .rows_stream::<KvHistoryRow>()?;
+            let page = collect_page(
+                &mut rows_stream,
+                params.limit - rows.len(),
+                0,
+                None,
+                |row: KvHistoryRow| match &cursor {
+                    Some(c) if !after_contract_cursor(&row, c, is_asc) => None,
+                    _ => Some(row),
+                },
+            )
+            .await;
+            dropped_rows += page.dropped_rows;
+            rows.extend(page.items);
+            if page.has_more {
+                has_more = true;
+                break;
+            }
+        }
+
+        let next_cursor = rows.last().map(contract_timeline_cursor);
+        let entries = rows.into_iter().map(KvEntry::from).collect();
+        Ok((entries, has_more, dropped_rows, next_cursor))
     }
 
     /// Returns (entries, has_more, dropped_rows).
-    pub async fn query_edges(
+    async fn query_edges(
         &self,
         edge_type: &str,
         target: &str,
@@ -1049,7 +1651,7 @@ impl ScyllaDb {
         Ok((page.items, page.has_more, page.dropped_rows))
     }
 
-    pub async fn count_edges(&self, edge_type: &str, target: &str) -> anyhow::Result<usize> {
+    async fn count_edges(&self, edge_type: &str, target: &str) -> anyhow::Result<usize> {
         let result = self
             .scylla_session
             .execute_unpaged(&self.edges_count, (edge_type, target))
@@ -1066,10 +1668,130 @@ impl ScyllaDb {
         Ok(count)
     }
 
-    pub async fn get_kv_history(
+    async fn count_keys(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key_prefix: Option<&str>,
+        exclude_deleted: bool,
+    ) -> anyhow::Result<(usize, bool)> {
+        let prefix = key_prefix.unwrap_or("");
+        let prefix_end = compute_prefix_end(prefix);
+        if exclude_deleted {
+            let rows = self
+                .scylla_session
+                .execute_iter(
+                    self.prefix_query.clone(),
+                    (predecessor_id, current_account_id, prefix, &prefix_end),
+                )
+                .await?
+                .rows_stream::<KvRow>()?;
+            return count_live_rows(rows).await;
+        }
+
+        let result = self
+            .scylla_session
+            .execute_unpaged(
+                &self.count_kv,
+                (predecessor_id, current_account_id, prefix, &prefix_end),
+            )
+            .await?
+            .into_rows_result()?;
+        let count = result
+            .rows::<(i64,)>()?
+            .next()
+            .transpose()?
+            .map_or(0, |row| row.0.max(0) as usize);
+
+        Ok((count, false))
+    }
+
+    async fn count_writers(
+        &self,
+        current_account_id: &str,
+        key: &str,
+        exclude_deleted: bool,
+    ) -> anyhow::Result<(usize, bool)> {
+        if exclude_deleted {
+            let rows = self
+                .scylla_session
+                .execute_iter(self.reverse_list.clone(), (current_account_id, key))
+                .await?
+                .rows_stream::<KvRow>()?;
+            return count_live_rows(rows).await;
+        }
+
+        let result = self
+            .scylla_session
+            .execute_unpaged(&self.count_writers, (current_account_id, key))
+            .await?
+            .into_rows_result()?;
+        let count = result
+            .rows::<(i64,)>()?
+            .next()
+            .transpose()?
+            .map_or(0, |row| row.0.max(0) as usize);
+
+        Ok((count, false))
+    }
+
+    async fn query_kv_by_tx(
+        &self,
+        tx_hash: &str,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
+        let statement = self.kv_by_tx.clone().ok_or(Unsupported("mv_kv_by_tx"))?;
+        let mut rows_stream = self
+            .scylla_session
+            .execute_iter(statement, (tx_hash,))
+            .await?
+            .rows_stream::<KvHistoryRow>()?;
+
+        let page = collect_page(
+            &mut rows_stream,
+            limit,
+            offset,
+            None,
+            |row: KvHistoryRow| Some(KvEntry::from(row)),
+        )
+        .await;
+
+        Ok((page.items, page.has_more, page.dropped_rows))
+    }
+
+    async fn query_kv_by_receipt(
+        &self,
+        receipt_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
+        let statement = self
+            .kv_by_receipt
+            .clone()
+            .ok_or(Unsupported("mv_kv_by_receipt"))?;
+        let mut rows_stream = self
+            .scylla_session
+            .execute_iter(statement, (receipt_id,))
+            .await?
+            .rows_stream::<KvHistoryRow>()?;
+
+        let page = collect_page(
+            &mut rows_stream,
+            limit,
+            offset,
+            None,
+            |row: KvHistoryRow| Some(KvEntry::from(row)),
+        )
+        .await;
+
+        Ok((page.items, page.has_more, page.dropped_rows))
+    }
+
+    async fn get_kv_history(
         &self,
         params: &HistoryParams,
-    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)> {
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)> {
         let is_asc = params.order.eq_ignore_ascii_case("asc");
 
         let cursor = match &params.cursor {
@@ -1112,11 +1834,14 @@ impl ScyllaDb {
             .await?
             .rows_stream::<KvHistoryRow>()?;
 
-        let page = collect_page(
+        // signer_id is not a clustering column: filter app-side on a budget
+        let budget = params.signer_id.is_some().then_some(MAX_FILTER_SCAN);
+        let mut last_scanned = None;
+        let page = collect_page_budgeted(
             &mut rows_stream,
             params.limit,
             0,
-            None,
+            budget,
             |row: KvHistoryRow| {
                 if let Some((cb, co)) = cursor {
                     if row.block_height == cb {
@@ -1129,22 +1854,88 @@ impl ScyllaDb {
                         }
                     }
                 }
+                last_scanned = Some((row.block_height, row.order_id));
+                if params.signer_id.as_deref().is_some_and(|signer| row.signer_id != signer) {
+                    return None;
+                }
                 let oid = row.order_id;
                 Some((KvEntry::from(row), oid))
             },
         )
         .await;
 
-        let next_cursor = page
-            .items
-            .last()
-            .map(|(e, oid)| format!("{}:{oid}", e.block_height));
+        let next_cursor = if page.truncated {
+            last_scanned.map(|(bh, oid)| format!("{bh}:{oid}"))
+        } else {
+            page.items
+                .last()
+                .map(|(e, oid)| format!("{}:{oid}", e.block_height))
+        };
         let entries: Vec<KvEntry> = page.items.into_iter().map(|(e, _)| e).collect();
 
-        Ok((entries, page.has_more, page.dropped_rows, next_cursor))
+        Ok((
+            entries,
+            page.has_more,
+            page.truncated,
+            page.dropped_rows,
+            next_cursor,
+        ))
+    }
+
+    async fn query_kv_by_block(
+        &self,
+        block_height: i64,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)> {
+        let statement = self
+            .kv_by_block
+            .clone()
+            .ok_or(Unsupported("mv_kv_by_block"))?;
+        let mut rows_stream = self
+            .scylla_session
+            .execute_iter(statement, (block_height,))
+            .await?
+            .rows_stream::<KvHistoryRow>()?;
+
+        let page = collect_page(&mut rows_stream, limit, 0, None, |row: KvHistoryRow| {
+            Some(KvEntry::from(row))
+        })
+        .await;
+
+        Ok((page.items, page.has_more, page.dropped_rows))
+    }
+
+    async fn first_block_at_or_after(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        self.block_at_time(
+            &self.block_at_or_after_time,
+            predecessor_id,
+            current_account_id,
+            timestamp_ns,
+        )
+        .await
     }
 
-    pub async fn get_indexer_block_height(&self) -> anyhow::Result<Option<u64>> {
+    async fn last_block_at_or_before(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>> {
+        self.block_at_time(
+            &self.block_at_or_before_time,
+            predecessor_id,
+            current_account_id,
+            timestamp_ns,
+        )
+        .await
+    }
+
+    async fn get_indexer_block_height(&self) -> anyhow::Result<Option<u64>> {
         let result = self
             .scylla_session
             .execute_unpaged(&self.meta_query, ("kv-1",))
@@ -1159,19 +1950,114 @@ impl ScyllaDb {
 
         Ok(block_height)
     }
+
+    async fn stream_reverse_kv(
+        &self,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<KvRowStream> {
+        let rows_stream = self
+            .scylla_session
+            .execute_iter(self.reverse_kv.clone(), (current_account_id, key))
+            .await?
+            .rows_stream::<KvRow>()?;
+
+        Ok(rows_stream.map(|r| r.map_err(anyhow::Error::from)).boxed())
+    }
 }
 
-fn compute_prefix_end(prefix: &str) -> String {
+pub(crate) fn compute_prefix_end(prefix: &str) -> String {
     format!("{prefix}\u{10ffff}")
 }
 
-fn effective_offset(cursor: Option<&str>, offset: usize) -> usize {
+/// Half-open key range `[lower, upper)` of a prefix query: the intersection of
+/// the scan prefix, `start_key`/`end_key` and the `after_key`/`before_key` cursor.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct KeyRange {
+    pub lower: String,
+    pub upper: String,
+}
+
+impl KeyRange {
+    pub(crate) fn new(params: &QueryParams, prefix: Option<&str>) -> Self {
+        let prefix = prefix.unwrap_or("");
+        let start = params.start_key.as_deref().map(|k| {
+            if params.start_inclusive == Some(false) {
+                key_successor(k)
+            } else {
+                k.to_string()
+            }
+        });
+        let end = params.end_key.as_deref().map(|k| {
+            if params.end_inclusive == Some(true) {
+                key_successor(k)
+            } else {
+                k.to_string()
+            }
+        });
+        let lower = [start, params.after_key.as_deref().map(key_successor)]
+            .into_iter()
+            .flatten()
+            .fold(prefix.to_string(), std::cmp::max);
+        let upper = [end, params.before_key.clone()]
+            .into_iter()
+            .flatten()
+            .fold(compute_prefix_end(prefix), std::cmp::min);
+        KeyRange { lower, upper }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lower >= self.upper
+    }
+}
+
+/// The smallest key that sorts after `key`, turning `> key` into `>= successor`.
+fn key_successor(key: &str) -> String {
+    format!("{key}\0")
+}
+
+pub(crate) fn effective_offset(cursor: Option<&str>, offset: usize) -> usize {
     if cursor.is_some() { 0 } else { offset }
 }
 
+/// Blocks per `s_kv_by_contract` partition (`block_bucket = block_height / 100_000`).
+const CONTRACT_BLOCK_BUCKET: i64 = 100_000;
+
+/// Parsed `/kv/contract/timeline` cursor: `(block_height, order_id, predecessor_id, key)`.
+pub(crate) type ContractCursor = (i64, i64, String, String);
+
+/// True when `row` sorts after `cursor` in page order: `s_kv_by_contract`
+/// clustering for asc, its exact reverse for desc.
+pub(crate) fn after_contract_cursor(
+    row: &KvHistoryRow,
+    cursor: &ContractCursor,
+    is_asc: bool,
+) -> bool {
+    let row_pos = (
+        row.block_height,
+        row.order_id,
+        row.predecessor_id.as_str(),
+        row.key.as_str(),
+    );
+    let cursor_pos = (cursor.0, cursor.1, cursor.2.as_str(), cursor.3.as_str());
+    if is_asc {
+        row_pos > cursor_pos
+    } else {
+        row_pos < cursor_pos
+    }
+}
+
+pub(crate) fn contract_timeline_cursor(row: &KvHistoryRow) -> String {
+    format!(
+        "{}:{}:{}:{}",
+        row.block_height, row.order_id, row.predecessor_id, row.key
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use scylla::errors::NextRowError;
 
     #[test]
     fn test_validate_identifier_accepts_valid() {
@@ -1294,10 +2180,173 @@ mod tests {
         assert_eq!(page.dropped_rows, 0);
     }
 
+    #[tokio::test]
+    async fn test_collect_page_budgeted_truncates_sparse_scan() {
+        // Only multiples of 7 match; 10 rows are scanned before the page fills
+        let items: Vec<Result<i32, NextRowError>> = (1..=30).map(Ok).collect();
+        let mut s = futures::stream::iter(items);
+        let page =
+            collect_page_budgeted(&mut s, 5, 0, Some(10), |n| (n % 7 == 0).then_some(n)).await;
+        assert_eq!(page.items, vec![7]);
+        assert!(page.truncated);
+        assert!(page.has_more);
+        // The stream resumes right after the budget (one row peeked)
+        assert_eq!(s.next().await.unwrap().unwrap(), 12);
+    }
+
+    #[tokio::test]
+    async fn test_collect_page_budgeted_exhausted_at_budget() {
+        // Budget equals the stream length: nothing left, so not truncated
+        let items: Vec<Result<i32, NextRowError>> = (1..=10).map(Ok).collect();
+        let mut s = futures::stream::iter(items);
+        let page =
+            collect_page_budgeted(&mut s, 5, 0, Some(10), |n| (n % 7 == 0).then_some(n)).await;
+        assert_eq!(page.items, vec![7]);
+        assert!(!page.truncated);
+        assert!(!page.has_more);
+
+        // A full page within budget is ordinary overfetch
+        let items: Vec<Result<i32, NextRowError>> = (1..=30).map(Ok).collect();
+        let mut s = futures::stream::iter(items);
+        let page = collect_page_budgeted(&mut s, 2, 0, Some(10), Some).await;
+        assert_eq!(page.items, vec![1, 2]);
+        assert!(page.has_more);
+        assert!(!page.truncated);
+    }
+
+    #[tokio::test]
+    async fn test_count_live_rows_caps_scan() {
+        let row = |value: &str| KvRow {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key: "k".to_string(),
+            value: value.to_string(),
+            block_height: 0,
+            block_timestamp: 0,
+            receipt_id: String::new(),
+            tx_hash: String::new(),
+        };
+        let rows = |n: usize| {
+            let items: Vec<Result<KvRow, NextRowError>> = (0..n)
+                .map(|i| Ok(row(if i % 2 == 0 { "null" } else { "1" })))
+                .collect();
+            futures::stream::iter(items)
+        };
+
+        assert_eq!(count_live_rows(rows(10)).await.unwrap(), (5, false));
+        assert_eq!(
+            count_live_rows(rows(MAX_COUNT_SCAN)).await.unwrap(),
+            (MAX_COUNT_SCAN / 2, false)
+        );
+        assert_eq!(
+            count_live_rows(rows(MAX_COUNT_SCAN + 1)).await.unwrap(),
+            (MAX_COUNT_SCAN / 2, true)
+        );
+    }
+
     #[test]
     fn test_compute_prefix_end() {
         assert_eq!(compute_prefix_end("graph/follow/"), "graph/follow/\u{10ffff}");
         assert_eq!(compute_prefix_end("test"), "test\u{10ffff}");
         assert_eq!(compute_prefix_end(""), "\u{10ffff}");
     }
+
+    fn history_row(key: &str, block_height: i64, order_id: i64) -> KvHistoryRow {
+        KvHistoryRow {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            key: key.to_string(),
+            block_height,
+            order_id,
+            value: format!("{block_height}.{order_id}"),
+            block_timestamp: 0,
+            receipt_id: String::new(),
+            tx_hash: String::new(),
+            signer_id: String::new(),
+            shard_id: 0,
+            receipt_index: 0,
+            action_index: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_latest_as_of_keeps_newest_per_key() {
+        let rows: Vec<Result<KvHistoryRow, NextRowError>> = vec![
+            Ok(history_row("a", 10, 0)),
+            Ok(history_row("a", 20, 0)),
+            Ok(history_row("a", 20, 1)),
+            Ok(history_row("a", 30, 0)),
+            Err(make_err()),
+            Ok(history_row("b", 25, 0)), // first written after the target block
+            Ok(history_row("c", 5, 0)),
+        ];
+        let out: Vec<_> = latest_as_of(futures::stream::iter(rows), 20).collect().await;
+        assert_eq!(out.len(), 3);
+        assert!(out[0].is_err(), "row errors are yielded as soon as they are read");
+        let values: Vec<String> = out.into_iter().filter_map(|r| r.ok()).map(|r| r.value).collect();
+        assert_eq!(values, vec!["20.1", "5.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_latest_as_of_desc_keeps_newest_per_key() {
+        let rows: Vec<Result<KvHistoryRow, NextRowError>> = vec![
+            Ok(history_row("c", 5, 0)),
+            Ok(history_row("b", 25, 0)), // first written after the target block
+            Ok(history_row("a", 30, 0)),
+            Ok(history_row("a", 20, 1)),
+            Ok(history_row("a", 20, 0)),
+            Ok(history_row("a", 10, 0)),
+        ];
+        let out: Vec<_> = latest_as_of_desc(futures::stream::iter(rows), 20)
+            .collect()
+            .await;
+        let values: Vec<String> = out
+            .into_iter()
+            .filter_map(|r| r.ok())
+            .map(|r| r.value)
+            .collect();
+        assert_eq!(values, vec!["5.0", "20.1"]);
+    }
+
+    #[test]
+    fn test_key_range_intersects_bounds() {
+        let range = |query: &str| {
+            let params = actix_web::web::Query::<QueryParams>::from_query(&format!(
+                "accountId=alice.near&contractId=social.near&{query}"
+            ))
+            .unwrap();
+            let KeyRange { lower, upper } = KeyRange::new(&params, params.key_prefix.as_deref());
+            (lower, upper)
+        };
+
+        assert_eq!(
+            range("key_prefix=post/"),
+            ("post/".into(), "post/\u{10ffff}".into())
+        );
+        assert_eq!(
+            range("key_prefix=post/&start_key=post/5&end_key=post/9"),
+            ("post/5".into(), "post/9".into())
+        );
+        assert_eq!(
+            range("start_key=a&start_inclusive=false&end_key=b&end_inclusive=true"),
+            ("a\0".into(), "b\0".into())
+        );
+        // Cursors only narrow the range
+        assert_eq!(
+            range("key_prefix=post/&order=desc&before_key=post/7"),
+            ("post/".into(), "post/7".into())
+        );
+        assert_eq!(
+            range("start_key=b&after_key=a"),
+            ("b".into(), "\u{10ffff}".into())
+        );
+        assert!(KeyRange::new(
+            &actix_web::web::Query::<QueryParams>::from_query(
+                "accountId=a.near&contractId=b.near&key_prefix=x/&end_key=w"
+            )
+            .unwrap(),
+            Some("x/"),
+        )
+        .is_empty());
+    }
 }
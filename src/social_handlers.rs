@@ -1,7 +1,10 @@
-use actix_web::{get, post, web, HttpResponse};
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use futures::stream::StreamExt;
 
-use crate::handlers::{require_db, validate_account_id, validate_cursor_or_offset, validate_order};
+use crate::handlers::{
+    require_db, resolve_time_range, validate_account_id, validate_cursor_or_offset, validate_order,
+    validate_time_range, Validators,
+};
 use crate::models::*;
 use crate::tree::build_tree;
 use crate::AppState;
@@ -12,7 +15,7 @@ static SOCIAL_CONTRACT: LazyLock<String> = LazyLock::new(|| {
     std::env::var("SOCIAL_CONTRACT").unwrap_or_else(|_| "social.near".to_string())
 });
 
-fn resolve_contract(contract_id: &Option<String>) -> Result<&str, ApiError> {
+pub(crate) fn resolve_contract(contract_id: &Option<String>) -> Result<&str, ApiError> {
     match contract_id {
         Some(id) => {
             validate_account_id(id, "contract_id")?;
@@ -39,6 +42,7 @@ fn build_social_query(
         format: None,
         value_format: None,
         after_key: None,
+        ..Default::default()
     }
 }
 
@@ -53,6 +57,7 @@ fn build_writers_query(key: String, contract: &str) -> WritersParams {
         fields: None,
         value_format: None,
         after_account: None,
+        where_clause: None,
     }
 }
 
@@ -184,14 +189,8 @@ async fn query_index(q: IndexQuery<'_>) -> Result<(Vec<IndexEntry>, usize, bool)
     } = q;
     let index_key = format!("index/{}/{}", action, key);
 
-    let scylladb = require_db(app_state).await?;
-    let mut rows_stream = scylladb
-        .scylla_session
-        .execute_iter(scylladb.reverse_kv.clone(), (contract_id, &index_key))
-        .await
-        .map_err(anyhow::Error::from)?
-        .rows_stream::<KvRow>()
-        .map_err(anyhow::Error::from)?;
+    let db = require_db(app_state).await?;
+    let mut rows_stream = db.stream_reverse_kv(contract_id, &index_key).await?;
 
     let mut entries: Vec<IndexEntry> = Vec::new();
     let mut error_count = 0usize;
@@ -271,7 +270,7 @@ async fn query_index(q: IndexQuery<'_>) -> Result<(Vec<IndexEntry>, usize, bool)
     if order == "asc" {
         entries.sort_by_key(|e| e.block_height);
     } else {
-        entries.sort_by(|a, b| b.block_height.cmp(&a.block_height));
+        entries.sort_by_key(|e| std::cmp::Reverse(e.block_height));
     }
 
     entries.truncate(limit);
@@ -322,7 +321,7 @@ pub async fn social_get_handler(
 
     tracing::info!(target: PROJECT_ID, key_count = body.keys.len(), contract_id = %contract, "POST /v1/social/get");
 
-    let scylladb = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
     let mut result_root = serde_json::Map::new();
     let mut truncated = false;
 
@@ -331,7 +330,7 @@ pub async fn social_get_handler(
 
         match parsed {
             KeyPattern::Exact { account_id, key } => {
-                let entry = scylladb.get_kv(&account_id, contract, &key).await?;
+                let entry = db.get_kv(&account_id, contract, &key).await?;
 
                 if let Some(entry) = entry {
                     let items = vec![(entry.key.clone(), entry.value.clone())];
@@ -363,8 +362,8 @@ pub async fn social_get_handler(
                 let prefix = if key_prefix.is_empty() { None } else { Some(key_prefix) };
                 let query = build_social_query(account_id.clone(), contract, prefix, !return_deleted);
 
-                let (entries, _has_more, dropped) =
-                    scylladb.query_kv_with_pagination(&query).await?;
+                let (entries, _has_more, _truncated, dropped, _) =
+                    db.query_kv_with_pagination(&query).await?;
                 if dropped > 0 {
                     tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social get (recursive wildcard)");
                 }
@@ -397,8 +396,8 @@ pub async fn social_get_handler(
                 let prefix = if key_prefix.is_empty() { None } else { Some(key_prefix.clone()) };
                 let query = build_social_query(account_id.clone(), contract, prefix, !return_deleted);
 
-                let (entries, _has_more, dropped) =
-                    scylladb.query_kv_with_pagination(&query).await?;
+                let (entries, _has_more, _truncated, dropped, _) =
+                    db.query_kv_with_pagination(&query).await?;
                 if dropped > 0 {
                     tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social get (single wildcard)");
                 }
@@ -428,8 +427,8 @@ pub async fn social_get_handler(
             KeyPattern::WildcardAccount { key } => {
                 // Use by-key view to find all predecessors with this exact key
                 let by_key_params = build_writers_query(key.clone(), contract);
-                let (entries, _has_more, _truncated, dropped) =
-                    scylladb.query_writers(&by_key_params).await?;
+                let (entries, _has_more, _truncated, dropped, _) =
+                    db.query_writers(&by_key_params).await?;
                 if dropped > 0 {
                     tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social get (wildcard account)");
                 }
@@ -506,7 +505,7 @@ pub async fn social_keys_handler(
 
     tracing::info!(target: PROJECT_ID, key_count = body.keys.len(), contract_id = %contract, "POST /v1/social/keys");
 
-    let scylladb = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
     let mut result_root = serde_json::Map::new();
     let mut truncated = false;
 
@@ -515,7 +514,7 @@ pub async fn social_keys_handler(
 
         match parsed {
             KeyPattern::Exact { account_id, key } => {
-                let entry = scylladb.get_kv(&account_id, contract, &key).await?;
+                let entry = db.get_kv(&account_id, contract, &key).await?;
 
                 if let Some(entry) = entry {
                     if !return_deleted && entry.value == "null" {
@@ -549,8 +548,8 @@ pub async fn social_keys_handler(
                 let prefix = if key_prefix.is_empty() { None } else { Some(key_prefix.clone()) };
                 let query = build_social_query(account_id.clone(), contract, prefix, false);
 
-                let (entries, _has_more, dropped) =
-                    scylladb.query_kv_with_pagination(&query).await?;
+                let (entries, _has_more, _truncated, dropped, _) =
+                    db.query_kv_with_pagination(&query).await?;
                 if dropped > 0 {
                     tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social keys");
                 }
@@ -654,8 +653,8 @@ pub async fn social_keys_handler(
             }
             KeyPattern::WildcardAccount { key } => {
                 let by_key_params = build_writers_query(key.clone(), contract);
-                let (entries, _has_more, _truncated, dropped) =
-                    scylladb.query_writers(&by_key_params).await?;
+                let (entries, _has_more, _truncated, dropped, _) =
+                    db.query_writers(&by_key_params).await?;
                 if dropped > 0 {
                     tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social keys (wildcard account)");
                 }
@@ -758,6 +757,7 @@ pub async fn social_index_handler(
         truncated: timed_out,
         next_cursor,
         dropped_rows: dropped_to_option(dropped),
+        ..Default::default()
     };
 
     Ok(HttpResponse::Ok().json(PaginatedResponse {
@@ -782,30 +782,32 @@ pub async fn social_index_handler(
 pub async fn social_profile_handler(
     query: web::Query<SocialProfileParams>,
     app_state: web::Data<AppState>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, ApiError> {
     validate_account_id(&query.account_id, "account_id")?;
     let contract = resolve_contract(&query.contract_id)?;
 
     tracing::info!(target: PROJECT_ID, account_id = %query.account_id, contract_id = %contract, "GET /v1/social/profile");
 
-    let scylladb = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
     let params = build_social_query(query.account_id.clone(), contract, Some("profile/".to_string()), true);
 
-    let (entries, _has_more, dropped) = scylladb.query_kv_with_pagination(&params).await?;
+    let (entries, has_more, _truncated, dropped, next_cursor) = db.query_kv_with_pagination(&params).await?;
     if dropped > 0 {
         tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social profile");
     }
 
-    let items: Vec<(String, String)> = entries
-        .into_iter()
-        .map(|e| {
-            let key = e.key.strip_prefix("profile/").unwrap_or(&e.key).to_string();
-            (key, e.value)
-        })
-        .collect();
-
-    let tree = build_tree(&items);
-    Ok(HttpResponse::Ok().json(tree))
+    let validators = Validators::new(&req, &entries, has_more, next_cursor.as_deref());
+    Ok(validators.respond(&req, || {
+        let items: Vec<(String, String)> = entries
+            .into_iter()
+            .map(|e| {
+                let key = e.key.strip_prefix("profile/").unwrap_or(&e.key).to_string();
+                (key, e.value)
+            })
+            .collect();
+        HttpResponse::Ok().json(build_tree(&items))
+    }))
 }
 
 /// Get accounts following a user
@@ -838,7 +840,7 @@ pub async fn social_followers_handler(
 
     tracing::info!(target: PROJECT_ID, account_id = %query.account_id, contract_id = %contract, "GET /v1/social/followers");
 
-    let scylladb = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
     // Followers are accounts that wrote key "graph/follow/{accountId}" to the contract
     let follow_key = format!("graph/follow/{}", query.account_id);
 
@@ -851,7 +853,7 @@ pub async fn social_followers_handler(
         after_account: query.after_account.clone(),
     };
 
-    let (accounts, has_more, dropped) = scylladb.query_accounts(&params).await?;
+    let (accounts, has_more, dropped) = db.query_accounts(&params).await?;
     let count = accounts.len();
     let next_cursor = accounts.last().cloned();
 
@@ -863,6 +865,7 @@ pub async fn social_followers_handler(
             truncated: false,
             next_cursor,
             dropped_rows: dropped_to_option(dropped),
+            ..Default::default()
         },
     }))
 }
@@ -897,7 +900,7 @@ pub async fn social_following_handler(
 
     tracing::info!(target: PROJECT_ID, account_id = %query.account_id, contract_id = %contract, "GET /v1/social/following");
 
-    let scylladb = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
     // Following are keys under "graph/follow/" written by this account to the contract
     let params = QueryParams {
         predecessor_id: query.account_id.clone(),
@@ -913,9 +916,10 @@ pub async fn social_following_handler(
             .after_account
             .as_ref()
             .map(|a| format!("graph/follow/{}", a)),
+        ..Default::default()
     };
 
-    let (entries, has_more, dropped) = scylladb.query_kv_with_pagination(&params).await?;
+    let (entries, has_more, _truncated, dropped, _) = db.query_kv_with_pagination(&params).await?;
 
     let accounts: Vec<String> = entries
         .into_iter()
@@ -932,6 +936,7 @@ pub async fn social_following_handler(
             truncated: false,
             next_cursor,
             dropped_rows: dropped_to_option(dropped),
+            ..Default::default()
         },
     }))
 }
@@ -958,10 +963,17 @@ pub async fn social_account_feed_handler(
     validate_order(&query.order)?;
     let order = query.order.to_ascii_lowercase();
     let contract = resolve_contract(&query.contract_id)?;
+    let times = validate_time_range(query.from_time.as_deref(), query.to_time.as_deref())?;
 
-    tracing::info!(target: PROJECT_ID, account_id = %query.account_id, "GET /v1/social/feed/account");
+    tracing::info!(
+        target: PROJECT_ID,
+        account_id = %query.account_id,
+        from_time = ?query.from_time,
+        to_time = ?query.to_time,
+        "GET /v1/social/feed/account"
+    );
 
-    let scylladb = require_db(&app_state).await?;
+    let db = require_db(&app_state).await?;
     let include_replies = query.include_replies.unwrap_or(false);
 
     // `from` is an exclusive cursor: skip the boundary block to avoid duplicates.
@@ -978,6 +990,16 @@ pub async fn social_account_feed_handler(
         }
         None => (None, None),
     };
+    let bounds = resolve_time_range(
+        db.as_ref(),
+        &query.account_id,
+        contract,
+        times,
+        from_block,
+        to_block,
+    )
+    .await?;
+    let (from_block, to_block) = (bounds.from_block, bounds.to_block);
 
     let to_index_entry = |e: KvEntry| IndexEntry {
         account_id: e.predecessor_id,
@@ -1000,6 +1022,9 @@ pub async fn social_account_feed_handler(
         fields: None,
         value_format: None,
         cursor: None,
+        signer_id: None,
+        from_time: None,
+        to_time: None,
     };
 
     let comment_params = HistoryParams {
@@ -1013,13 +1038,18 @@ pub async fn social_account_feed_handler(
         fields: None,
         value_format: None,
         cursor: None,
+        signer_id: None,
+        from_time: None,
+        to_time: None,
     };
 
-    let (all_posts, total_dropped): (Vec<IndexEntry>, usize) = if include_replies {
-        let ((entries, _hm1, dropped1, _), (comment_entries, _hm2, dropped2, _)) =
+    let (all_posts, total_dropped): (Vec<IndexEntry>, usize) = if bounds.is_empty() {
+        (Vec::new(), 0)
+    } else if include_replies {
+        let ((entries, _hm1, _, dropped1, _), (comment_entries, _hm2, _, dropped2, _)) =
             futures::future::try_join(
-                scylladb.get_kv_history(&history_params),
-                scylladb.get_kv_history(&comment_params),
+                db.get_kv_history(&history_params),
+                db.get_kv_history(&comment_params),
             )
             .await?;
         let dropped = dropped1 + dropped2;
@@ -1036,14 +1066,14 @@ pub async fn social_account_feed_handler(
         if order == "asc" {
             combined.sort_by_key(|e| e.block_height);
         } else {
-            combined.sort_by(|a, b| b.block_height.cmp(&a.block_height));
+            combined.sort_by_key(|e| std::cmp::Reverse(e.block_height));
         }
 
         combined.truncate(fetch_limit);
         (combined, dropped)
     } else {
-        let (entries, _has_more, dropped, _) =
-            scylladb.get_kv_history(&history_params).await?;
+        let (entries, _has_more, _, dropped, _) =
+            db.get_kv_history(&history_params).await?;
         if dropped > 0 {
             tracing::warn!(target: PROJECT_ID, dropped, "Dropped rows in social feed");
         }
@@ -1059,6 +1089,7 @@ pub async fn social_account_feed_handler(
         truncated: false,
         next_cursor,
         dropped_rows: dropped_to_option(total_dropped),
+        resolved_range: bounds.resolved,
     };
 
     Ok(HttpResponse::Ok().json(PaginatedResponse {
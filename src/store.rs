@@ -0,0 +1,232 @@
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+
+use crate::models::{
+    AccountsParams, ContractTimelineParams, EdgeSourceEntry, HistoryParams, KvEntry, KvRow,
+    PrefixHistoryParams, QueryParams, TimelineParams, WritersParams,
+};
+
+/// Owned row stream returned by scan-style store methods. Items are `Err`
+/// when a single row fails to decode; callers skip and count those.
+pub type KvRowStream = BoxStream<'static, anyhow::Result<KvRow>>;
+
+/// A query needs a table or view this backend does not have. Returned inside
+/// `anyhow::Error`; handlers answer it with 501 for that endpoint alone.
+#[derive(Debug)]
+pub struct Unsupported(pub &'static str);
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} is not available on this backend", self.0)
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+/// Read surface used by the HTTP handlers.
+///
+/// `ScyllaDb` is the production implementation. Every method mirrors one
+/// query shape of the underlying tables, so alternative backends only need
+/// to reproduce the same ordering and cursor semantics.
+#[async_trait]
+pub trait KvStore: Send + Sync {
+    async fn health_check(&self) -> anyhow::Result<()>;
+
+    /// Latest value for one key (`s_kv_last`).
+    async fn get_kv(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<Option<KvEntry>>;
+
+    /// Latest raw value only, for batch lookups.
+    async fn get_kv_last(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<Option<String>>;
+
+    /// Returns (entries, has_more, truncated, dropped_rows, next_cursor).
+    /// `truncated` means a `where=` scan hit `MAX_FILTER_SCAN`; `next_cursor`
+    /// is then the last writer scanned rather than the last one returned.
+    async fn query_writers(
+        &self,
+        params: &WritersParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)>;
+
+    /// Returns (accounts, has_more, dropped_rows).
+    async fn query_accounts(
+        &self,
+        params: &AccountsParams,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)>;
+
+    /// Returns (accounts, has_more, truncated, dropped_rows).
+    async fn query_accounts_by_contract(
+        &self,
+        contract_id: &str,
+        key: Option<&str>,
+        limit: usize,
+        offset: usize,
+        after_account: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, bool, usize)>;
+
+    /// Returns (accounts, has_more, dropped_rows).
+    async fn query_all_accounts(
+        &self,
+        limit: usize,
+        after_account: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)>;
+
+    /// Returns (contracts, has_more, dropped_rows).
+    async fn query_all_contracts(
+        &self,
+        limit: usize,
+        after_contract: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)>;
+
+    /// Returns (contracts, has_more, dropped_rows).
+    async fn query_contracts_by_account(
+        &self,
+        account_id: &str,
+        limit: usize,
+        after_contract: Option<&str>,
+    ) -> anyhow::Result<(Vec<String>, bool, usize)>;
+
+    /// Returns (entries, has_more, truncated, dropped_rows, next_cursor). Reads
+    /// `s_kv` instead of `s_kv_last` when `params.at_block` is set. `truncated`
+    /// and `next_cursor` follow the same `where=` rules as `query_writers`.
+    /// Keys are returned in `params.order`; `next_cursor` resumes via
+    /// `after_key` (asc) or `before_key` (desc).
+    async fn query_kv_with_pagination(
+        &self,
+        params: &QueryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)>;
+
+    /// Newest write at or before `block_height` (`s_kv`), i.e. the value as of that block.
+    async fn get_kv_at_block(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key: &str,
+        block_height: i64,
+    ) -> anyhow::Result<Option<KvEntry>>;
+
+    /// Returns (entries, has_more, dropped_rows, next_cursor).
+    async fn get_kv_timeline(
+        &self,
+        params: &TimelineParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)>;
+
+    /// Every write under `key_prefix` in block order (`s_kv_by_block`).
+    /// Returns (entries, has_more, truncated, dropped_rows, next_cursor).
+    /// `truncated` means the prefix scan hit `MAX_FILTER_SCAN`; `next_cursor`
+    /// is then the last write scanned rather than the last one returned.
+    async fn get_kv_prefix_history(
+        &self,
+        params: &PrefixHistoryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)>;
+
+    /// Every write to a contract across all writers, in block order
+    /// (`s_kv_by_contract`). Returns (entries, has_more, dropped_rows, next_cursor);
+    /// `Unsupported` without the table.
+    async fn get_contract_timeline(
+        &self,
+        params: &ContractTimelineParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize, Option<String>)>;
+
+    /// Returns (entries, has_more, dropped_rows).
+    async fn query_edges(
+        &self,
+        edge_type: &str,
+        target: &str,
+        limit: usize,
+        offset: usize,
+        after_source: Option<&str>,
+    ) -> anyhow::Result<(Vec<EdgeSourceEntry>, bool, usize)>;
+
+    async fn count_edges(&self, edge_type: &str, target: &str) -> anyhow::Result<usize>;
+
+    /// Returns (count, approximate): keys under `key_prefix` in one writer's
+    /// partition. Exact `COUNT(*)` unless `exclude_deleted`, which scans at
+    /// most `MAX_COUNT_SCAN` rows and sets `approximate` when capped.
+    async fn count_keys(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        key_prefix: Option<&str>,
+        exclude_deleted: bool,
+    ) -> anyhow::Result<(usize, bool)>;
+
+    /// Returns (count, approximate): distinct writers of a key, with the same
+    /// `COUNT(*)` / capped-scan split as `count_keys`.
+    async fn count_writers(
+        &self,
+        current_account_id: &str,
+        key: &str,
+        exclude_deleted: bool,
+    ) -> anyhow::Result<(usize, bool)>;
+
+    /// Returns (entries, has_more, truncated, dropped_rows, next_cursor).
+    /// `truncated` means a `signer_id` scan hit `MAX_FILTER_SCAN`; `next_cursor`
+    /// is then the last write scanned rather than the last one returned.
+    async fn get_kv_history(
+        &self,
+        params: &HistoryParams,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, bool, usize, Option<String>)>;
+
+    /// Every write produced by a transaction (`mv_kv_by_tx`), in execution order.
+    /// Returns (entries, has_more, dropped_rows); `Unsupported` without the view.
+    async fn query_kv_by_tx(
+        &self,
+        tx_hash: &str,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)>;
+
+    /// Every write produced by a receipt (`mv_kv_by_receipt`), in execution order.
+    /// Returns (entries, has_more, dropped_rows); `Unsupported` without the view.
+    async fn query_kv_by_receipt(
+        &self,
+        receipt_id: &str,
+        limit: usize,
+        offset: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)>;
+
+    /// Every write stored for a block (`mv_kv_by_block`), in execution order.
+    /// Returns (entries, has_more, dropped_rows); `Unsupported` without the view.
+    async fn query_kv_by_block(
+        &self,
+        block_height: i64,
+        limit: usize,
+    ) -> anyhow::Result<(Vec<KvEntry>, bool, usize)>;
+
+    /// Lowest block in the writer's `s_kv_by_block` partition whose
+    /// `block_timestamp` is at or after `timestamp_ns`.
+    async fn first_block_at_or_after(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>>;
+
+    /// Highest block in the writer's `s_kv_by_block` partition whose
+    /// `block_timestamp` is at or before `timestamp_ns`.
+    async fn last_block_at_or_before(
+        &self,
+        predecessor_id: &str,
+        current_account_id: &str,
+        timestamp_ns: u64,
+    ) -> anyhow::Result<Option<u64>>;
+
+    async fn get_indexer_block_height(&self) -> anyhow::Result<Option<u64>>;
+
+    /// All current writers of `(contract, key)`, newest block first
+    /// (`mv_kv_cur_key` ordering). Used by the social index endpoints.
+    async fn stream_reverse_kv(
+        &self,
+        current_account_id: &str,
+        key: &str,
+    ) -> anyhow::Result<KvRowStream>;
+}
@@ -0,0 +1,91 @@
+//! Fixtures shared by the handler and store tests.
+
+use crate::memory_store::MemoryStore;
+use crate::models::KvHistoryRow;
+use crate::store::KvStore;
+use crate::AppState;
+use std::sync::Arc;
+
+/// State serving `store`, with every other field at its default.
+pub(crate) fn test_app_state(store: Arc<dyn KvStore>) -> AppState {
+    AppState {
+        store: Arc::new(tokio::sync::RwLock::new(Some(store))),
+        chain_id: fastnear_primitives::types::ChainId::Mainnet,
+        scan_throttle: Default::default(),
+        watch_count: Default::default(),
+        watch_hub: Default::default(),
+        webhooks: Default::default(),
+        indexer_head: Default::default(),
+    }
+}
+
+/// A `social.near` write whose timestamp is `block_height * 1000`.
+pub(crate) fn write(
+    pred: &str,
+    key: &str,
+    value: &str,
+    block_height: i64,
+    order_id: i64,
+) -> KvHistoryRow {
+    KvHistoryRow {
+        predecessor_id: pred.to_string(),
+        current_account_id: "social.near".to_string(),
+        key: key.to_string(),
+        block_height,
+        order_id,
+        value: value.to_string(),
+        block_timestamp: block_height * 1_000,
+        receipt_id: format!("r{block_height}"),
+        tx_hash: format!("t{block_height}"),
+        signer_id: pred.to_string(),
+        shard_id: 0,
+        receipt_index: 0,
+        action_index: 0,
+    }
+}
+
+/// Alice's profile and two follows of bob.near, one later deleted.
+pub(crate) fn sample_store() -> MemoryStore {
+    let store = MemoryStore::default();
+    store.insert(write("alice.near", "profile/name", "\"Alice\"", 100, 0));
+    store.insert(write("alice.near", "profile/name", "\"Alicia\"", 120, 0));
+    store.insert(write("alice.near", "profile/bio", "\"hi\"", 110, 0));
+    store.insert(write("alice.near", "graph/follow/bob.near", "\"\"", 105, 0));
+    store.insert(write("carol.near", "graph/follow/bob.near", "\"\"", 106, 0));
+    store.insert(write("carol.near", "graph/follow/bob.near", "null", 130, 0));
+    store
+}
+
+/// Reads `n` SSE `change` events as `(id, accountId/key)` pairs.
+pub(crate) async fn read_watch_events<B: actix_web::body::MessageBody>(
+    body: &mut std::pin::Pin<Box<B>>,
+    n: usize,
+) -> Vec<(String, String)> {
+    let mut events = Vec::new();
+    while events.len() < n {
+        let chunk = next_sse_chunk(body).await;
+        let text = std::str::from_utf8(&chunk).unwrap();
+        let id = text.lines().find_map(|l| l.strip_prefix("id: "));
+        let data = text.lines().find_map(|l| l.strip_prefix("data: "));
+        if let (Some(id), Some(data)) = (id, data) {
+            let event: serde_json::Value = serde_json::from_str(data).unwrap();
+            let key = format!("{}/{}", event["accountId"], event["key"]).replace('"', "");
+            events.push((id.to_string(), key));
+        }
+    }
+    events
+}
+
+pub(crate) async fn next_sse_chunk<B: actix_web::body::MessageBody>(
+    body: &mut std::pin::Pin<Box<B>>,
+) -> actix_web::web::Bytes {
+    tokio::time::timeout(
+        std::time::Duration::from_secs(5),
+        futures::future::poll_fn(|cx| body.as_mut().poll_next(cx)),
+    )
+    .await
+    .expect("watch event")
+    .unwrap()
+    .ok()
+    .unwrap()
+}
@@ -38,6 +38,49 @@ fn insert_nested(
     }
 }
 
+/// RFC 6902 operations that turn tree `a` into tree `b`. Objects are diffed
+/// per member; anything else that differs becomes a `replace`. New subtrees
+/// are added whole, so every `add` targets an existing parent.
+pub fn json_patch(a: &serde_json::Value, b: &serde_json::Value) -> Vec<serde_json::Value> {
+    let mut ops = Vec::new();
+    diff_into(&mut ops, String::new(), a, b);
+    ops
+}
+
+fn diff_into(
+    ops: &mut Vec<serde_json::Value>,
+    path: String,
+    a: &serde_json::Value,
+    b: &serde_json::Value,
+) {
+    match (a, b) {
+        (serde_json::Value::Object(a_map), serde_json::Value::Object(b_map)) => {
+            for (key, a_val) in a_map {
+                let child = format!("{path}/{}", escape_pointer(key));
+                match b_map.get(key) {
+                    Some(b_val) => diff_into(ops, child, a_val, b_val),
+                    None => ops.push(serde_json::json!({ "op": "remove", "path": child })),
+                }
+            }
+            for (key, b_val) in b_map {
+                if !a_map.contains_key(key) {
+                    let child = format!("{path}/{}", escape_pointer(key));
+                    ops.push(serde_json::json!({ "op": "add", "path": child, "value": b_val }));
+                }
+            }
+        }
+        _ if a != b => {
+            ops.push(serde_json::json!({ "op": "replace", "path": path, "value": b }));
+        }
+        _ => {}
+    }
+}
+
+/// JSON Pointer (RFC 6901) reference-token escaping.
+fn escape_pointer(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +146,27 @@ mod tests {
         // "a/b" was set first as a leaf, so "a/b/c" can't nest under it
         assert_eq!(tree["a"]["b"], "leaf");
     }
+
+    #[test]
+    fn test_json_patch_ops() {
+        let a = serde_json::json!({ "profile": { "name": "Alice", "bio": "hi", "tags": { "x": "" } } });
+        let b = serde_json::json!({ "profile": { "name": "Alicia", "image": { "url": "u" }, "tags": { "x": "" } } });
+        let ops = json_patch(&a, &b);
+        assert_eq!(
+            ops,
+            vec![
+                serde_json::json!({ "op": "remove", "path": "/profile/bio" }),
+                serde_json::json!({ "op": "replace", "path": "/profile/name", "value": "Alicia" }),
+                serde_json::json!({ "op": "add", "path": "/profile/image", "value": { "url": "u" } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_patch_identical_and_escaping() {
+        let a = serde_json::json!({ "a~b": 1 });
+        assert!(json_patch(&a, &a).is_empty());
+        let ops = json_patch(&a, &serde_json::json!({ "a~b": 2 }));
+        assert_eq!(ops[0]["path"], "/a~0b");
+    }
 }
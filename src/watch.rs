@@ -0,0 +1,807 @@
+//! Watch subscriptions: what an SSE watch follows and how it polls the store.
+//!
+//! A write's position is `(block_height, order_id)`, which is also its SSE
+//! event id.  `WatchHub` polls each distinct topic once per round, however
+//! many connections follow it, and fans the writes out over a broadcast
+//! channel.  A connection reads the store itself only to catch up: from its
+//! `Last-Event-ID`, or after falling behind the channel.
+
+use crate::handlers::{validate_account_id, validate_key, BATCH_CONCURRENCY};
+use crate::models::{
+    parse_history_cursor, ApiError, HistoryParams, KvEntry, PrefixHistoryParams, WatchEvent,
+    WatchFilter, MAX_EDGE_TYPE_LENGTH, MAX_KEY_LENGTH, MAX_POLL_INTERVAL, MAX_PREFIX_LENGTH,
+    MAX_WATCH_EVENTS_PER_POLL, MIN_POLL_INTERVAL, PROJECT_ID, WATCH_POLL_CONCURRENCY,
+};
+use crate::social_handlers::resolve_contract;
+use crate::store::KvStore;
+use actix_web::web::Bytes;
+use futures::stream::{self, StreamExt};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch, Mutex, OnceCell, RwLock};
+
+/// Writes a topic channel buffers before slow connections fall back to catching up.
+const TOPIC_CAPACITY: usize = 2 * MAX_WATCH_EVENTS_PER_POLL;
+
+/// Position of a write in execution order; a watch resumes strictly after it.
+pub(crate) type WatchPosition = (i64, i64);
+
+/// What a subscription follows under one writer and contract.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) enum WatchTarget {
+    /// Every write to one key, from `s_kv` history.
+    Key(String),
+    /// The newest write per key and block under a prefix, from `s_kv_by_block`.
+    Prefix(String),
+    /// Writes to one key by any account, from `mv_kv_cur_key`: edge targets
+    /// (`{edge_type}/{target}`) and social index keys (`index/{action}/{key}`).
+    Writers(String),
+}
+
+/// One deduplicated unit of polling in `WatchHub`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct Topic {
+    /// Empty for `Writers` targets, which span every writer.
+    pub(crate) predecessor_id: String,
+    pub(crate) current_account_id: String,
+    pub(crate) target: WatchTarget,
+}
+
+impl Topic {
+    /// Resolve a client filter to the topic that serves it.  `field` names
+    /// the filter in validation errors.
+    pub(crate) fn from_filter(filter: WatchFilter, field: &str) -> Result<Self, ApiError> {
+        Ok(match filter {
+            WatchFilter::Key {
+                predecessor_id,
+                current_account_id,
+                key,
+            } => {
+                validate_account_id(&predecessor_id, &format!("{field}.accountId"))?;
+                validate_account_id(&current_account_id, &format!("{field}.contractId"))?;
+                validate_key(&key, &format!("{field}.key"), MAX_KEY_LENGTH)?;
+                Topic {
+                    predecessor_id,
+                    current_account_id,
+                    target: WatchTarget::Key(key),
+                }
+            }
+            WatchFilter::Prefix {
+                predecessor_id,
+                current_account_id,
+                key_prefix,
+            } => {
+                validate_account_id(&predecessor_id, &format!("{field}.accountId"))?;
+                validate_account_id(&current_account_id, &format!("{field}.contractId"))?;
+                validate_key(
+                    &key_prefix,
+                    &format!("{field}.key_prefix"),
+                    MAX_PREFIX_LENGTH,
+                )?;
+                Topic {
+                    predecessor_id,
+                    current_account_id,
+                    target: WatchTarget::Prefix(key_prefix),
+                }
+            }
+            WatchFilter::Edge {
+                edge_type,
+                target,
+                contract_id,
+            } => {
+                validate_key(
+                    &edge_type,
+                    &format!("{field}.edge_type"),
+                    MAX_EDGE_TYPE_LENGTH,
+                )?;
+                validate_account_id(&target, &format!("{field}.target"))?;
+                Topic {
+                    predecessor_id: String::new(),
+                    current_account_id: resolve_contract(&contract_id)?.to_string(),
+                    target: WatchTarget::Writers(format!("{edge_type}/{target}")),
+                }
+            }
+            WatchFilter::SocialIndex {
+                action,
+                key,
+                contract_id,
+            } => {
+                validate_key(&action, &format!("{field}.action"), MAX_KEY_LENGTH)?;
+                validate_key(&key, &format!("{field}.key"), MAX_KEY_LENGTH)?;
+                Topic {
+                    predecessor_id: String::new(),
+                    current_account_id: resolve_contract(&contract_id)?.to_string(),
+                    target: WatchTarget::Writers(format!("index/{action}/{key}")),
+                }
+            }
+        })
+    }
+}
+
+pub(crate) struct Subscription {
+    topic: Topic,
+    /// Last position already delivered; `None` follows from the first write.
+    after: Option<WatchPosition>,
+    /// Prefix targets page `s_kv_by_block` with its `block_height:key`
+    /// cursor; without one they re-read from `after`'s block.
+    prefix_cursor: Option<String>,
+    /// Writers rows carry no `order_id`, so every writer in a block shares
+    /// `(block_height, 0)`: the accounts already delivered at `after`.
+    block_writers: HashSet<String>,
+}
+
+impl Subscription {
+    pub(crate) fn new(topic: Topic, after: Option<WatchPosition>) -> Self {
+        Self {
+            topic,
+            after,
+            prefix_cursor: None,
+            block_writers: HashSet::new(),
+        }
+    }
+
+    /// Where a fresh subscription begins: a key opens with its current value,
+    /// a prefix or writer set follows writes after the indexer head.
+    pub(crate) async fn start(&mut self, db: &dyn KvStore) -> anyhow::Result<Vec<KvEntry>> {
+        match &self.topic.target {
+            WatchTarget::Key(key) => {
+                let mut newest = self.history_params(key);
+                newest.limit = 1;
+                newest.order = "desc".to_string();
+                let (entries, _, _, _, _) = db.get_kv_history(&newest).await?;
+                self.after = entries.first().map(watch_position);
+                Ok(entries)
+            }
+            WatchTarget::Prefix(_) | WatchTarget::Writers(_) => {
+                let head = db.get_indexer_block_height().await?.unwrap_or(0);
+                self.after = Some((head as i64, i64::MAX));
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    /// Writes since the last poll, oldest first, and whether the store holds
+    /// more: a full page, or a scan cut short by its budget.
+    pub(crate) async fn poll(&mut self, db: &dyn KvStore) -> anyhow::Result<(Vec<KvEntry>, bool)> {
+        match &self.topic.target {
+            WatchTarget::Key(key) => {
+                let mut params = self.history_params(key);
+                params.cursor = self.after.map(|(bh, oid)| format!("{bh}:{oid}"));
+                let (entries, has_more, truncated, _, _) = db.get_kv_history(&params).await?;
+                if let Some(last) = entries.last() {
+                    self.after = Some(watch_position(last));
+                }
+                Ok((entries, has_more || truncated))
+            }
+            WatchTarget::Prefix(key_prefix) => {
+                let params = PrefixHistoryParams {
+                    predecessor_id: self.topic.predecessor_id.clone(),
+                    current_account_id: self.topic.current_account_id.clone(),
+                    key_prefix: key_prefix.clone(),
+                    limit: MAX_WATCH_EVENTS_PER_POLL,
+                    order: "asc".to_string(),
+                    from_block: self.after.map(|(bh, _)| bh.max(0)),
+                    to_block: None,
+                    fields: None,
+                    value_format: None,
+                    cursor: self.prefix_cursor.clone(),
+                };
+                let (entries, has_more, truncated, _, next_cursor) =
+                    db.get_kv_prefix_history(&params).await?;
+                // Rows within a block are key-ordered, so `after` only trims
+                // the first read; from then on the cursor tracks progress.
+                let after = self.after.filter(|_| self.prefix_cursor.is_none());
+                if next_cursor.is_some() {
+                    self.prefix_cursor = next_cursor;
+                }
+                let entries: Vec<KvEntry> = entries
+                    .into_iter()
+                    .filter(|e| after.is_none_or(|a| watch_position(e) > a))
+                    .collect();
+                if let Some(last) = entries.iter().map(watch_position).max() {
+                    self.after = self.after.max(Some(last));
+                }
+                Ok((entries, has_more || truncated))
+            }
+            WatchTarget::Writers(key) => {
+                // Newest block first, one row per writer: read down to the
+                // last block seen, which may have gained writers since.
+                // Past the limit the newest are dropped, so the oldest go
+                // out first and the next poll resumes after them.
+                let after_block = self.after.map_or(-1, |(bh, _)| bh);
+                let mut rows = db
+                    .stream_reverse_kv(&self.topic.current_account_id, key)
+                    .await?;
+                let mut newest_first = VecDeque::new();
+                let mut more = false;
+                while let Some(row) = rows.next().await {
+                    let row = row?;
+                    if row.block_height < after_block {
+                        break;
+                    }
+                    let entry = KvEntry::from(row);
+                    if !self.is_new(&entry) {
+                        continue;
+                    }
+                    if newest_first.len() == MAX_WATCH_EVENTS_PER_POLL {
+                        newest_first.pop_front();
+                        more = true;
+                    }
+                    newest_first.push_back(entry);
+                }
+                let entries: Vec<KvEntry> = newest_first.into_iter().rev().collect();
+                for entry in &entries {
+                    self.advance(entry);
+                }
+                Ok((entries, more))
+            }
+        }
+    }
+
+    /// Last position delivered, if any.
+    pub(crate) fn position(&self) -> Option<WatchPosition> {
+        self.after
+    }
+
+    /// Whether `entry` comes after everything delivered so far.
+    fn is_new(&self, entry: &KvEntry) -> bool {
+        let position = watch_position(entry);
+        self.after.is_none_or(|after| {
+            position > after
+                || (position == after
+                    && matches!(self.topic.target, WatchTarget::Writers(_))
+                    && !self.block_writers.contains(&entry.predecessor_id))
+        })
+    }
+
+    /// Record a delivered write.
+    fn advance(&mut self, entry: &KvEntry) {
+        let position = watch_position(entry);
+        if self.after != Some(position) {
+            self.block_writers.clear();
+        }
+        if matches!(self.topic.target, WatchTarget::Writers(_)) {
+            self.block_writers.insert(entry.predecessor_id.clone());
+        }
+        self.after = Some(position);
+        self.prefix_cursor = None;
+    }
+
+    fn history_params(&self, key: &str) -> HistoryParams {
+        HistoryParams {
+            predecessor_id: self.topic.predecessor_id.clone(),
+            current_account_id: self.topic.current_account_id.clone(),
+            key: key.to_string(),
+            limit: MAX_WATCH_EVENTS_PER_POLL,
+            order: "asc".to_string(),
+            from_block: None,
+            to_block: None,
+            fields: None,
+            value_format: None,
+            cursor: None,
+            signer_id: None,
+            from_time: None,
+            to_time: None,
+        }
+    }
+}
+
+/// Start fresh subscriptions, merging their opening values.
+pub(crate) async fn start_all(
+    subscriptions: &mut [Subscription],
+    db: &dyn KvStore,
+) -> anyhow::Result<Vec<KvEntry>> {
+    let batches: Vec<Vec<KvEntry>> = stream::iter(subscriptions.iter_mut())
+        .map(|s| s.start(db))
+        .buffered(BATCH_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<anyhow::Result<_>>()?;
+    Ok(merge_batches(batches))
+}
+
+/// A connection's view of one topic: its own position, plus the topic
+/// channel once it has caught up with the store.
+pub(crate) struct Feed {
+    subscription: Subscription,
+    rx: broadcast::Receiver<Arc<KvEntry>>,
+    live: bool,
+}
+
+impl Feed {
+    pub(crate) fn is_live(&self) -> bool {
+        self.live
+    }
+
+    pub(crate) fn position(&self) -> Option<WatchPosition> {
+        self.subscription.after
+    }
+
+    /// Writes to deliver now: store reads until one reports nothing more,
+    /// then the channel.
+    async fn next_batch(&mut self, db: &dyn KvStore) -> anyhow::Result<Vec<KvEntry>> {
+        if !self.live {
+            // Anything broadcast so far is also in the store read below
+            while !matches!(
+                self.rx.try_recv(),
+                Err(broadcast::error::TryRecvError::Empty | broadcast::error::TryRecvError::Closed)
+            ) {}
+            let (entries, more) = self.subscription.poll(db).await?;
+            self.live = !more;
+            return Ok(entries);
+        }
+        let mut entries = Vec::new();
+        loop {
+            match self.rx.try_recv() {
+                Ok(entry) => {
+                    if self.subscription.is_new(&entry) {
+                        self.subscription.advance(&entry);
+                        entries.push(KvEntry::clone(&entry));
+                    }
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => {
+                    self.live = false;
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Drain every feed once, tagging each write with its feed's index.  A failed
+/// feed keeps its position and is retried next round; the flag reports that
+/// at least one failed.
+pub(crate) async fn drain_all(
+    feeds: &mut [Feed],
+    db: &dyn KvStore,
+) -> (Vec<(usize, KvEntry)>, bool) {
+    // Futures built up front, as in `poll_round`, so spawned tasks stay `Send`
+    let batches: Vec<_> = feeds.iter_mut().map(|f| f.next_batch(db)).collect();
+    let results: Vec<_> = stream::iter(batches)
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await;
+    let mut failed = false;
+    let mut entries = Vec::new();
+    for (index, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(batch) => entries.extend(batch.into_iter().map(|entry| (index, entry))),
+            Err(e) => {
+                tracing::warn!(target: PROJECT_ID, error = %e, "Watch poll error");
+                failed = true;
+            }
+        }
+    }
+    entries.sort_by_key(|(_, entry)| watch_position(entry));
+    (entries, failed)
+}
+
+/// One topic's poller and channel.  The poller has its own lock, so a slow
+/// read holds up only this topic, never the registry.
+struct TopicState {
+    subscription: Mutex<Subscription>,
+    /// Set once the poller has started at the head; rounds skip it until then.
+    started: OnceCell<()>,
+    tx: broadcast::Sender<Arc<KvEntry>>,
+}
+
+impl TopicState {
+    fn new(topic: Topic) -> Self {
+        Self {
+            subscription: Mutex::new(Subscription::new(topic, None)),
+            started: OnceCell::new(),
+            tx: broadcast::channel(TOPIC_CAPACITY).0,
+        }
+    }
+
+    async fn start(&self, db: &dyn KvStore) -> anyhow::Result<()> {
+        self.started
+            .get_or_try_init(|| async {
+                self.subscription.lock().await.start(db).await?;
+                anyhow::Ok(())
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn poll(&self, db: &dyn KvStore) -> anyhow::Result<()> {
+        if !self.started.initialized() {
+            return Ok(());
+        }
+        let (entries, _) = self.subscription.lock().await.poll(db).await?;
+        for entry in entries {
+            // Receivers may drop mid-round; they are pruned next round
+            let _ = self.tx.send(Arc::new(entry));
+        }
+        Ok(())
+    }
+}
+
+/// Registry of watched topics, polled once per round for every connection.
+pub struct WatchHub {
+    /// Held only to look topics up, never across a store read.
+    topics: Mutex<HashMap<Topic, Arc<TopicState>>>,
+    /// Completed poll rounds; connections drain their feeds after each.
+    rounds: watch::Sender<u64>,
+}
+
+impl Default for WatchHub {
+    fn default() -> Self {
+        Self {
+            topics: Mutex::default(),
+            rounds: watch::channel(0).0,
+        }
+    }
+}
+
+impl WatchHub {
+    /// Join `subscription`'s topic, creating it at the current head if no
+    /// connection follows it yet.
+    pub(crate) async fn subscribe(
+        &self,
+        subscription: Subscription,
+        db: &dyn KvStore,
+    ) -> anyhow::Result<Feed> {
+        let state = self
+            .topics
+            .lock()
+            .await
+            .entry(subscription.topic.clone())
+            .or_insert_with(|| Arc::new(TopicState::new(subscription.topic.clone())))
+            .clone();
+        // Subscribed before the poller starts, so its first round can't be missed.
+        // A failed start leaves no receiver, and the topic is pruned next round.
+        let rx = state.tx.subscribe();
+        state.start(db).await?;
+        Ok(Feed {
+            subscription,
+            rx,
+            live: false,
+        })
+    }
+
+    pub(crate) fn rounds(&self) -> watch::Receiver<u64> {
+        self.rounds.subscribe()
+    }
+
+    /// Poll every followed topic once and broadcast its new writes.
+    /// Returns the number of topics polled.
+    pub(crate) async fn poll_round(&self, db: &dyn KvStore) -> usize {
+        let topics: Vec<Arc<TopicState>> = {
+            let mut topics = self.topics.lock().await;
+            topics.retain(|_, state| state.tx.receiver_count() > 0);
+            topics.values().cloned().collect()
+        };
+        // Build the futures up front: a lazy `map` over borrowed states is
+        // not provably `Send` for the spawned hub task.
+        let polls: Vec<_> = topics.iter().map(|state| state.poll(db)).collect();
+        let results: Vec<_> = stream::iter(polls)
+            .buffer_unordered(WATCH_POLL_CONCURRENCY)
+            .collect()
+            .await;
+        for e in results.into_iter().filter_map(Result::err) {
+            tracing::warn!(target: PROJECT_ID, error = %e, "Watch topic poll error");
+        }
+        self.rounds.send_modify(|round| *round += 1);
+        topics.len()
+    }
+
+    /// Run a poll round whenever the cached indexer head advances, and at
+    /// least every `MAX_POLL_INTERVAL` seconds.
+    pub async fn run(
+        self: Arc<Self>,
+        store: Arc<RwLock<Option<Arc<dyn KvStore>>>>,
+        indexer_block: Arc<AtomicU64>,
+    ) {
+        let mut tick = tokio::time::interval(Duration::from_secs(MIN_POLL_INTERVAL));
+        let mut last_head = 0;
+        let mut last_round = Instant::now();
+        loop {
+            tick.tick().await;
+            let head = indexer_block.load(Ordering::Acquire);
+            if head == last_head && last_round.elapsed() < Duration::from_secs(MAX_POLL_INTERVAL) {
+                continue;
+            }
+            last_head = head;
+            last_round = Instant::now();
+            // Clone the Arc so the RwLock is not held across the round
+            let db = store.read().await.clone();
+            if let Some(db) = db {
+                let topics = self.poll_round(db.as_ref()).await;
+                tracing::debug!(target: PROJECT_ID, head, topics, "Watch poll round");
+            }
+        }
+    }
+}
+
+fn merge_batches(batches: Vec<Vec<KvEntry>>) -> Vec<KvEntry> {
+    let mut entries: Vec<KvEntry> = batches.into_iter().flatten().collect();
+    entries.sort_by_key(watch_position);
+    entries
+}
+
+fn watch_position(entry: &KvEntry) -> WatchPosition {
+    (
+        entry.block_height as i64,
+        entry.order_id.unwrap_or_default(),
+    )
+}
+
+/// Parse a `Last-Event-ID`.  Ids used to be bare block heights; those resume
+/// after that whole block.
+pub(crate) fn parse_last_event_id(id: &str) -> Option<WatchPosition> {
+    if let Ok(position) = parse_history_cursor(id) {
+        return Some(position);
+    }
+    id.parse::<i64>()
+        .ok()
+        .filter(|bh| *bh >= 0)
+        .map(|bh| (bh, i64::MAX))
+}
+
+/// SSE event id of a history write: its `s_kv` cursor, `block_height:order_id`.
+pub(crate) fn watch_event_id(entry: &KvEntry) -> String {
+    let (block_height, order_id) = watch_position(entry);
+    format!("{block_height}:{order_id}")
+}
+
+/// One `change` frame; `None` only if the event fails to serialize.
+pub(crate) fn watch_change_frame(entry: KvEntry) -> Option<Bytes> {
+    let id = watch_event_id(&entry);
+    let data = serde_json::to_string(&WatchEvent::from(entry)).ok()?;
+    Some(Bytes::from(format!(
+        "id: {id}\nevent: change\ndata: {data}\n\n"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_store::MemoryStore;
+    use crate::models::MAX_FILTER_SCAN;
+    use crate::test_support::{
+        next_sse_chunk, read_watch_events, sample_store, test_app_state, write,
+    };
+    use actix_web::{test as actix_test, web, App};
+
+    #[actix_web::test]
+    async fn test_watch_hub_polls_shared_key_once() {
+        let memory = Arc::new(sample_store());
+        let store: Arc<dyn KvStore> = memory.clone();
+        let state = test_app_state(store.clone());
+        let hub = state.watch_hub.clone();
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(crate::handlers::watch_kv_handler),
+        )
+        .await;
+
+        let mut bodies = Vec::new();
+        for _ in 0..2 {
+            let req = actix_test::TestRequest::get()
+                .uri("/v1/kv/watch?accountId=alice.near&contractId=social.near&key=profile/name")
+                .to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            let mut body = Box::pin(resp.into_body());
+            assert_eq!(read_watch_events(&mut body, 1).await[0].0, "120:0");
+            // The first heartbeat follows the catch-up read
+            while !next_sse_chunk(&mut body).await.starts_with(b": heartbeat") {}
+            bodies.push(body);
+        }
+
+        memory.insert(write("alice.near", "profile/name", "\"Al\"", 140, 0));
+        assert_eq!(hub.poll_round(store.as_ref()).await, 1);
+        for body in &mut bodies {
+            assert_eq!(read_watch_events(body, 1).await[0].0, "140:0");
+        }
+
+        // Topics without connections are dropped on the next round
+        drop(bodies);
+        assert_eq!(hub.poll_round(store.as_ref()).await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_writers_feed_keeps_writers_sharing_a_block() {
+        let store = sample_store();
+        let hub = WatchHub::default();
+        let key = "graph/follow/bob.near";
+        let topic = Topic {
+            predecessor_id: String::new(),
+            current_account_id: "social.near".to_string(),
+            target: WatchTarget::Writers(key.to_string()),
+        };
+        let follow = |account: &str, order_id| {
+            store.insert(write(account, key, "\"\"", 140, order_id));
+        };
+        let writers = |entries: Vec<(usize, KvEntry)>| -> Vec<String> {
+            entries.into_iter().map(|(_, e)| e.predecessor_id).collect()
+        };
+
+        let subscription = Subscription::new(topic.clone(), Some((130, i64::MAX)));
+        let mut live = vec![hub.subscribe(subscription, &store).await.unwrap()];
+        assert!(drain_all(&mut live, &store).await.0.is_empty());
+
+        follow("dave.near", 0);
+        follow("erin.near", 1);
+        hub.poll_round(&store).await;
+        assert_eq!(
+            writers(drain_all(&mut live, &store).await.0),
+            ["dave.near", "erin.near"]
+        );
+
+        // A writer indexed later in a block already delivered
+        follow("frank.near", 2);
+        hub.poll_round(&store).await;
+        assert_eq!(
+            writers(drain_all(&mut live, &store).await.0),
+            ["frank.near"]
+        );
+
+        // Catching up from the store sees the same writers
+        let subscription = Subscription::new(topic, Some((130, i64::MAX)));
+        let mut behind = vec![hub.subscribe(subscription, &store).await.unwrap()];
+        assert_eq!(
+            writers(drain_all(&mut behind, &store).await.0),
+            ["dave.near", "erin.near", "frank.near"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_writers_poll_delivers_every_writer_past_the_limit() {
+        let store = sample_store();
+        let key = "graph/follow/zed.near";
+        let total = 2 * MAX_WATCH_EVENTS_PER_POLL + 500;
+        // Ten writers per block, sharing its position as in `mv_kv_cur_key`
+        for i in 0..total {
+            let account = format!("w{i:05}.near");
+            store.insert(write(&account, key, "\"\"", 200 + (i / 10) as i64, 0));
+        }
+        let topic = Topic {
+            predecessor_id: String::new(),
+            current_account_id: "social.near".to_string(),
+            target: WatchTarget::Writers(key.to_string()),
+        };
+
+        let mut subscription = Subscription::new(topic, Some((199, i64::MAX)));
+        let mut delivered = Vec::new();
+        loop {
+            let (entries, _) = subscription.poll(&store).await.unwrap();
+            if entries.is_empty() {
+                break;
+            }
+            assert!(entries.len() <= MAX_WATCH_EVENTS_PER_POLL);
+            delivered.extend(entries);
+        }
+        let blocks: Vec<u64> = delivered.iter().map(|e| e.block_height).collect();
+        assert!(blocks.is_sorted());
+        let writers: HashSet<&String> = delivered.iter().map(|e| &e.predecessor_id).collect();
+        assert_eq!(delivered.len(), total);
+        assert_eq!(writers.len(), total);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_feed_catches_up_through_a_capped_scan() {
+        let store = sample_store();
+        // More non-matching rows than one prefix scan reads
+        for i in 0..MAX_FILTER_SCAN as i64 + 5 {
+            store.insert(write("alice.near", "widget/app", "\"\"", 200 + i, 0));
+        }
+        store.insert(write("alice.near", "profile/name", "\"Al\"", 20_000, 0));
+        let topic = Topic {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            target: WatchTarget::Prefix("profile/".to_string()),
+        };
+
+        let hub = WatchHub::default();
+        let subscription = Subscription::new(topic, Some((130, i64::MAX)));
+        let mut feeds = vec![hub.subscribe(subscription, &store).await.unwrap()];
+        let mut delivered = Vec::new();
+        while !feeds[0].is_live() {
+            delivered.extend(drain_all(&mut feeds, &store).await.0);
+        }
+        let ids: Vec<String> = delivered.iter().map(|(_, e)| watch_event_id(e)).collect();
+        assert_eq!(ids, ["20000:0"]);
+    }
+
+    /// Resume a feed on `topic` after `after`, with a poll round and a new
+    /// write from `write_at(round)` before each drain, until it has been live
+    /// for a few rounds.  Returns everything the feed delivered.
+    async fn resume_while_writing(
+        store: &MemoryStore,
+        topic: Topic,
+        after: WatchPosition,
+        write_at: impl Fn(i64),
+    ) -> Vec<KvEntry> {
+        let hub = WatchHub::default();
+        let subscription = Subscription::new(topic, Some(after));
+        let mut feeds = vec![hub.subscribe(subscription, store).await.unwrap()];
+        let mut delivered = Vec::new();
+        let mut live_rounds = 0;
+        for round in 0..50 {
+            write_at(round);
+            hub.poll_round(store).await;
+            delivered.extend(drain_all(&mut feeds, store).await.0);
+            if feeds[0].is_live() {
+                live_rounds += 1;
+                if live_rounds == 3 {
+                    return delivered.into_iter().map(|(_, e)| e).collect();
+                }
+            }
+        }
+        panic!("feed never went live");
+    }
+
+    #[tokio::test]
+    async fn test_prefix_feed_resumed_far_behind_loses_nothing() {
+        let store = sample_store();
+        let mut expected = Vec::new();
+        for i in 0..2500 {
+            let key = format!("profile/n{}", i % 7);
+            store.insert(write("alice.near", &key, "\"\"", 200 + i, 0));
+            // Enough other keys that catch-up scans stop at `MAX_FILTER_SCAN`
+            for order_id in 1..=10 {
+                let other = format!("widget/w{order_id}");
+                store.insert(write("alice.near", &other, "\"\"", 200 + i, order_id));
+            }
+            expected.push(format!("{}:0", 200 + i));
+        }
+        let topic = Topic {
+            predecessor_id: "alice.near".to_string(),
+            current_account_id: "social.near".to_string(),
+            target: WatchTarget::Prefix("profile/".to_string()),
+        };
+
+        let delivered = resume_while_writing(&store, topic, (130, i64::MAX), |round| {
+            store.insert(write("alice.near", "profile/live", "\"\"", 5000 + round, 0));
+        })
+        .await;
+        let rounds = delivered.len() - expected.len();
+        expected.extend((0..rounds as i64).map(|round| format!("{}:0", 5000 + round)));
+        let ids: Vec<String> = delivered.iter().map(watch_event_id).collect();
+        assert_eq!(ids, expected);
+        assert!(rounds >= 3);
+    }
+
+    #[tokio::test]
+    async fn test_writers_feed_resumed_far_behind_loses_nothing() {
+        let store = sample_store();
+        let key = "graph/follow/zed.near";
+        let follow = |account: String, block_height| {
+            store.insert(write(&account, key, "\"\"", block_height, 0));
+        };
+        for i in 0..2500 {
+            follow(format!("w{i:05}.near"), 200 + i / 10);
+        }
+        let topic = Topic {
+            predecessor_id: String::new(),
+            current_account_id: "social.near".to_string(),
+            target: WatchTarget::Writers(key.to_string()),
+        };
+
+        // Each round adds a writer, and one late to the previous round's block
+        let delivered = resume_while_writing(&store, topic, (130, i64::MAX), |round| {
+            follow(format!("live{round:02}.near"), 5000 + round);
+            if round > 0 {
+                follow(format!("late{round:02}.near"), 5000 + round - 1);
+            }
+        })
+        .await;
+        let blocks: Vec<u64> = delivered.iter().map(|e| e.block_height).collect();
+        let writers: HashSet<&String> = delivered.iter().map(|e| &e.predecessor_id).collect();
+        let backlog = writers.iter().filter(|w| w.starts_with('w')).count();
+        let live = writers.iter().filter(|w| w.starts_with("live")).count();
+        let late = writers.iter().filter(|w| w.starts_with("late")).count();
+        assert_eq!(writers.len(), delivered.len());
+        assert_eq!(backlog, 2500);
+        assert!(live >= 3);
+        assert_eq!(late, live - 1);
+        assert!(blocks[..2500].is_sorted());
+    }
+}
@@ -0,0 +1,402 @@
+use actix_web::{delete, get, http::header, post, web, HttpRequest, HttpResponse};
+
+use crate::handlers::{extract_client_ip, require_db};
+use crate::models::*;
+use crate::watch::{Subscription, Topic};
+use crate::webhooks::Webhook;
+use crate::AppState;
+
+/// Register a webhook for writes matching a filter. Requires the operator
+/// token in `Authorization: Bearer`.
+///
+/// The URL may not point at a private, loopback or link-local address
+/// unless its host is in `WEBHOOK_ALLOWED_HOSTS`.
+///
+/// Delivery starts after the filter's current head: a key's newest write,
+/// or the indexer block for other filters.  Each POST carries
+/// `X-Webhook-Signature: sha256=<hex>`, the HMAC-SHA256 of
+/// `{X-Webhook-Timestamp}.{body}` under `secret`.
+#[utoipa::path(
+    post,
+    path = "/v1/webhooks",
+    request_body = WebhookRequest,
+    responses(
+        (status = 201, description = "Webhook registered", body = inline(DataResponse<WebhookResponse>)),
+        (status = 400, description = "Invalid URL, filter or secret", body = ErrorResponse),
+        (status = 401, description = "Missing or wrong operator token", body = ErrorResponse),
+        (status = 429, description = "Too many registered webhooks", body = ErrorResponse),
+        (status = 501, description = "Registration disabled: no operator token configured", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "webhooks"
+)]
+#[post("/v1/webhooks")]
+pub async fn create_webhook_handler(
+    req: HttpRequest,
+    body: web::Json<WebhookRequest>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let token = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    app_state.webhooks.authorize(token)?;
+    let WebhookRequest {
+        url,
+        filter,
+        secret,
+    } = body.into_inner();
+    let parsed = validate_webhook_url(&url)?;
+    let secret_len = secret.chars().count();
+    if !(MIN_WEBHOOK_SECRET_LENGTH..=MAX_WEBHOOK_SECRET_LENGTH).contains(&secret_len) {
+        return Err(ApiError::InvalidParameter(format!(
+            "secret: must be {MIN_WEBHOOK_SECRET_LENGTH} to {MAX_WEBHOOK_SECRET_LENGTH} characters"
+        )));
+    }
+    let topic = Topic::from_filter(filter.clone(), "filter")?;
+    app_state.webhooks.check_destination(&parsed).await?;
+
+    let client = extract_client_ip(&req);
+    tracing::info!(target: PROJECT_ID, %url, %client, "POST /v1/webhooks");
+
+    let db = require_db(&app_state).await?;
+    let mut start = Subscription::new(topic.clone(), None);
+    start.start(db.as_ref()).await?;
+
+    let webhook = Webhook {
+        id: uuid::Uuid::new_v4().to_string(),
+        url,
+        filter,
+        secret,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        client,
+        after: start.position(),
+    };
+    let response = WebhookResponse {
+        id: webhook.id.clone(),
+        url: webhook.url.clone(),
+        filter: webhook.filter.clone(),
+        created_at: webhook.created_at.clone(),
+    };
+    app_state
+        .webhooks
+        .register(webhook, topic, &app_state.watch_hub, &app_state.store)?;
+    Ok(HttpResponse::Created().json(DataResponse { data: response }))
+}
+
+/// Delete a webhook. Requires its secret in `X-Webhook-Secret`.
+#[utoipa::path(
+    delete,
+    path = "/v1/webhooks/{id}",
+    params(("id" = String, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Webhook deleted"),
+        (status = 400, description = "Missing X-Webhook-Secret", body = ErrorResponse),
+        (status = 404, description = "Unknown webhook or wrong secret", body = ErrorResponse),
+    ),
+    tag = "webhooks"
+)]
+#[delete("/v1/webhooks/{id}")]
+pub async fn delete_webhook_handler(
+    req: HttpRequest,
+    path: web::Path<String>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let id = path.into_inner();
+    tracing::info!(target: PROJECT_ID, %id, "DELETE /v1/webhooks");
+
+    app_state.webhooks.remove(&id, webhook_secret(&req)?)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Payloads that exhausted their delivery attempts, newest first. Requires
+/// the webhook's secret in `X-Webhook-Secret`.
+#[utoipa::path(
+    get,
+    path = "/v1/webhooks/{id}/dead-letters",
+    params(("id" = String, Path, description = "Webhook id"), DeadLettersParams),
+    responses(
+        (status = 200, description = "Dead letters, newest first", body = inline(PaginatedResponse<DeadLetter>)),
+        (status = 400, description = "Invalid parameters or missing X-Webhook-Secret", body = ErrorResponse),
+        (status = 404, description = "Unknown webhook or wrong secret", body = ErrorResponse),
+    ),
+    tag = "webhooks"
+)]
+#[get("/v1/webhooks/{id}/dead-letters")]
+pub async fn webhook_dead_letters_handler(
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<DeadLettersParams>,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    validate_limit(query.limit)?;
+    let id = path.into_inner();
+    tracing::info!(target: PROJECT_ID, %id, limit = query.limit, "GET /v1/webhooks/dead-letters");
+
+    let (letters, has_more) = app_state
+        .webhooks
+        .dead_letters(&id, webhook_secret(&req)?, query.limit)
+        .await?;
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(PaginatedResponse {
+            data: letters,
+            meta: PaginationMeta {
+                has_more,
+                ..Default::default()
+            },
+        }))
+}
+
+fn validate_webhook_url(url: &str) -> Result<reqwest::Url, ApiError> {
+    if url.len() > MAX_WEBHOOK_URL_LENGTH {
+        return Err(ApiError::InvalidParameter(format!(
+            "url: cannot exceed {MAX_WEBHOOK_URL_LENGTH} characters"
+        )));
+    }
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.has_host() => {
+            Ok(parsed)
+        }
+        _ => Err(ApiError::InvalidParameter(
+            "url: must be an absolute http or https URL".to_string(),
+        )),
+    }
+}
+
+fn webhook_secret(req: &HttpRequest) -> Result<&str, ApiError> {
+    req.headers()
+        .get("X-Webhook-Secret")
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| {
+            ApiError::InvalidParameter("X-Webhook-Secret: header is required".to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::KvStore;
+    use crate::test_support::{sample_store, test_app_state, write};
+    use crate::webhooks::{WebhookAccess, WebhookRegistry};
+    use actix_web::{test as actix_test, App};
+    use std::sync::Arc;
+
+    const OPERATOR: &str = "Bearer operator-token";
+
+    fn test_access() -> WebhookAccess {
+        WebhookAccess {
+            operator_token: Some("operator-token".to_string()),
+            allowed_hosts: ["127.0.0.1".to_string()].into(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_webhook_registrations_are_limited_per_client() {
+        let state = AppState {
+            webhooks: Arc::new(WebhookRegistry::default().with_access(test_access())),
+            ..test_app_state(Arc::new(sample_store()))
+        };
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(create_webhook_handler),
+        )
+        .await;
+
+        let register = |client: &str| {
+            actix_test::TestRequest::post()
+                .uri("/v1/webhooks")
+                .insert_header((header::AUTHORIZATION, OPERATOR))
+                .insert_header(("X-Forwarded-For", client.to_string()))
+                .set_json(serde_json::json!({
+                    "url": "http://127.0.0.1:9/hook",
+                    "filter": {"kind": "key", "accountId": "alice.near",
+                        "contractId": "social.near", "key": "profile/name"},
+                    "secret": "0123456789abcdef",
+                }))
+                .to_request()
+        };
+        for _ in 0..MAX_WEBHOOKS_PER_CLIENT {
+            let resp = actix_test::call_service(&app, register("203.0.113.1")).await;
+            assert_eq!(resp.status(), 201);
+        }
+        let resp = actix_test::call_service(&app, register("203.0.113.1")).await;
+        assert_eq!(resp.status(), 429);
+        let resp = actix_test::call_service(&app, register("203.0.113.2")).await;
+        assert_eq!(resp.status(), 201);
+    }
+
+    #[actix_web::test]
+    async fn test_webhook_signed_delivery_retry_and_dead_letters() {
+        use crate::webhooks::{sign, RetryPolicy};
+        use actix_web::{HttpRequest, HttpResponse};
+
+        // Local receiver: records every POST, fails those to /fail
+        let received = Arc::new(std::sync::Mutex::new(Vec::<[String; 4]>::new()));
+        let server = {
+            let received = received.clone();
+            actix_web::HttpServer::new(move || {
+                let received = received.clone();
+                App::new().default_service(web::to(move |req: HttpRequest, body: web::Bytes| {
+                    let header = |name: &str| {
+                        let value = req.headers().get(name).and_then(|v| v.to_str().ok());
+                        value.unwrap_or_default().to_string()
+                    };
+                    received.lock().unwrap().push([
+                        req.path().to_string(),
+                        header("X-Webhook-Timestamp"),
+                        header("X-Webhook-Signature"),
+                        String::from_utf8_lossy(&body).into_owned(),
+                    ]);
+                    let status = if req.path() == "/fail" { 500 } else { 200 };
+                    async move { HttpResponse::build(status.try_into().unwrap()).finish() }
+                }))
+            })
+            .workers(1)
+            .bind(("127.0.0.1", 0))
+            .unwrap()
+        };
+        let receiver = format!("http://127.0.0.1:{}", server.addrs()[0].port());
+        actix_web::rt::spawn(server.run());
+
+        let dir = std::env::temp_dir().join(format!("fastkv-webhooks-{}", uuid::Uuid::new_v4()));
+        let retry = RetryPolicy {
+            attempts: 2,
+            backoff: std::time::Duration::from_millis(10),
+        };
+        let registry = WebhookRegistry::open(dir.clone(), retry).unwrap();
+        let webhooks = Arc::new(registry.with_access(test_access()));
+        let memory = Arc::new(sample_store());
+        let db: Arc<dyn KvStore> = memory.clone();
+        let state = AppState {
+            webhooks: webhooks.clone(),
+            ..test_app_state(db.clone())
+        };
+        let (store, hub) = (state.store.clone(), state.watch_hub.clone());
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(create_webhook_handler)
+                .service(delete_webhook_handler)
+                .service(webhook_dead_letters_handler),
+        )
+        .await;
+
+        let secret = "0123456789abcdef";
+        let filter = serde_json::json!({"kind": "key", "accountId": "alice.near",
+            "contractId": "social.near", "key": "profile/name"});
+        let body = serde_json::json!({"url": receiver, "filter": filter, "secret": secret});
+        for token in [None, Some("Bearer wrong-token")] {
+            let mut req = actix_test::TestRequest::post().uri("/v1/webhooks");
+            if let Some(token) = token {
+                req = req.insert_header((header::AUTHORIZATION, token));
+            }
+            let resp = actix_test::call_service(&app, req.set_json(&body).to_request()).await;
+            assert_eq!(resp.status(), 401);
+        }
+        // Only the allow-listed 127.0.0.1 may be private, not names resolving to it
+        let localhost = receiver.replace("127.0.0.1", "localhost");
+        for (url, secret) in [
+            ("ftp://example.com/", secret),
+            (receiver.as_str(), "short"),
+            ("http://169.254.169.254/latest/meta-data/", secret),
+            ("http://[::1]/", secret),
+            (localhost.as_str(), secret),
+        ] {
+            let req = actix_test::TestRequest::post()
+                .uri("/v1/webhooks")
+                .insert_header((header::AUTHORIZATION, OPERATOR))
+                .set_json(serde_json::json!({"url": url, "filter": filter, "secret": secret}))
+                .to_request();
+            assert_eq!(actix_test::call_service(&app, req).await.status(), 400);
+        }
+        let mut ids = Vec::new();
+        for path in ["/ok", "/fail"] {
+            let req = actix_test::TestRequest::post()
+                .uri("/v1/webhooks")
+                .insert_header((header::AUTHORIZATION, OPERATOR))
+                .set_json(serde_json::json!({
+                    "url": format!("{receiver}{path}"), "filter": filter, "secret": secret}))
+                .to_request();
+            let resp = actix_test::call_service(&app, req).await;
+            assert_eq!(resp.status(), 201);
+            let body: serde_json::Value = actix_test::read_body_json(resp).await;
+            assert!(body["data"].get("secret").is_none());
+            ids.push(body["data"]["id"].as_str().unwrap().to_string());
+        }
+
+        // Only writes after registration are delivered
+        memory.insert(write("alice.near", "profile/name", "\"Al\"", 140, 0));
+        let dead_letters = format!("/v1/webhooks/{}/dead-letters", ids[1]);
+        let mut letters = serde_json::Value::Null;
+        for _ in 0..500 {
+            hub.poll_round(db.as_ref()).await;
+            let req = actix_test::TestRequest::get()
+                .uri(&dead_letters)
+                .insert_header(("X-Webhook-Secret", secret))
+                .to_request();
+            letters = actix_test::call_and_read_body_json(&app, req).await;
+            if !letters["data"].as_array().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let received = received.lock().unwrap().clone();
+        let paths: Vec<_> = received.iter().map(|[path, ..]| path.as_str()).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(sorted, ["/fail", "/fail", "/ok"]);
+        for [_, timestamp, signature, body] in &received {
+            let timestamp = timestamp.parse().unwrap();
+            assert_eq!(
+                *signature,
+                format!("sha256={}", sign(secret, timestamp, body.as_bytes()))
+            );
+        }
+        let [_, _, _, body] = &received[paths.iter().position(|p| *p == "/ok").unwrap()];
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["webhookId"], ids[0].as_str());
+        assert_eq!(payload["changes"].as_array().unwrap().len(), 1);
+        assert_eq!(payload["changes"][0]["id"], "140:0");
+        assert_eq!(payload["changes"][0]["value"], "\"Al\"");
+
+        assert_eq!(letters["data"].as_array().unwrap().len(), 1);
+        assert_eq!(letters["data"][0]["attempts"], 2);
+        assert_eq!(letters["data"][0]["error"], "HTTP 500");
+        assert_eq!(letters["data"][0]["payload"]["changes"][0]["id"], "140:0");
+        let req = actix_test::TestRequest::get()
+            .uri(&dead_letters)
+            .insert_header(("X-Webhook-Secret", "fedcba9876543210"))
+            .to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 404);
+        let req = actix_test::TestRequest::get()
+            .uri(&dead_letters)
+            .to_request();
+        assert_eq!(actix_test::call_service(&app, req).await.status(), 400);
+
+        for expected in [204, 404] {
+            let req = actix_test::TestRequest::delete()
+                .uri(&format!("/v1/webhooks/{}", ids[0]))
+                .insert_header(("X-Webhook-Secret", secret))
+                .to_request();
+            assert_eq!(actix_test::call_service(&app, req).await.status(), expected);
+        }
+
+        // The remaining webhook persists with its delivery position, and
+        // its dead letters in a log of their own
+        webhooks.flush().await;
+        let log = dir.join("dead_letters").join(format!("{}.jsonl", ids[1]));
+        assert!(log.exists());
+        let saved: serde_json::Value =
+            serde_json::from_slice(&std::fs::read(dir.join("webhooks.json")).unwrap()).unwrap();
+        assert_eq!(saved.as_array().unwrap().len(), 1);
+        assert_eq!(saved[0]["id"], ids[1].as_str());
+        assert_eq!(saved[0]["after"], serde_json::json!([140, 0]));
+        let reopened = Arc::new(WebhookRegistry::open(dir.clone(), retry).unwrap());
+        assert_eq!(reopened.resume(&hub, &store), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
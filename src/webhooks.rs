@@ -0,0 +1,752 @@
+//! Webhook subscriptions: registered filters whose writes are POSTed,
+//! HMAC-signed, to a client URL.
+//!
+//! Registration needs the operator token, and a URL may not point at a
+//! private, loopback or link-local address unless its host is allow-listed.
+//! Delivery re-checks every resolved address and never follows redirects,
+//! so neither DNS rebinding nor a 3xx can reach an internal service.
+//!
+//! Each webhook follows its topic through `WatchHub` like a watch
+//! connection and delivers one payload per batch of writes.  A failed POST
+//! is retried with exponential backoff; a payload that exhausts its attempts
+//! is appended to the webhook's dead-letter log and delivery moves on.  With
+//! a data directory, webhooks and their delivery positions persist in
+//! `webhooks.json`, so a restart resumes after the last write handled.
+//! Saves are debounced and, like dead-letter reads and writes, run on the
+//! blocking pool.
+
+use crate::models::{
+    ApiError, DeadLetter, KvEntry, WatchFilter, WebhookChange, WebhookPayload,
+    MAX_DEAD_LETTER_LOG_BYTES, MAX_WEBHOOKS, MAX_WEBHOOKS_PER_CLIENT, PROJECT_ID,
+    WEBHOOK_DELIVERY_TIMEOUT_SECS, WEBHOOK_SAVE_DEBOUNCE_MILLIS,
+};
+use crate::store::KvStore;
+use crate::watch::{self, Feed, Subscription, Topic, WatchHub, WatchPosition};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tokio::sync::RwLock;
+
+const WEBHOOKS_FILE: &str = "webhooks.json";
+/// Per-webhook dead-letter logs, `{id}.jsonl`, each rotated once to `{id}.jsonl.1`.
+const DEAD_LETTERS_DIR: &str = "dead_letters";
+/// Where an unreadable `webhooks.json` is moved so saves don't overwrite it.
+const CORRUPT_WEBHOOKS_FILE: &str = "webhooks.json.corrupt";
+
+/// A registered webhook, as persisted.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Webhook {
+    pub(crate) id: String,
+    pub(crate) url: String,
+    pub(crate) filter: WatchFilter,
+    pub(crate) secret: String,
+    pub(crate) created_at: String,
+    /// IP of the client that registered it, for the per-client limit.
+    #[serde(default)]
+    pub(crate) client: String,
+    /// Last write delivered or dead-lettered; delivery resumes after it.
+    #[serde(default)]
+    pub(crate) after: Option<WatchPosition>,
+}
+
+/// Delivery attempts per payload; the wait between them doubles from `backoff`.
+#[derive(Clone, Copy)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Who may register webhooks and which hosts may be private.
+#[derive(Clone, Default)]
+pub struct WebhookAccess {
+    /// Bearer token required to register a webhook; `None` disables registration.
+    pub operator_token: Option<String>,
+    /// Hosts, as written in the URL, exempt from the private-address check.
+    pub allowed_hosts: HashSet<String>,
+}
+
+struct Registered {
+    webhook: Webhook,
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// Registered webhooks and their delivery tasks.
+pub struct WebhookRegistry {
+    /// Where webhooks and dead letters persist; `None` keeps webhooks in
+    /// memory and only logs dead letters.
+    dir: Option<PathBuf>,
+    retry: RetryPolicy,
+    access: WebhookAccess,
+    client: reqwest::Client,
+    hooks: Mutex<HashMap<String, Registered>>,
+    /// Set while a debounced save is queued.
+    save_pending: AtomicBool,
+    /// Serializes writes of `webhooks.json`.
+    save_lock: tokio::sync::Mutex<()>,
+    /// Serializes appends and rotations of the dead-letter logs.
+    dead_letter_lock: Arc<Mutex<()>>,
+}
+
+impl WebhookRegistry {
+    /// Registry persisting under `dir`, which is created if missing.
+    pub fn open(dir: PathBuf, retry: RetryPolicy) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir.join(DEAD_LETTERS_DIR))?;
+        Ok(Self {
+            dir: Some(dir),
+            ..Self::in_memory(retry)
+        })
+    }
+
+    /// Registry keeping webhooks in memory only; they are lost on restart.
+    pub fn in_memory(retry: RetryPolicy) -> Self {
+        Self {
+            dir: None,
+            retry: RetryPolicy {
+                attempts: retry.attempts.max(1),
+                backoff: retry.backoff,
+            },
+            access: WebhookAccess::default(),
+            client: delivery_client(&HashSet::new()),
+            hooks: Default::default(),
+            save_pending: Default::default(),
+            save_lock: Default::default(),
+            dead_letter_lock: Default::default(),
+        }
+    }
+
+    /// Require `access.operator_token` to register and exempt its hosts from
+    /// the private-address check.
+    pub fn with_access(self, access: WebhookAccess) -> Self {
+        Self {
+            client: delivery_client(&access.allowed_hosts),
+            access,
+            ..self
+        }
+    }
+
+    /// Check the `Authorization: Bearer` token against the operator token.
+    pub(crate) fn authorize(&self, token: Option<&str>) -> Result<(), ApiError> {
+        let Some(expected) = &self.access.operator_token else {
+            return Err(ApiError::NotImplemented(
+                "webhook registration is disabled on this deployment".to_string(),
+            ));
+        };
+        match token {
+            Some(token) if secrets_match(expected, token) => Ok(()),
+            _ => Err(ApiError::Unauthorized(
+                "a valid operator token is required".to_string(),
+            )),
+        }
+    }
+
+    /// Reject a URL whose host is, or resolves to, a non-public address,
+    /// unless the host is allow-listed.
+    pub(crate) async fn check_destination(&self, url: &reqwest::Url) -> Result<(), ApiError> {
+        let host = url.host_str().unwrap_or_default();
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if self.access.allowed_hosts.contains(host) {
+            return Ok(());
+        }
+        let addrs: Vec<IpAddr> = match host.parse() {
+            Ok(ip) => vec![ip],
+            Err(_) => {
+                let port = url.port_or_known_default().unwrap_or(80);
+                match tokio::net::lookup_host((host, port)).await {
+                    Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+                    Err(_) => Vec::new(),
+                }
+            }
+        };
+        if addrs.is_empty() {
+            return Err(ApiError::InvalidParameter(
+                "url: host does not resolve".to_string(),
+            ));
+        }
+        if !addrs.into_iter().all(is_public) {
+            return Err(ApiError::InvalidParameter(
+                "url: must not point at a private, loopback or link-local address".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Restart delivery for every persisted webhook.  Returns how many resumed.
+    ///
+    /// An unreadable `webhooks.json` is logged and moved aside, so startup
+    /// continues with no webhooks rather than failing.
+    pub fn resume(
+        self: &Arc<Self>,
+        hub: &Arc<WatchHub>,
+        store: &Arc<RwLock<Option<Arc<dyn KvStore>>>>,
+    ) -> usize {
+        let Some(path) = self.path(WEBHOOKS_FILE) else {
+            return 0;
+        };
+        let loaded = std::fs::read(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| Ok(serde_json::from_slice::<Vec<Webhook>>(&json)?));
+        let webhooks = match loaded {
+            Ok(webhooks) => webhooks,
+            Err(e) if is_not_found(&e) => return 0,
+            Err(e) => {
+                tracing::warn!(target: PROJECT_ID, path = %path.display(), error = %e, "Skipping unreadable webhooks file");
+                if let Some(aside) = self.path(CORRUPT_WEBHOOKS_FILE) {
+                    if let Err(e) = std::fs::rename(&path, &aside) {
+                        tracing::error!(target: PROJECT_ID, error = %e, "Failed to move unreadable webhooks file aside");
+                    }
+                }
+                return 0;
+            }
+        };
+        let mut resumed = 0;
+        for webhook in webhooks {
+            match Topic::from_filter(webhook.filter.clone(), "filter") {
+                Ok(topic) => {
+                    self.spawn(webhook, topic, hub, store);
+                    resumed += 1;
+                }
+                Err(e) => {
+                    tracing::warn!(target: PROJECT_ID, id = %webhook.id, error = %e, "Skipping invalid webhook");
+                }
+            }
+        }
+        resumed
+    }
+
+    /// Register `webhook` and start delivering writes after `webhook.after`.
+    pub(crate) fn register(
+        self: &Arc<Self>,
+        webhook: Webhook,
+        topic: Topic,
+        hub: &Arc<WatchHub>,
+        store: &Arc<RwLock<Option<Arc<dyn KvStore>>>>,
+    ) -> Result<(), ApiError> {
+        {
+            let hooks = self.hooks.lock().unwrap_or_else(|e| e.into_inner());
+            if hooks.len() >= MAX_WEBHOOKS {
+                return Err(ApiError::TooManyRequests(
+                    "Too many registered webhooks".to_string(),
+                ));
+            }
+            let by_client = hooks
+                .values()
+                .filter(|r| r.webhook.client == webhook.client)
+                .count();
+            if by_client >= MAX_WEBHOOKS_PER_CLIENT {
+                return Err(ApiError::TooManyRequests(
+                    "Too many webhooks registered by this client".to_string(),
+                ));
+            }
+        }
+        self.spawn(webhook, topic, hub, store);
+        self.save();
+        Ok(())
+    }
+
+    /// Stop and forget a webhook, deleting its dead letters.
+    pub(crate) fn remove(self: &Arc<Self>, id: &str, secret: &str) -> Result<(), ApiError> {
+        let mut hooks = self.hooks.lock().unwrap_or_else(|e| e.into_inner());
+        match hooks.get(id) {
+            Some(registered) if secrets_match(&registered.webhook.secret, secret) => {}
+            _ => return Err(ApiError::NotFound("webhook".to_string())),
+        }
+        if let Some(registered) = hooks.remove(id) {
+            registered.task.abort();
+        }
+        drop(hooks);
+        self.save();
+        if let Some(path) = self.dead_letter_path(id) {
+            tokio::task::spawn_blocking(move || {
+                let _ = std::fs::remove_file(rotated(&path));
+                let _ = std::fs::remove_file(path);
+            });
+        }
+        Ok(())
+    }
+
+    /// A webhook's newest dead letters, newest first, and whether older ones remain.
+    pub(crate) async fn dead_letters(
+        &self,
+        id: &str,
+        secret: &str,
+        limit: usize,
+    ) -> Result<(Vec<DeadLetter>, bool), ApiError> {
+        match self.hooks.lock().unwrap_or_else(|e| e.into_inner()).get(id) {
+            Some(registered) if secrets_match(&registered.webhook.secret, secret) => {}
+            _ => return Err(ApiError::NotFound("webhook".to_string())),
+        }
+        let Some(path) = self.dead_letter_path(id) else {
+            return Ok((Vec::new(), false));
+        };
+        let read = tokio::task::spawn_blocking(move || read_dead_letters(&path, limit))
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        Ok(read.map_err(anyhow::Error::from)?)
+    }
+
+    fn spawn(
+        self: &Arc<Self>,
+        webhook: Webhook,
+        topic: Topic,
+        hub: &Arc<WatchHub>,
+        store: &Arc<RwLock<Option<Arc<dyn KvStore>>>>,
+    ) {
+        let task = tokio::spawn(Arc::clone(self).deliver_changes(
+            webhook.clone(),
+            topic,
+            Arc::clone(hub),
+            Arc::clone(store),
+        ));
+        let id = webhook.id.clone();
+        if let Some(replaced) = self
+            .hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(id, Registered { webhook, task })
+        {
+            replaced.task.abort();
+        }
+    }
+
+    /// Follow the webhook's topic, delivering each batch of new writes.
+    async fn deliver_changes(
+        self: Arc<Self>,
+        webhook: Webhook,
+        topic: Topic,
+        hub: Arc<WatchHub>,
+        store: Arc<RwLock<Option<Arc<dyn KvStore>>>>,
+    ) {
+        let mut rounds = hub.rounds();
+        // Join the topic once the store is reachable
+        let mut feed: Feed = loop {
+            let db = store.read().await.clone();
+            if let Some(db) = db {
+                let subscription = Subscription::new(topic.clone(), webhook.after);
+                match hub.subscribe(subscription, db.as_ref()).await {
+                    Ok(feed) => break feed,
+                    Err(e) => {
+                        tracing::warn!(target: PROJECT_ID, id = %webhook.id, error = %e, "Webhook subscribe error");
+                    }
+                }
+            }
+            if rounds.changed().await.is_err() {
+                return;
+            }
+        };
+
+        // Catch up from the store first, then deliver after each round
+        let mut wait = false;
+        loop {
+            if wait && rounds.changed().await.is_err() {
+                return;
+            }
+            let db = store.read().await.clone();
+            let Some(db) = db else {
+                wait = true;
+                continue;
+            };
+            let (entries, failed) =
+                watch::drain_all(std::slice::from_mut(&mut feed), db.as_ref()).await;
+            wait = failed || feed.is_live();
+            if entries.is_empty() {
+                continue;
+            }
+            self.deliver(&webhook, entries.into_iter().map(|(_, e)| e).collect())
+                .await;
+            if let Some(position) = feed.position() {
+                self.advance(&webhook.id, position);
+            }
+        }
+    }
+
+    /// POST one payload, retrying with backoff, then dead-letter it.
+    async fn deliver(&self, webhook: &Webhook, entries: Vec<KvEntry>) {
+        let payload = WebhookPayload {
+            webhook_id: webhook.id.clone(),
+            changes: entries
+                .into_iter()
+                .map(|entry| WebhookChange {
+                    id: watch::watch_event_id(&entry),
+                    event: entry.into(),
+                })
+                .collect(),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!(target: PROJECT_ID, id = %webhook.id, error = %e, "Webhook payload serialization error");
+                return;
+            }
+        };
+
+        let mut backoff = self.retry.backoff;
+        let mut error = String::new();
+        for attempt in 1..=self.retry.attempts {
+            match self.post(webhook, &body).await {
+                Ok(()) => return,
+                Err(e) => error = e,
+            }
+            if attempt < self.retry.attempts {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+        }
+
+        tracing::warn!(target: PROJECT_ID, id = %webhook.id, %error, "Webhook delivery failed, dead-lettering");
+        self.dead_letter(DeadLetter {
+            webhook_id: webhook.id.clone(),
+            failed_at: chrono::Utc::now().to_rfc3339(),
+            attempts: self.retry.attempts,
+            error,
+            payload: serde_json::to_value(&payload).unwrap_or_default(),
+        })
+        .await;
+    }
+
+    async fn post(&self, webhook: &Webhook, body: &[u8]) -> Result<(), String> {
+        let timestamp = chrono::Utc::now().timestamp();
+        let response = self
+            .client
+            .post(&webhook.url)
+            .timeout(Duration::from_secs(WEBHOOK_DELIVERY_TIMEOUT_SECS))
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Webhook-Id", &webhook.id)
+            .header("X-Webhook-Timestamp", timestamp)
+            .header(
+                "X-Webhook-Signature",
+                format!("sha256={}", sign(&webhook.secret, timestamp, body)),
+            )
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("HTTP {}", response.status().as_u16()))
+        }
+    }
+
+    fn advance(self: &Arc<Self>, id: &str, position: WatchPosition) {
+        match self
+            .hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(id)
+        {
+            Some(registered) => registered.webhook.after = Some(position),
+            None => return,
+        }
+        self.save();
+    }
+
+    /// Append to the webhook's dead-letter log, first rotating it once it
+    /// reaches `MAX_DEAD_LETTER_LOG_BYTES`.
+    async fn dead_letter(&self, letter: DeadLetter) {
+        let Some(path) = self.dead_letter_path(&letter.webhook_id) else {
+            return;
+        };
+        let Ok(mut line) = serde_json::to_vec(&letter) else {
+            return;
+        };
+        line.push(b'\n');
+        let lock = Arc::clone(&self.dead_letter_lock);
+        let appended = tokio::task::spawn_blocking(move || {
+            let _lock = lock.lock().unwrap_or_else(|e| e.into_inner());
+            if std::fs::metadata(&path).is_ok_and(|m| m.len() >= MAX_DEAD_LETTER_LOG_BYTES) {
+                std::fs::rename(&path, rotated(&path))?;
+            }
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| file.write_all(&line))
+        })
+        .await
+        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+        if let Err(e) = appended {
+            tracing::error!(target: PROJECT_ID, error = %e, "Failed to append dead letter");
+        }
+    }
+
+    /// Queue a rewrite of `webhooks.json`.  Changes within
+    /// `WEBHOOK_SAVE_DEBOUNCE_MILLIS` of the first share one write.
+    fn save(self: &Arc<Self>) {
+        if self.dir.is_none() || self.save_pending.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let registry = Arc::clone(self);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(WEBHOOK_SAVE_DEBOUNCE_MILLIS)).await;
+            registry.flush().await;
+        });
+    }
+
+    /// Rewrite `webhooks.json` from the registry now, via a temp file and rename.
+    pub async fn flush(&self) {
+        let Some(path) = self.path(WEBHOOKS_FILE) else {
+            return;
+        };
+        // Each write snapshots the registry only once it holds the lock, so
+        // the file always ends up with the newest snapshot
+        let _lock = self.save_lock.lock().await;
+        self.save_pending.store(false, Ordering::Release);
+        let json = {
+            let hooks = self.hooks.lock().unwrap_or_else(|e| e.into_inner());
+            let webhooks: Vec<&Webhook> = hooks.values().map(|r| &r.webhook).collect();
+            serde_json::to_vec_pretty(&webhooks)
+        };
+        let saved = match json {
+            Ok(json) => tokio::task::spawn_blocking(move || {
+                let tmp = path.with_extension("json.tmp");
+                std::fs::write(&tmp, json)?;
+                std::fs::rename(&tmp, &path)
+            })
+            .await
+            .unwrap_or_else(|e| Err(std::io::Error::other(e))),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = saved {
+            tracing::error!(target: PROJECT_ID, error = %e, "Failed to persist webhooks");
+        }
+    }
+
+    fn dead_letter_path(&self, id: &str) -> Option<PathBuf> {
+        self.path(DEAD_LETTERS_DIR)
+            .map(|dir| dir.join(format!("{id}.jsonl")))
+    }
+
+    fn path(&self, file: &str) -> Option<PathBuf> {
+        self.dir.as_ref().map(|dir| dir.join(file))
+    }
+}
+
+impl Default for WebhookRegistry {
+    fn default() -> Self {
+        Self::in_memory(RetryPolicy::default())
+    }
+}
+
+/// HTTP client for deliveries: no redirects, and hosts resolve only to
+/// public addresses unless allow-listed.
+fn delivery_client(allowed_hosts: &HashSet<String>) -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .dns_resolver(Arc::new(PublicResolver {
+            allowed_hosts: Arc::new(allowed_hosts.clone()),
+        }))
+        .build()
+        .expect("webhook HTTP client configuration is valid")
+}
+
+/// Resolver dropping non-public addresses, so a host that passed
+/// registration can't later be rebound to an internal service.
+struct PublicResolver {
+    allowed_hosts: Arc<HashSet<String>>,
+}
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        let allowed = self.allowed_hosts.contains(name.as_str());
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| allowed || is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{host} has no public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// Whether `ip` is publicly routable: not private, loopback, link-local
+/// (which covers the 169.254.169.254 metadata service) or otherwise reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || a == 0
+                || a >= 240
+                || (a == 100 && (64..128).contains(&b))
+                || (a == 198 && (18..20).contains(&b)))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Compare credentials in constant time, so response timing doesn't reveal
+/// how long a matching prefix a guess has.
+fn secrets_match(expected: &str, given: &str) -> bool {
+    expected.as_bytes().ct_eq(given.as_bytes()).into()
+}
+
+/// Where a full dead-letter log is moved, replacing the previous one.
+fn rotated(path: &Path) -> PathBuf {
+    path.with_extension("jsonl.1")
+}
+
+/// The newest `limit` dead letters in a log and its rotated predecessor,
+/// newest first, and whether older ones remain.
+fn read_dead_letters(path: &Path, limit: usize) -> std::io::Result<(Vec<DeadLetter>, bool)> {
+    let mut letters = Vec::new();
+    for file in [path.to_path_buf(), rotated(path)] {
+        let log = match std::fs::read_to_string(&file) {
+            Ok(log) => log,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let wanted = limit + 1 - letters.len();
+        letters.extend(
+            log.lines()
+                .rev()
+                .filter_map(|line| serde_json::from_str::<DeadLetter>(line).ok())
+                .take(wanted),
+        );
+        if letters.len() > limit {
+            break;
+        }
+    }
+    let has_more = letters.len() > limit;
+    letters.truncate(limit);
+    Ok((letters, has_more))
+}
+
+fn is_not_found(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}` under the webhook secret.
+pub(crate) fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_covers_timestamp_and_body() {
+        let signature = sign("0123456789abcdef", 1700000000, b"{}");
+        assert_eq!(signature.len(), 64);
+        assert_eq!(signature, sign("0123456789abcdef", 1700000000, b"{}"));
+        assert_ne!(signature, sign("0123456789abcdef", 1700000001, b"{}"));
+        assert_ne!(signature, sign("0123456789abcdef", 1700000000, b"[]"));
+        assert_ne!(signature, sign("fedcba9876543210", 1700000000, b"{}"));
+    }
+
+    #[test]
+    fn test_is_public_rejects_internal_addresses() {
+        for ip in ["8.8.8.8", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_log_rotates_at_its_size_bound() {
+        let dir = std::env::temp_dir().join(format!("fastkv-webhooks-{}", uuid::Uuid::new_v4()));
+        let registry = WebhookRegistry::open(dir.clone(), RetryPolicy::default()).unwrap();
+        let padding = "x".repeat(64 * 1024);
+        for i in 0..40 {
+            let letter = DeadLetter {
+                webhook_id: "hook".to_string(),
+                failed_at: String::new(),
+                attempts: 1,
+                error: i.to_string(),
+                payload: serde_json::Value::String(padding.clone()),
+            };
+            registry.dead_letter(letter).await;
+        }
+
+        // Only the live log and one rotation remain
+        let path = registry.dead_letter_path("hook").unwrap();
+        let size = |path: &Path| std::fs::metadata(path).unwrap().len();
+        assert!(size(&path) <= MAX_DEAD_LETTER_LOG_BYTES + padding.len() as u64 * 2);
+        assert!(size(&rotated(&path)) <= MAX_DEAD_LETTER_LOG_BYTES + padding.len() as u64 * 2);
+        let (letters, has_more) = read_dead_letters(&path, 100).unwrap();
+        assert!(!has_more);
+        assert!(letters.len() < 40);
+        let errors: Vec<String> = letters.iter().map(|l| l.error.clone()).collect();
+        let expected: Vec<String> = (40 - letters.len()..40)
+            .rev()
+            .map(|i| i.to_string())
+            .collect();
+        assert_eq!(errors, expected);
+
+        let (letters, has_more) = read_dead_letters(&path, 3).unwrap();
+        assert!(has_more);
+        assert_eq!(letters[2].error, "37");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_resume_skips_unreadable_webhooks_file() {
+        let dir = std::env::temp_dir().join(format!("fastkv-webhooks-{}", uuid::Uuid::new_v4()));
+        let registry =
+            Arc::new(WebhookRegistry::open(dir.clone(), RetryPolicy::default()).unwrap());
+        let (hub, store) = (Arc::new(WatchHub::default()), Arc::new(RwLock::new(None)));
+        assert_eq!(registry.resume(&hub, &store), 0);
+
+        std::fs::write(dir.join(WEBHOOKS_FILE), "{not json").unwrap();
+        assert_eq!(registry.resume(&hub, &store), 0);
+        assert!(!dir.join(WEBHOOKS_FILE).exists());
+        let aside = std::fs::read_to_string(dir.join(CORRUPT_WEBHOOKS_FILE)).unwrap();
+        assert_eq!(aside, "{not json");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,389 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::Message;
+
+use crate::handlers::{claim_watch_slot, require_db, validate_key, WatchGuard};
+use crate::models::*;
+use crate::store::KvStore;
+use crate::watch::{self, Feed, Subscription, Topic, WatchHub};
+use crate::AppState;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Watch keys, prefixes, edge targets and social index keys over a WebSocket.
+///
+/// Clients send JSON `subscribe`/`unsubscribe` messages (`WsClientMessage`)
+/// and receive acks, errors, `change` events and heartbeats
+/// (`WsServerMessage`).  Subscriptions share `WatchHub` pollers with SSE
+/// watches; the socket takes one `MAX_CONCURRENT_WATCHES` slot.
+#[utoipa::path(
+    get,
+    path = "/v1/ws",
+    responses(
+        (status = 101, description = "WebSocket upgrade; speaks WsClientMessage / WsServerMessage"),
+        (status = 400, description = "Not a WebSocket handshake", body = ErrorResponse),
+        (status = 429, description = "Too many watch connections", body = ErrorResponse),
+        (status = 503, description = "Database unavailable", body = ErrorResponse),
+    ),
+    tag = "kv"
+)]
+#[get("/v1/ws")]
+pub async fn ws_handler(
+    req: HttpRequest,
+    body: web::Payload,
+    app_state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let guard = claim_watch_slot(&app_state)?;
+    require_db(&app_state).await?;
+    let (response, session, messages) = actix_ws::handle(&req, body)
+        .map_err(|e| ApiError::InvalidParameter(format!("upgrade: {e}")))?;
+
+    tracing::info!(target: PROJECT_ID, "GET /v1/ws (WebSocket)");
+
+    actix_web::rt::spawn(run_session(
+        session,
+        messages,
+        app_state.watch_hub.clone(),
+        app_state.store.clone(),
+        guard,
+    ));
+    Ok(response)
+}
+
+async fn run_session(
+    mut session: actix_ws::Session,
+    mut messages: actix_ws::MessageStream,
+    hub: Arc<WatchHub>,
+    store: Arc<tokio::sync::RwLock<Option<Arc<dyn KvStore>>>>,
+    _guard: WatchGuard,
+) {
+    let mut subscriptions = WsSubscriptions::default();
+    let mut rounds = hub.rounds();
+    let mut heartbeat_interval = tokio::time::interval(Duration::from_secs(SSE_HEARTBEAT_SECS));
+    let mut catch_up = false;
+
+    loop {
+        let mut replies = Vec::new();
+        let mut drain = false;
+        tokio::select! {
+            message = messages.recv() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    // Clone the Arc so the RwLock is not held across .await
+                    let db = store.read().await.clone();
+                    replies = match db {
+                        Some(db) => subscriptions.handle(&text, &hub, db.as_ref()).await,
+                        None => vec![WsServerMessage::Error {
+                            id: None,
+                            error: "database_unavailable".to_string(),
+                        }],
+                    };
+                    catch_up = subscriptions.catching_up();
+                }
+                Some(Ok(Message::Ping(bytes))) => {
+                    if session.pong(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            changed = rounds.changed(), if !catch_up => {
+                if changed.is_err() {
+                    break;
+                }
+                drain = true;
+            }
+            // Subscriptions behind their topic channel read the store right away
+            _ = std::future::ready(()), if catch_up => drain = true,
+            _ = heartbeat_interval.tick() => replies.push(WsServerMessage::Heartbeat),
+        }
+
+        if drain {
+            let db = store.read().await.clone();
+            if let Some(ref db) = db {
+                let (changes, failed) = subscriptions.drain(db.as_ref()).await;
+                replies.extend(changes);
+                catch_up = !failed && subscriptions.catching_up();
+                if failed {
+                    replies.push(WsServerMessage::Error {
+                        id: None,
+                        error: "poll_failed".to_string(),
+                    });
+                }
+            } else {
+                catch_up = false;
+                replies.push(WsServerMessage::Error {
+                    id: None,
+                    error: "database_unavailable".to_string(),
+                });
+            }
+        }
+
+        for reply in replies {
+            let Ok(text) = serde_json::to_string(&reply) else {
+                continue;
+            };
+            if session.text(text).await.is_err() {
+                return;
+            }
+        }
+    }
+    let _ = session.close(None).await;
+}
+
+/// Subscriptions held by one socket, by client-chosen id.
+#[derive(Default)]
+pub(crate) struct WsSubscriptions {
+    ids: Vec<String>,
+    feeds: Vec<Feed>,
+}
+
+impl WsSubscriptions {
+    /// Apply one client message.  Replies with an ack (followed by a key's
+    /// opening value) or an error; a bad message never closes the socket.
+    pub(crate) async fn handle(
+        &mut self,
+        text: &str,
+        hub: &WatchHub,
+        db: &dyn KvStore,
+    ) -> Vec<WsServerMessage> {
+        let message = match serde_json::from_str::<WsClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                return vec![WsServerMessage::Error {
+                    id: None,
+                    error: format!("invalid message: {e}"),
+                }];
+            }
+        };
+        match message {
+            WsClientMessage::Subscribe {
+                id,
+                topic,
+                from_block,
+            } => match self.subscribe(&id, topic, from_block, hub, db).await {
+                Ok(initial) => {
+                    let mut replies = vec![WsServerMessage::Ack {
+                        id: id.clone(),
+                        op: "subscribe".to_string(),
+                    }];
+                    replies.extend(initial.into_iter().map(|entry| WsServerMessage::Change {
+                        id: id.clone(),
+                        event: entry.into(),
+                    }));
+                    replies
+                }
+                Err(e) => vec![WsServerMessage::Error {
+                    id: Some(id),
+                    error: e.to_string(),
+                }],
+            },
+            WsClientMessage::Unsubscribe { id } => {
+                match self.ids.iter().position(|existing| *existing == id) {
+                    Some(index) => {
+                        self.ids.remove(index);
+                        self.feeds.remove(index);
+                        vec![WsServerMessage::Ack {
+                            id,
+                            op: "unsubscribe".to_string(),
+                        }]
+                    }
+                    None => vec![WsServerMessage::Error {
+                        id: Some(id),
+                        error: "Invalid parameter: id: not subscribed".to_string(),
+                    }],
+                }
+            }
+        }
+    }
+
+    async fn subscribe(
+        &mut self,
+        id: &str,
+        topic: WatchFilter,
+        from_block: Option<i64>,
+        hub: &WatchHub,
+        db: &dyn KvStore,
+    ) -> Result<Vec<KvEntry>, ApiError> {
+        validate_key(id, "id", MAX_KEY_LENGTH)?;
+        if self.ids.iter().any(|existing| existing == id) {
+            return Err(ApiError::InvalidParameter(
+                "id: already subscribed".to_string(),
+            ));
+        }
+        if self.ids.len() >= MAX_WATCH_KEYS {
+            return Err(ApiError::InvalidParameter(format!(
+                "id: cannot exceed {MAX_WATCH_KEYS} subscriptions"
+            )));
+        }
+        if matches!(from_block, Some(b) if b < 0) {
+            return Err(ApiError::InvalidParameter(
+                "from_block: must be non-negative".to_string(),
+            ));
+        }
+        let topic = Topic::from_filter(topic, "topic")?;
+
+        // Resume just before `from_block`; block 0 means from the first write
+        let after = from_block.filter(|b| *b > 0).map(|b| (b - 1, i64::MAX));
+        let mut subscription = Subscription::new(topic, after);
+        let initial = match from_block {
+            Some(_) => Vec::new(),
+            None => subscription.start(db).await?,
+        };
+        let feed = hub.subscribe(subscription, db).await?;
+        self.ids.push(id.to_string());
+        self.feeds.push(feed);
+        Ok(initial)
+    }
+
+    /// New writes across all subscriptions, in write order.
+    pub(crate) async fn drain(&mut self, db: &dyn KvStore) -> (Vec<WsServerMessage>, bool) {
+        let (entries, failed) = watch::drain_all(&mut self.feeds, db).await;
+        let changes = entries
+            .into_iter()
+            .map(|(index, entry)| WsServerMessage::Change {
+                id: self.ids[index].clone(),
+                event: entry.into(),
+            })
+            .collect();
+        (changes, failed)
+    }
+
+    /// Whether any subscription still reads the store to catch up.
+    pub(crate) fn catching_up(&self) -> bool {
+        self.feeds.iter().any(|f| !f.is_live())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{sample_store, test_app_state, write};
+    use actix_web::{test as actix_test, App};
+
+    #[tokio::test]
+    async fn test_ws_subscriptions_ack_resume_and_fan_out() {
+        let memory = Arc::new(sample_store());
+        let db: Arc<dyn KvStore> = memory.clone();
+        let hub = crate::watch::WatchHub::default();
+        let mut subs = WsSubscriptions::default();
+        let send = async |subs: &mut WsSubscriptions, msg: serde_json::Value| {
+            let replies = subs.handle(&msg.to_string(), &hub, db.as_ref()).await;
+            serde_json::to_value(replies).unwrap()
+        };
+
+        // A key opens with its current value
+        let replies = send(
+            &mut subs,
+            serde_json::json!({"type": "subscribe", "id": "name", "topic": {
+                "kind": "key", "accountId": "alice.near", "contractId": "social.near",
+                "key": "profile/name"}}),
+        )
+        .await;
+        assert_eq!(
+            replies[0],
+            serde_json::json!({"type": "ack", "id": "name", "op": "subscribe"})
+        );
+        assert_eq!(replies[1]["type"], "change");
+        assert_eq!(replies[1]["event"]["blockHeight"], 120);
+
+        // Edge targets resume from a block height, across writers
+        let replies = send(
+            &mut subs,
+            serde_json::json!({"type": "subscribe", "id": "followers", "from_block": 100,
+                "topic": {"kind": "edge", "edge_type": "graph/follow", "target": "bob.near"}}),
+        )
+        .await;
+        assert_eq!(replies.as_array().unwrap().len(), 1);
+        let (changes, failed) = subs.drain(db.as_ref()).await;
+        assert!(!failed);
+        let changes = serde_json::to_value(changes).unwrap();
+        let writers: Vec<_> = changes
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| format!("{}/{}", c["id"], c["event"]["accountId"]).replace('"', ""))
+            .collect();
+        assert_eq!(writers, ["followers/alice.near", "followers/carol.near"]);
+        assert!(!subs.catching_up());
+
+        for (msg, error) in [
+            (
+                serde_json::json!({"type": "subscribe", "id": "name", "topic": {
+                    "kind": "social_index", "action": "like", "key": "x"}}),
+                "Invalid parameter: id: already subscribed",
+            ),
+            (
+                serde_json::json!({"type": "unsubscribe", "id": "nope"}),
+                "Invalid parameter: id: not subscribed",
+            ),
+        ] {
+            let replies = send(&mut subs, msg).await;
+            assert_eq!(replies[0]["type"], "error");
+            assert_eq!(replies[0]["error"], error);
+        }
+        let replies = send(&mut subs, serde_json::json!({"type": "bogus"})).await;
+        assert_eq!(replies[0]["type"], "error");
+        assert!(replies[0].get("id").is_none());
+
+        // Live writes arrive through the hub, tagged with their subscription
+        let replies = send(
+            &mut subs,
+            serde_json::json!({"type": "subscribe", "id": "likes", "topic": {
+                "kind": "social_index", "action": "like", "key": "x"}}),
+        )
+        .await;
+        assert_eq!(replies.as_array().unwrap().len(), 1);
+        subs.drain(db.as_ref()).await;
+        memory.insert(write("alice.near", "profile/name", "\"Al\"", 140, 0));
+        memory.insert(write("dave.near", "graph/follow/bob.near", "\"\"", 141, 0));
+        memory.insert(write("erin.near", "index/like/x", "{}", 142, 0));
+        assert_eq!(hub.poll_round(db.as_ref()).await, 3);
+        let (changes, _) = subs.drain(db.as_ref()).await;
+        let ids: Vec<_> = serde_json::to_value(changes)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| c["id"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(ids, ["name", "followers", "likes"]);
+
+        let replies = send(
+            &mut subs,
+            serde_json::json!({"type": "unsubscribe", "id": "name"}),
+        )
+        .await;
+        assert_eq!(
+            replies[0],
+            serde_json::json!({"type": "ack", "id": "name", "op": "unsubscribe"})
+        );
+        assert_eq!(hub.poll_round(db.as_ref()).await, 2);
+    }
+
+    #[actix_web::test]
+    async fn test_ws_handshake() {
+        let store: Arc<dyn KvStore> = Arc::new(sample_store());
+        let state = test_app_state(store);
+        let app = actix_test::init_service(
+            App::new()
+                .app_data(web::Data::new(state))
+                .service(ws_handler),
+        )
+        .await;
+
+        let req = actix_test::TestRequest::get()
+            .uri("/v1/ws")
+            .insert_header(("Upgrade", "websocket"))
+            .insert_header(("Connection", "Upgrade"))
+            .insert_header(("Sec-WebSocket-Version", "13"))
+            .insert_header(("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ=="))
+            .to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 101);
+
+        let req = actix_test::TestRequest::get().uri("/v1/ws").to_request();
+        let resp = actix_test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+    }
+}